@@ -30,3 +30,206 @@ impl FloatExt for f32 {
         }
     }
 }
+
+/// Easing functions mapping linear progress in `[0, 1]` to eased progress
+pub mod ease {
+    /// An easing function
+    pub type Easing = fn(f32) -> f32;
+
+    #[inline]
+    pub fn linear(t: f32) -> f32 {
+        t
+    }
+    #[inline]
+    pub fn quad_in(t: f32) -> f32 {
+        t * t
+    }
+    #[inline]
+    pub fn quad_out(t: f32) -> f32 {
+        t * (2. - t)
+    }
+    pub fn quad_in_out(t: f32) -> f32 {
+        if t < 0.5 {
+            2. * t * t
+        } else {
+            -1. + (4. - 2. * t) * t
+        }
+    }
+    #[inline]
+    pub fn cubic_out(t: f32) -> f32 {
+        let t = t - 1.;
+        t * t * t + 1.
+    }
+    /// Overshoots a bit before settling
+    pub fn back_out(t: f32) -> f32 {
+        const C: f32 = 1.70158;
+        let t = t - 1.;
+        t * t * ((C + 1.) * t + C) + 1.
+    }
+}
+
+use self::ease::Easing;
+
+#[derive(Debug, Clone, Copy)]
+/// A value that is interpolated towards a target over a fixed duration
+///
+/// Progress is advanced in seconds so animations run at the same speed regardless of frame rate
+pub struct Tween {
+    from: f32,
+    to: f32,
+    elapsed: f32,
+    duration: f32,
+    easing: Easing,
+}
+
+impl Tween {
+    /// Makes a tween resting at `value`
+    pub fn new(value: f32, duration: f32, easing: Easing) -> Self {
+        Tween {
+            from: value,
+            to: value,
+            elapsed: duration,
+            duration,
+            easing,
+        }
+    }
+    /// Starts moving towards `to` from wherever the value is right now
+    pub fn set_target(&mut self, to: f32) {
+        if (to - self.to).abs() > std::f32::EPSILON {
+            self.from = self.value();
+            self.to = to;
+            self.elapsed = 0.;
+        }
+    }
+    /// Jumps straight to `value` without animating
+    pub fn snap(&mut self, value: f32) {
+        self.from = value;
+        self.to = value;
+        self.elapsed = self.duration;
+    }
+    /// Advances the animation by `dt` seconds
+    #[inline]
+    pub fn update(&mut self, dt: f32) {
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+    }
+    #[inline]
+    pub fn target(&self) -> f32 {
+        self.to
+    }
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+    /// The current interpolated value
+    pub fn value(&self) -> f32 {
+        if self.is_done() {
+            self.to
+        } else {
+            let t = (self.easing)(self.elapsed / self.duration);
+            self.from + (self.to - self.from) * t
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Counts down from a duration in seconds
+pub struct Timer {
+    duration: f32,
+    left: f32,
+}
+
+impl Timer {
+    /// Makes a timer that has already run out
+    #[inline]
+    pub fn new(duration: f32) -> Self {
+        Timer {
+            duration,
+            left: 0.,
+        }
+    }
+    /// Makes a timer that starts counting down right away
+    #[inline]
+    pub fn started(duration: f32) -> Self {
+        Timer {
+            duration,
+            left: duration,
+        }
+    }
+    #[inline]
+    pub fn restart(&mut self) {
+        self.left = self.duration;
+    }
+    #[inline]
+    pub fn set_duration(&mut self, duration: f32) {
+        self.duration = duration;
+    }
+    /// Counts down by `dt` seconds, returning whether it ran out during this update
+    pub fn update(&mut self, dt: f32) -> bool {
+        if self.left > 0. {
+            self.left -= dt;
+            self.left <= 0.
+        } else {
+            false
+        }
+    }
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.left <= 0.
+    }
+    /// Seconds left
+    #[inline]
+    pub fn left(&self) -> f32 {
+        self.left.max(0.)
+    }
+    /// How far it has counted, from 0 when just started to 1 when done
+    #[inline]
+    pub fn progress(&self) -> f32 {
+        if self.duration > 0. {
+            1. - self.left() / self.duration
+        } else {
+            1.
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Something that can only happen once in a while, like firing or spawning
+pub struct Cooldown {
+    timer: Timer,
+}
+
+impl Cooldown {
+    /// Makes a cooldown of `duration` seconds that is ready right away
+    #[inline]
+    pub fn new(duration: f32) -> Self {
+        Cooldown {
+            timer: Timer::new(duration),
+        }
+    }
+    #[inline]
+    pub fn update(&mut self, dt: f32) {
+        self.timer.update(dt);
+    }
+    #[inline]
+    pub fn is_ready(&self) -> bool {
+        self.timer.is_done()
+    }
+    /// Starts the cooldown if it's ready, returning whether it was
+    pub fn trigger(&mut self) -> bool {
+        if self.is_ready() {
+            self.timer.restart();
+            true
+        } else {
+            false
+        }
+    }
+    #[inline]
+    pub fn set_duration(&mut self, duration: f32) {
+        self.timer.set_duration(duration);
+    }
+    /// How far along the cooldown is, 1 meaning ready
+    #[inline]
+    pub fn progress(&self) -> f32 {
+        self.timer.progress()
+    }
+}
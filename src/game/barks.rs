@@ -0,0 +1,110 @@
+//! Short voice lines said when something happens to whoever's speaking
+//!
+//! Lines are given in the `[barks]` table of an enemy's definition, a list for each event,
+//! one of which is picked at random. Every line can have a sound, a subtitle or both:
+//!
+//! ```toml
+//! [barks]
+//! cooldown = 4.0  # seconds a speaker stays quiet after saying something, dying cutting in
+//! spot = [{ sound = "grunt_spot", text = "There you are!" }, { sound = "grunt_huh" }]
+//! lose = [{ text = "Where did they go?" }]
+//! hurt = [{ sound = "grunt_hurt" }]
+//! death = [{ sound = "grunt_death", text = "Argh!" }]
+//! ```
+
+use rand::Rng;
+use toml::Value;
+
+/// Seconds a speaker stays quiet after saying something by default
+const DEFAULT_COOLDOWN: f32 = 4.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Something that happened that can be said something about
+pub enum BarkEvent {
+    /// Noticed the player
+    Spot,
+    /// Gave up on the player
+    Lose,
+    Hurt,
+    Death,
+}
+
+const EVENTS: &[(&str, BarkEvent)] = &[
+    ("spot", BarkEvent::Spot),
+    ("lose", BarkEvent::Lose),
+    ("hurt", BarkEvent::Hurt),
+    ("death", BarkEvent::Death),
+];
+
+#[derive(Debug, Clone, Default)]
+/// One thing that can be said
+pub struct BarkLine {
+    pub sound: Option<String>,
+    /// Shown as a subtitle
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+/// Everything a speaker can say
+pub struct Barks {
+    /// Seconds a speaker stays quiet after saying something
+    pub cooldown: f32,
+    lines: Vec<(BarkEvent, Vec<BarkLine>)>,
+}
+
+impl Default for Barks {
+    fn default() -> Self {
+        Barks {
+            cooldown: DEFAULT_COOLDOWN,
+            lines: Vec::new(),
+        }
+    }
+}
+
+impl Barks {
+    /// Reads the `[barks]` table of a definition
+    pub fn from_toml(value: &Value) -> Result<Self, String> {
+        let cooldown = match value.get("cooldown") {
+            Some(&Value::Float(n)) => n as f32,
+            Some(&Value::Integer(n)) => n as f32,
+            Some(_) => return Err("barks.cooldown should be a number".to_owned()),
+            None => DEFAULT_COOLDOWN,
+        };
+        let mut lines = Vec::new();
+        for &(key, event) in EVENTS {
+            let list = match value.get(key) {
+                Some(Value::Array(list)) => list,
+                Some(_) => return Err(format!("barks.{} should be a list of lines", key)),
+                None => continue,
+            };
+            let list = list.iter().map(|line| match line {
+                Value::Table(_) => Ok(BarkLine {
+                    sound: line.get("sound").and_then(Value::as_str).map(str::to_owned),
+                    text: line.get("text").and_then(Value::as_str).map(str::to_owned),
+                }),
+                _ => Err(format!("barks.{} should be a list of tables", key)),
+            }).collect::<Result<_, _>>()?;
+            lines.push((event, list));
+        }
+        Ok(Barks {
+            cooldown: cooldown.max(0.),
+            lines,
+        })
+    }
+    /// Every sound any of the lines make
+    pub fn sounds(&self) -> impl Iterator<Item=&String> {
+        self.lines.iter().flat_map(|(_, l)| l).filter_map(|line| line.sound.as_ref())
+    }
+    /// Whether there is anything to say about `event`
+    pub fn has(&self, event: BarkEvent) -> bool {
+        self.lines.iter().any(|&(e, ref l)| e == event && !l.is_empty())
+    }
+    /// A random line to say about `event`
+    pub fn pick<R: Rng>(&self, event: BarkEvent, rng: &mut R) -> Option<&BarkLine> {
+        let lines = self.lines.iter().find(|&&(e, _)| e == event).map(|(_, l)| l)?;
+        if lines.is_empty() {
+            return None
+        }
+        Some(&lines[rng.gen_range(0, lines.len())])
+    }
+}
@@ -0,0 +1,126 @@
+use crate::util::{Point2, Vector2};
+use ggez::{
+    nalgebra::{Matrix4, Vector3},
+    graphics::Rect,
+};
+use rand::{thread_rng, Rng};
+
+/// How quickly the camera catches up with its target, higher being faster
+const FOLLOW_SPEED: f32 = 8.;
+/// How quickly the zoom catches up with the zoom being scrolled to
+const ZOOM_SPEED: f32 = 12.;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 2.;
+/// How much one notch of the mouse wheel zooms by
+const ZOOM_STEP: f32 = 1.1;
+/// Pixels the screen moves at most when shaking as hard as it can
+const MAX_SHAKE: f32 = 12.;
+/// How much shake wears off each second
+const SHAKE_DECAY: f32 = 1.5;
+
+/// Fraction of the way to move towards a target this frame so the movement doesn't depend on the frame rate
+#[inline]
+fn approach(speed: f32, dt: f32) -> f32 {
+    1. - (-speed * dt).exp()
+}
+
+#[derive(Debug, Clone)]
+/// What part of the world is shown on the screen
+pub struct Camera {
+    /// The point in the world at the center of the screen
+    center: Point2,
+    /// The point the camera moves towards
+    target: Point2,
+    zoom: f32,
+    target_zoom: f32,
+    /// How much the screen is shaking from 0 to 1
+    trauma: f32,
+    /// How far the screen is shaken away from the center right now
+    shake: Vector2,
+    /// Half the size of the screen
+    half_screen: Vector2,
+}
+
+impl Camera {
+    pub fn new(width: f32, height: f32) -> Self {
+        let half_screen = 0.5 * Vector2::new(width, height);
+        Camera {
+            center: Point2::from(half_screen),
+            target: Point2::from(half_screen),
+            zoom: 1.,
+            target_zoom: 1.,
+            trauma: 0.,
+            shake: Vector2::new(0., 0.),
+            half_screen,
+        }
+    }
+    /// Should be called when the screen changes size
+    pub fn resize(&mut self, width: f32, height: f32) {
+        self.half_screen = 0.5 * Vector2::new(width, height);
+    }
+    /// The point in the world at the center of the screen
+    #[inline]
+    pub fn center(&self) -> Point2 {
+        self.center
+    }
+    #[inline]
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+    /// Makes the camera move smoothly towards a point
+    #[inline]
+    pub fn follow(&mut self, target: Point2) {
+        self.target = target;
+    }
+    /// Centers the camera on a point right away
+    pub fn snap_to(&mut self, p: Point2) {
+        self.target = p;
+        self.center = p;
+    }
+    /// Zooms in by a number of mouse wheel notches, out if it's negative
+    pub fn zoom_by(&mut self, notches: f32) {
+        self.target_zoom = (self.target_zoom * ZOOM_STEP.powf(notches)).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+    /// Shakes the screen, `amount` from 0 to 1 being how hard
+    pub fn add_shake(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.);
+    }
+    /// Moves the camera towards its target, `dt` being the seconds passed since last update
+    pub fn update(&mut self, dt: f32) {
+        self.center += (self.target - self.center) * approach(FOLLOW_SPEED, dt);
+        self.zoom += (self.target_zoom - self.zoom) * approach(ZOOM_SPEED, dt);
+
+        self.trauma = (self.trauma - SHAKE_DECAY * dt).max(0.);
+        // Squaring makes small shakes subtle and big ones violent
+        let strength = self.trauma * self.trauma * MAX_SHAKE;
+        if strength > 0. {
+            let mut rng = thread_rng();
+            self.shake = strength * Vector2::new(rng.gen_range(-1., 1.), rng.gen_range(-1., 1.));
+        } else {
+            self.shake = Vector2::new(0., 0.);
+        }
+    }
+    /// Converts a point on the screen to where it is in the world
+    #[inline]
+    pub fn screen_to_world(&self, p: Point2) -> Point2 {
+        self.center + (p.coords - self.half_screen - self.shake) / self.zoom
+    }
+    /// Converts a point in the world to where it is drawn on the screen
+    #[inline]
+    pub fn world_to_screen(&self, p: Point2) -> Point2 {
+        Point2::from((p - self.center) * self.zoom + self.half_screen + self.shake)
+    }
+    /// The part of the world that can be seen on the screen
+    pub fn view_rect(&self) -> Rect {
+        let top_left = self.screen_to_world(Point2::new(0., 0.));
+        let size = 2. * self.half_screen / self.zoom;
+        Rect::new(top_left.x, top_left.y, size.x, size.y)
+    }
+    /// The transformation that draws the world as seen by the camera
+    pub fn transform(&self) -> Matrix4<f32> {
+        let screen_offset = self.half_screen + self.shake;
+        Matrix4::new_translation(&Vector3::new(screen_offset.x, screen_offset.y, 0.))
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(self.zoom, self.zoom, 1.))
+            * Matrix4::new_translation(&Vector3::new(-self.center.x, -self.center.y, 0.))
+    }
+}
@@ -0,0 +1,207 @@
+//! Campaigns, ordered lists of levels played one after another
+//!
+//! A campaign is defined by a manifest in `/campaigns`, like `/campaigns/main.toml`:
+//!
+//! ```toml
+//! name = "The Farm"
+//! levels = ["barn", "fields", "mine"]
+//! music = ["farm", "harvest"]   # optional, played in levels without music of their own
+//! ambience = "birds"            # optional, looped in levels without an ambience of their own
+//! ```
+//!
+//! Which levels have been completed is kept in the current profile's saves.
+
+use std::collections::BTreeSet;
+use std::io::Read;
+
+use ggez::{Context, GameResult, GameError, filesystem};
+use toml::Value;
+
+use crate::io::save::Saves;
+use super::{
+    State, StateSwitch, Console, CommandInfo, CommandError, Completion,
+    world::level_path,
+};
+
+/// Path of the manifest of the campaign with the given id
+pub fn campaign_path(id: &str) -> String {
+    format!("/campaigns/{}.toml", id)
+}
+
+fn bad_campaign<T>(id: &str, msg: &str) -> GameResult<T> {
+    Err(GameError::ResourceLoadError(format!("Bad campaign {}: {}", id, msg)))
+}
+
+#[derive(Debug, Clone)]
+pub struct Campaign {
+    pub id: String,
+    pub name: String,
+    levels: Vec<String>,
+    /// Music played in levels that don't have their own
+    pub music: Vec<String>,
+    /// Ambience looped in levels that don't have their own
+    pub ambience: Option<String>,
+    /// Index of the level being played
+    current: usize,
+    completed: BTreeSet<String>,
+}
+
+impl Campaign {
+    /// Loads a campaign's manifest and starts at its first level
+    pub fn load(ctx: &mut Context, id: &str) -> GameResult<Self> {
+        let mut src = String::new();
+        filesystem::open(ctx, campaign_path(id))?.read_to_string(&mut src)?;
+        let value: Value = match src.parse() {
+            Ok(v) => v,
+            Err(e) => return bad_campaign(id, &e.to_string()),
+        };
+
+        let levels: Vec<_> = match value.get("levels").and_then(Value::as_array) {
+            Some(levels) => levels.iter().filter_map(Value::as_str).map(str::to_owned).collect(),
+            None => return bad_campaign(id, "levels should be a list of level names"),
+        };
+        if levels.is_empty() {
+            return bad_campaign(id, "no levels")
+        }
+        if let Some(missing) = levels.iter().find(|l| !filesystem::exists(ctx, level_path(l))) {
+            return bad_campaign(id, &format!("level {} doesn't exist", missing))
+        }
+        let music = match value.get("music") {
+            Some(Value::Array(tracks)) => tracks.iter().filter_map(Value::as_str).map(str::to_owned).collect(),
+            Some(_) => return bad_campaign(id, "music should be a list of track names"),
+            None => Vec::new(),
+        };
+        let ambience = match value.get("ambience") {
+            Some(Value::String(ambience)) => Some(ambience.clone()),
+            Some(_) => return bad_campaign(id, "ambience should be a track name"),
+            None => None,
+        };
+
+        Ok(Campaign {
+            id: id.to_owned(),
+            name: value.get("name").and_then(Value::as_str).unwrap_or(id).to_owned(),
+            levels,
+            music,
+            ambience,
+            current: 0,
+            completed: BTreeSet::new(),
+        })
+    }
+    /// Name of the save the progress is kept in
+    #[inline]
+    fn save_name(&self) -> String {
+        format!("campaign-{}", self.id)
+    }
+    /// Reads which levels have been completed from the saves
+    pub fn load_progress(&mut self, saves: &mut Saves) {
+        match saves.read(&self.save_name()) {
+            Ok(Some(bytes)) => {
+                self.completed = String::from_utf8_lossy(&bytes).lines().map(str::to_owned).collect();
+            }
+            Ok(None) => (),
+            Err(e) => error!("Couldn't read progress of campaign {}: {}", self.id, e),
+        }
+    }
+    fn save_progress(&self, saves: &mut Saves) {
+        let text: Vec<_> = self.completed.iter().map(String::as_str).collect();
+        if let Err(e) = saves.write(&self.save_name(), text.join("\n").as_bytes()) {
+            error!("Couldn't save progress of campaign {}: {}", self.id, e);
+        }
+    }
+    /// Name of the level being played
+    #[inline]
+    pub fn level(&self) -> &str {
+        &self.levels[self.current]
+    }
+    #[inline]
+    pub fn levels(&self) -> &[String] {
+        &self.levels
+    }
+    #[inline]
+    pub fn is_completed(&self, level: &str) -> bool {
+        self.completed.contains(level)
+    }
+    /// Marks the current level as completed and saves the progress
+    pub fn complete_level(&mut self, saves: &mut Saves) {
+        if self.completed.insert(self.level().to_owned()) {
+            self.save_progress(saves);
+        }
+    }
+    /// Goes to the level with the given name, returning whether it's in the campaign
+    pub fn select(&mut self, level: &str) -> bool {
+        match self.levels.iter().position(|l| l == level) {
+            Some(i) => {
+                self.current = i;
+                true
+            }
+            None => false,
+        }
+    }
+    /// Goes to the next level, returning whether there was one
+    pub fn advance(&mut self) -> bool {
+        if self.current + 1 < self.levels.len() {
+            self.current += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Adds the campaign commands to the console
+pub fn register_commands(console: &mut Console) {
+    console.register(CommandInfo::new("campaign", "[name]", "Shows the campaign's progress or starts another one").completing(Completion::Campaigns), |_, ctx, state, _, args| {
+        if args.is_empty() {
+            let campaign = state.campaign.as_ref().ok_or(CommandError::NoCampaign)?;
+            info!("{} ({})", campaign.name, campaign.id);
+            for level in campaign.levels() {
+                let current = if level == campaign.level() { ">" } else { " " };
+                let done = if campaign.is_completed(level) { " (completed)" } else { "" };
+                info!(" {} {}{}", current, level, done);
+            }
+            return Ok(())
+        }
+        let id = args.name(1)?;
+        match Campaign::load(ctx, id) {
+            Ok(mut campaign) => {
+                campaign.load_progress(&mut state.saves);
+                state.notify(&format!("Starting {}", campaign.name));
+                state.campaign = Some(campaign);
+                state.switch(StateSwitch::Loading);
+                Ok(())
+            }
+            Err(e) => {
+                error!("Couldn't start campaign {}: {}", id, e);
+                Err(CommandError::NoSuchFile)
+            }
+        }
+    });
+    console.register(CommandInfo::new("level", "<name>", "Goes to a level of the campaign").completing(Completion::CampaignLevels), |_, _, state, _, args| {
+        let level = args.string(1)?;
+        let campaign = state.campaign.as_mut().ok_or(CommandError::NoCampaign)?;
+        if !campaign.select(level) {
+            return Err(CommandError::NoSuchLevel)
+        }
+        state.switch(StateSwitch::Loading);
+        Ok(())
+    });
+    console.register(CommandInfo::new("nextlevel", "", "Skips to the next level of the campaign"), |_, _, state, _, _| {
+        next_level(state).ok_or(CommandError::NoCampaign)
+    });
+}
+
+/// Moves on to the next level of the campaign, going back to the menu after the last one
+///
+/// Returns `None` if there's no campaign
+pub fn next_level(state: &mut State) -> Option<()> {
+    let campaign = state.campaign.as_mut()?;
+    if campaign.advance() {
+        state.switch(StateSwitch::Loading);
+    } else {
+        let msg = format!("Finished {}!", campaign.name);
+        state.campaign = None;
+        state.notify(&msg);
+        state.switch(StateSwitch::Menu);
+    }
+    Some(())
+}
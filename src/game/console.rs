@@ -0,0 +1,933 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::io::Read;
+use std::fmt::{self, Display};
+use std::mem;
+use crate::{
+    util::{Point2, RED, GREEN, BLUE},
+    ext::{Tween, ease},
+    io::{
+        tex::{Assets, PosText},
+        save::list_profiles,
+        input::{Input, Action, CONSOLE_FALLBACK},
+    },
+};
+use ggez::{
+    Context, GameResult, GameError,
+    filesystem,
+    graphics::{self, DrawMode, Rect, MeshBuilder, Text, TextFragment, DrawParam, Color},
+    input::mouse::{self, MouseCursor},
+};
+use log::{Log, Metadata, Record, Level as LogLevel};
+use lazy_static::lazy_static;
+
+use super::{State, StateSwitch, GameState, world::CompanionKind};
+
+lazy_static! {
+    pub(super) static ref CONSOLE_LOGGER: ConsoleLogger = ConsoleLogger::default();
+}
+
+#[derive(Debug, Default)]
+pub(super) struct ConsoleLogger {
+    fragments: Mutex<Vec<TextFragment>>
+}
+
+impl ConsoleLogger {
+    #[inline]
+    fn get_colour(l: LogLevel) -> Option<Color> {
+        use self::LogLevel::*;
+        match l {
+            Trace => Some(BLUE),
+            Info => None,
+            Debug => Some(GREEN),
+            Warn => Some(Color{r:1.,g:1.,b:0.,a:1.}),
+            Error => Some(RED),
+        }
+    }
+    pub(super) fn empty(&self) -> impl Iterator<Item=TextFragment> {
+        let frags = std::mem::replace(&mut *self.fragments.lock().unwrap(), Vec::new());
+        
+        frags.into_iter()
+    }
+}
+
+impl Log for ConsoleLogger {
+    #[inline]
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Only want to deal with logs from this crate
+        metadata.target().starts_with("cowfarg")
+    }
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            print!("{}: ", record.level());
+
+            println!("{}", record.args());
+
+            let frag: TextFragment = format!("{}\n", record.args()).into();
+
+            let mut frags = self.fragments.lock().unwrap();
+
+            if let Some(color) = Self::get_colour(record.level()) {
+                frags.push(frag.color(color));
+            } else {
+                frags.push(frag);
+            }
+        }
+    }
+    fn flush(&self) {}
+}
+
+pub(super) const PROMPT_Y: f32 = 196.;
+/// Seconds it takes the console to slide open or closed
+const CONSOLE_SLIDE_TIME: f32 = 0.15;
+/// How deep `exec` scripts may run other scripts
+const MAX_EXEC_DEPTH: u8 = 8;
+
+pub struct Console {
+    pub(super) history: Text,
+    pub(super) prompt: PosText,
+    /// Set by the `consolekey` command so the next key pressed becomes the console key
+    pub(super) capturing_key: bool,
+    /// Whether the character typed by the console key should be ignored
+    pub(super) swallow_char: bool,
+    /// How far open the console is
+    pub(super) slide: Tween,
+    /// How many `exec` scripts are currently running inside each other
+    exec_depth: u8,
+    /// Width and height of a character in the (monospaced) console font
+    cell: (f32, f32),
+    /// Start and end (line, column) of the selected history text
+    pub(super) selection: Option<(TextPos, TextPos)>,
+    pub(super) selecting: bool,
+    commands: BTreeMap<&'static str, Command>,
+    completing: Option<Completing>,
+    /// Lines entered in the prompt, oldest first
+    entered: VecDeque<String>,
+    /// Which entered line is recalled into the prompt, if any
+    recalled: Option<usize>,
+    /// What was in the prompt before recalling entered lines
+    draft: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Description of a console command
+pub struct CommandInfo {
+    pub name: &'static str,
+    /// The arguments it takes, like `<file>`
+    pub usage: &'static str,
+    pub help: &'static str,
+    /// What the first argument can be tab completed with
+    pub complete: Completion,
+}
+
+impl CommandInfo {
+    pub const fn new(name: &'static str, usage: &'static str, help: &'static str) -> Self {
+        CommandInfo { name, usage, help, complete: Completion::Nothing }
+    }
+    pub const fn completing(self, complete: Completion) -> Self {
+        CommandInfo { complete, ..self }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Things the arguments of a command can be tab completed with
+pub enum Completion {
+    Nothing,
+    Commands,
+    Cvars,
+    Levels,
+    Profiles,
+    Weapons,
+    Enemies,
+    /// Kinds of companions
+    Companions,
+    Campaigns,
+    /// Levels in the current campaign
+    CampaignLevels,
+    /// Keys and mouse buttons
+    Inputs,
+    Replays,
+}
+
+#[derive(Debug)]
+/// A tab completion that can be cycled through
+struct Completing {
+    /// The prompt before the word being completed
+    start: String,
+    candidates: Vec<String>,
+    /// The candidate currently in the prompt
+    index: usize,
+    /// What the prompt was left as, to tell whether it has been edited since
+    line: String,
+}
+
+/// Runs a console command
+pub type Handler = fn(&mut Console, &mut Context, &mut State, &mut dyn GameState, Args) -> Result<(), CommandError>;
+
+#[derive(Clone, Copy)]
+struct Command {
+    info: CommandInfo,
+    handler: Handler,
+}
+
+/// A line and column in the console history
+pub(super) type TextPos = (usize, usize);
+
+const SELECTION_COLOUR: Color = Color{r:0.3,g:0.3,b:0.8,a:0.6};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommandError {
+    NoWorld,
+    NoCampaign,
+    /// The argument at `index` couldn't be parsed as the `expected` type
+    InvalidArg {
+        index: usize,
+        expected: ArgType,
+    },
+    MissingArg {
+        index: usize,
+        expected: ArgType,
+    },
+    NoSuchLevel,
+    NoSuchWeapon,
+    NoSuchEnemy,
+    NoSuchFile,
+    NoSuchCvar,
+    NoSuchCommand,
+    NotBound,
+    NotRecording,
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::CommandError::*;
+        match *self {
+            NoWorld => "No world".fmt(f),
+            NoCampaign => "No campaign loaded".fmt(f),
+            InvalidArg{index, expected} => write!(f, "Invalid argument {}, expected {}", index, expected),
+            MissingArg{index, expected} => write!(f, "Missing argument {}, expected {}", index, expected),
+            NoSuchLevel => "No such level".fmt(f),
+            NoSuchWeapon => "No such weapon".fmt(f),
+            NoSuchEnemy => "No such enemy".fmt(f),
+            NoSuchFile => "No such file".fmt(f),
+            NoSuchCvar => "No such cvar".fmt(f),
+            NoSuchCommand => "No such command".fmt(f),
+            NotBound => "Not bound".fmt(f),
+            NotRecording => "Not recording".fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The types console arguments can be parsed as
+pub enum ArgType {
+    Str,
+    Int,
+    Float,
+    Bool,
+    /// Letters, digits, `-` and `_`, safe to use as a file name
+    Name,
+    /// The name of a key or mouse button
+    Input,
+    Action,
+}
+
+impl Display for ArgType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::ArgType::*;
+        match *self {
+            Str => "a string".fmt(f),
+            Int => "an integer".fmt(f),
+            Float => "a number".fmt(f),
+            Bool => "true or false".fmt(f),
+            Name => "a name of letters, digits, - and _".fmt(f),
+            Input => "a key or mouse button".fmt(f),
+            Action => "an action".fmt(f),
+        }
+    }
+}
+
+/// Whether `name` can be used as a name, like for a level or a profile, being only letters, digits, `-` and `_`
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+#[derive(Debug, Clone, Copy)]
+/// The arguments of a console command, with the command name at index 0
+pub struct Args<'a> {
+    args: &'a [&'a str],
+}
+
+impl<'a> Args<'a> {
+    #[inline]
+    pub fn new(args: &'a [&'a str]) -> Self {
+        Args { args }
+    }
+    /// The name of the command
+    #[inline]
+    pub fn cmd(&self) -> &'a str {
+        self.args[0]
+    }
+    /// Number of arguments not counting the command name
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.args.len() - 1
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    #[inline]
+    pub fn get(&self, i: usize) -> Option<&'a str> {
+        self.args.get(i).copied()
+    }
+    pub fn string(&self, i: usize) -> Result<&'a str, CommandError> {
+        self.get(i).ok_or(CommandError::MissingArg{index: i, expected: ArgType::Str})
+    }
+    pub fn name(&self, i: usize) -> Result<&'a str, CommandError> {
+        let name = self.get(i).ok_or(CommandError::MissingArg{index: i, expected: ArgType::Name})?;
+        if is_valid_name(name) {
+            Ok(name)
+        } else {
+            Err(CommandError::InvalidArg{index: i, expected: ArgType::Name})
+        }
+    }
+    pub fn input(&self, i: usize) -> Result<Input, CommandError> {
+        let name = self.get(i).ok_or(CommandError::MissingArg{index: i, expected: ArgType::Input})?;
+        Input::from_name(name).ok_or(CommandError::InvalidArg{index: i, expected: ArgType::Input})
+    }
+    pub fn action(&self, i: usize) -> Result<Action, CommandError> {
+        let name = self.get(i).ok_or(CommandError::MissingArg{index: i, expected: ArgType::Action})?;
+        Action::from_name(name).ok_or(CommandError::InvalidArg{index: i, expected: ArgType::Action})
+    }
+    /// All arguments from `i` onwards joined by spaces
+    pub fn rest(&self, i: usize) -> String {
+        self.args.get(i..).unwrap_or_default().join(" ")
+    }
+    fn parse<T: std::str::FromStr>(&self, i: usize, expected: ArgType) -> Result<T, CommandError> {
+        self.get(i)
+            .ok_or(CommandError::MissingArg{index: i, expected})?
+            .parse()
+            .map_err(|_| CommandError::InvalidArg{index: i, expected})
+    }
+    pub fn int(&self, i: usize) -> Result<i64, CommandError> {
+        self.parse(i, ArgType::Int)
+    }
+    pub fn float(&self, i: usize) -> Result<f32, CommandError> {
+        self.parse(i, ArgType::Float)
+    }
+    pub fn bool(&self, i: usize) -> Result<bool, CommandError> {
+        match self.get(i) {
+            None => Err(CommandError::MissingArg{index: i, expected: ArgType::Bool}),
+            Some("true") | Some("1") | Some("on") | Some("yes") => Ok(true),
+            Some("false") | Some("0") | Some("off") | Some("no") => Ok(false),
+            Some(_) => Err(CommandError::InvalidArg{index: i, expected: ArgType::Bool}),
+        }
+    }
+}
+
+/// Splits a line into commands and those into arguments
+///
+/// Commands are separated by `;`, everything after a `#` is a comment
+/// and quotes group words with spaces into one argument.
+fn tokenize(line: &str) -> Vec<Vec<String>> {
+    let mut cmds = Vec::new();
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    // Whether there's an argument being built, since `""` is an empty argument
+    let mut in_arg = false;
+    let mut in_quote = false;
+
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_quote = !in_quote;
+                in_arg = true;
+            }
+            '\\' if in_quote => {
+                if let Some(c) = chars.next() {
+                    arg.push(c);
+                }
+            }
+            c if in_quote => arg.push(c),
+            '#' => break,
+            ';' | ' ' | '\t' => {
+                if in_arg {
+                    args.push(mem::take(&mut arg));
+                    in_arg = false;
+                }
+                if c == ';' && !args.is_empty() {
+                    cmds.push(mem::take(&mut args));
+                }
+            }
+            c => {
+                arg.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(arg);
+    }
+    if !args.is_empty() {
+        cmds.push(args);
+    }
+    cmds
+}
+
+/// Names without the extension of the files in `dir` with the extension `ext`
+fn file_names(ctx: &mut Context, dir: &str, ext: &str) -> Vec<String> {
+    filesystem::read_dir(ctx, dir)
+        .map(|paths| paths
+            .filter(|p| p.extension().map_or(false, |e| e == ext))
+            .filter_map(|p| p.file_stem()?.to_str().map(str::to_owned))
+            .collect())
+        .unwrap_or_default()
+}
+
+/// The commands every console starts out with
+const BUILTINS: &[(CommandInfo, Handler)] = &[
+    (CommandInfo::new("help", "[command]", "Lists the commands or shows how to use one").completing(Completion::Commands), |console, _, _, gs, args| {
+        if let Some(name) = args.get(1) {
+            let info = console.info(gs, name).ok_or(CommandError::NoSuchCommand)?;
+            info!("  {} {}: {}", info.name, info.usage, info.help);
+        } else {
+            for c in console.commands.values().map(|c| &c.info).chain(gs.commands()) {
+                info!("  {} {}", c.name, c.usage);
+            }
+        }
+        Ok(())
+    }),
+    (CommandInfo::new("clear", "", "Clears the console"), |console, _, state, _, _| {
+        console.history = state.assets.raw_text_with("", 18.);
+        Ok(())
+    }),
+    (CommandInfo::new("reload", "", "Goes back to the main menu"), |_, _, state, _, _| {
+        state.switch(StateSwitch::Menu);
+        Ok(())
+    }),
+    (CommandInfo::new("hello", "", "Says hello"), |_, _, _, _, _| {
+        info!("Hello!");
+        Ok(())
+    }),
+    (CommandInfo::new("notify", "<text>", "Shows a notification"), |_, _, state, _, args| {
+        state.notify(&args.rest(1));
+        Ok(())
+    }),
+    (CommandInfo::new("quit", "", "Quits the game"), |_, ctx, _, _, _| {
+        ctx.continuing = false;
+        Ok(())
+    }),
+    (CommandInfo::new("exec", "<file>", "Runs each line of a file as console commands"), |console, ctx, state, gs, args| {
+        console.exec(ctx, state, gs, args.string(1)?)
+    }),
+    (CommandInfo::new("saves", "", "Lists the saves and whether they are in sync"), |_, _, state, _, _| {
+        let names = state.saves.list().map_err(|_| CommandError::NoSuchFile)?;
+        if let Some(backend) = state.saves.backend_name() {
+            info!("Syncing with {}", backend);
+        }
+        for name in names {
+            match state.saves.check(&name) {
+                Ok(sync) => info!("  {} ({:?})", name, sync),
+                Err(e) => error!("  {} ({})", name, e),
+            }
+        }
+        Ok(())
+    }),
+    (CommandInfo::new("profile", "[name]", "Shows the current profile or switches to another one, making it if it's new").completing(Completion::Profiles), |_, _, state, _, args| {
+        if args.is_empty() {
+            info!("Using profile {}", state.saves.profile());
+            return Ok(())
+        }
+        state.switch(StateSwitch::Profile(args.name(1)?.to_owned()));
+        Ok(())
+    }),
+    (CommandInfo::new("profiles", "", "Lists the local profiles"), |_, ctx, state, _, _| {
+        let names = list_profiles(ctx).map_err(|_| CommandError::NoSuchFile)?;
+        for name in names {
+            let current = if name == state.saves.profile() { " (current)" } else { "" };
+            info!("  {}{}", name, current);
+        }
+        Ok(())
+    }),
+    (CommandInfo::new("volume", "<master|music|sfx> [percent]", "Shows or sets a volume"), |_, ctx, state, _, args| {
+        let c = &mut state.config;
+        let volume = match args.string(1)? {
+            "master" => &mut c.master_volume,
+            "music" => &mut c.music_volume,
+            "sfx" => &mut c.sfx_volume,
+            _ => return Err(CommandError::InvalidArg{index: 1, expected: ArgType::Str}),
+        };
+        if args.len() < 2 {
+            info!("{:.0}%", *volume * 100.);
+            return Ok(())
+        }
+        *volume = (args.float(2)? / 100.).clamp(0., 1.);
+        state.mplayer.set_volumes(&state.config);
+        if let Err(e) = state.config.save(ctx, state.saves.profile()) {
+            error!("Couldn't save the config: {}", e);
+        }
+        Ok(())
+    }),
+    (CommandInfo::new("seed", "[seed]", "Shows or sets the seed of the gameplay's randomness, used from the next level on"), |_, _, state, _, args| {
+        if args.is_empty() {
+            info!("Seed: {}", state.rng.seed());
+            return Ok(())
+        }
+        state.rng.reseed(args.int(1)? as u64);
+        Ok(())
+    }),
+    (CommandInfo::new("clock", "", "Shows how much time has passed"), |_, _, state, gs, _| {
+        info!("{} ticks since start", state.ticks);
+        let clock = gs.get_world().ok_or(CommandError::NoWorld)?.clock;
+        info!("World time {:.2}s ({} ticks)", clock.time, clock.ticks);
+        Ok(())
+    }),
+    (CommandInfo::new("set", "<cvar> <value>", "Sets a cvar").completing(Completion::Cvars), |_, _, state, _, args| {
+        state.cvars.set_str(args.string(1)?, args.string(2)?, 2)
+    }),
+    (CommandInfo::new("cvarlist", "", "Lists all cvars"), |_, _, state, _, _| {
+        // Changed cvars are marked with a `*`, like other consoles do
+        for (name, cvar) in state.cvars.iter() {
+            let modified = if cvar.is_modified() { "*" } else { " " };
+            info!(" {}{} = {}: {}", modified, name, cvar.value, cvar.description);
+        }
+        Ok(())
+    }),
+    (CommandInfo::new("reset", "<cvar>", "Sets a cvar back to its default").completing(Completion::Cvars), |_, _, state, _, args| {
+        state.cvars.reset(args.string(1)?)
+    }),
+    (CommandInfo::new("resetall", "", "Sets all cvars back to their defaults"), |_, _, state, _, _| {
+        state.cvars.reset_all();
+        Ok(())
+    }),
+    (CommandInfo::new("consolekey", "", "Sets the key that opens the console to the next key pressed"), |console, _, _, _, _| {
+        info!("Press the key that should open the console");
+        console.capturing_key = true;
+        Ok(())
+    }),
+    (CommandInfo::new("bind", "<key> [action]", "Binds a key or mouse button to an action or shows what it's bound to").completing(Completion::Inputs), |_, _, state, _, args| {
+        let input = args.input(1)?;
+        if args.len() < 2 {
+            let action = state.input.action(input).ok_or(CommandError::NotBound)?;
+            info!("  {} is bound to {}", input, action.name());
+        } else {
+            state.input.bind(input, args.action(2)?);
+        }
+        Ok(())
+    }),
+    (CommandInfo::new("unbind", "<key>", "Removes the binding of a key or mouse button").completing(Completion::Inputs), |_, _, state, _, args| {
+        state.input.unbind(args.input(1)?).ok_or(CommandError::NotBound)?;
+        if !state.input.is_bound(Action::Console) {
+            info!("Nothing is bound to the console, so {} still opens it", CONSOLE_FALLBACK);
+        }
+        Ok(())
+    }),
+    (CommandInfo::new("unbindall", "", "Removes every binding"), |_, _, state, _, _| {
+        state.input.unbind_all();
+        Ok(())
+    }),
+    (CommandInfo::new("bindlist", "", "Lists the bindings and the actions that can be bound"), |_, _, state, _, _| {
+        for (input, action) in state.input.binds() {
+            info!("  {} = {}", input, action.name());
+        }
+        info!("Actions: {}", Action::names().collect::<Vec<_>>().join(", "));
+        Ok(())
+    }),
+    (CommandInfo::new("defaultbinds", "", "Sets all bindings back to the defaults"), |_, _, state, _, _| {
+        state.input.reset();
+        Ok(())
+    }),
+];
+
+impl Console {
+    pub(super) fn new(ctx: &mut Context, assets: &Assets) -> GameResult<Self> {
+        log::set_logger(&*CONSOLE_LOGGER).expect("to be first logger");
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let (cell_w, cell_h) = assets.raw_text_with("M", 18.).dimensions(ctx);
+
+        Ok(Console {
+            history: assets.raw_text_with("Acheivements disabled.\n", 18.),
+            prompt: assets.text(Point2::new(0., PROMPT_Y)).and_text("> ").and_text(String::with_capacity(32)),
+            capturing_key: false,
+            swallow_char: false,
+            slide: Tween::new(0., CONSOLE_SLIDE_TIME, ease::quad_out),
+            cell: (cell_w as f32, cell_h as f32),
+            selection: None,
+            selecting: false,
+            exec_depth: 0,
+            commands: BTreeMap::new(),
+            completing: None,
+            entered: VecDeque::new(),
+            recalled: None,
+            draft: String::new(),
+        }.with_builtins())
+    }
+    /// Adds a command, replacing any earlier one with the same name
+    pub fn register(&mut self, info: CommandInfo, handler: Handler) {
+        self.commands.insert(info.name, Command { info, handler });
+    }
+    fn with_builtins(mut self) -> Self {
+        for &(info, handler) in BUILTINS {
+            self.register(info, handler);
+        }
+        self
+    }
+    /// Finds the description of a command, whether registered or from the current state
+    fn info(&self, gs: &dyn GameState, name: &str) -> Option<CommandInfo> {
+        self.commands.get(name)
+            .map(|c| c.info)
+            .or_else(|| gs.commands().iter().find(|c| c.name == name).copied())
+    }
+    /// The history as one string
+    fn history_string(&self) -> String {
+        self.history.fragments().iter().map(|f| &*f.text).collect()
+    }
+    /// Names of every command and cvar that can be run right now
+    fn command_names(&self, state: &State, gs: &dyn GameState) -> Vec<String> {
+        self.commands.keys().copied()
+            .chain(gs.commands().iter().map(|c| c.name))
+            .chain(state.cvars.iter().map(|(name, _)| name))
+            .map(str::to_owned)
+            .collect()
+    }
+    fn candidates(&self, ctx: &mut Context, state: &State, gs: &dyn GameState, complete: Completion) -> Vec<String> {
+        match complete {
+            Completion::Nothing => Vec::new(),
+            Completion::Commands => self.command_names(state, gs),
+            Completion::Cvars => state.cvars.iter().map(|(name, _)| name.to_owned()).collect(),
+            Completion::Levels => file_names(ctx, "/levels", "lvl"),
+            Completion::Profiles => list_profiles(ctx).unwrap_or_default(),
+            Completion::Weapons => state.weapons.ids().map(str::to_owned).collect(),
+            Completion::Enemies => state.enemies.ids().map(str::to_owned).collect(),
+            Completion::Companions => CompanionKind::names().map(str::to_owned).collect(),
+            Completion::Campaigns => file_names(ctx, "/campaigns", "toml"),
+            Completion::CampaignLevels => state.campaign.as_ref().map_or_else(Vec::new, |c| c.levels().to_vec()),
+            Completion::Inputs => Input::names().map(str::to_owned).collect(),
+            Completion::Replays => file_names(ctx, "/replays", "rpl"),
+        }
+    }
+    /// Completes the word at the end of the prompt
+    ///
+    /// Pressing tab again without editing the prompt cycles through the other candidates
+    pub(super) fn complete(&mut self, ctx: &mut Context, state: &State, gs: &dyn GameState) {
+        let line = self.prompt.text.fragments()[1].text.clone();
+        if let Some(ref mut c) = self.completing {
+            if line == c.line {
+                c.index = (c.index + 1) % c.candidates.len();
+                c.line = format!("{}{}", c.start, c.candidates[c.index]);
+                self.prompt.text.fragments_mut()[1].text = c.line.clone();
+                return
+            }
+        }
+        self.completing = None;
+
+        let word_start = line.rfind(|c: char| c == ' ' || c == '\t' || c == ';').map_or(0, |i| i + 1);
+        let cmd_start = line[..word_start].rfind(';').map_or(0, |i| i + 1);
+        let (start, word) = line.split_at(word_start);
+        let words: Vec<_> = line[cmd_start..word_start].split_whitespace().collect();
+
+        let mut candidates = match *words {
+            [] => self.command_names(state, gs),
+            [cmd] => match self.info(gs, cmd) {
+                Some(info) => self.candidates(ctx, state, gs, info.complete),
+                None => Vec::new(),
+            },
+            _ => Vec::new(),
+        };
+        candidates.retain(|c| c.starts_with(word));
+        candidates.sort();
+        candidates.dedup();
+
+        let prompt = &mut self.prompt.text.fragments_mut()[1].text;
+        match candidates.len() {
+            0 => (),
+            1 => *prompt = format!("{}{} ", start, candidates[0]),
+            _ => {
+                info!("  {}", candidates.join("  "));
+                *prompt = format!("{}{}", start, candidates[0]);
+                self.completing = Some(Completing {
+                    start: start.to_owned(),
+                    line: prompt.clone(),
+                    candidates,
+                    index: 0,
+                });
+            }
+        }
+    }
+    /// Gets the character boundary in the history closest to a point on the screen
+    pub(super) fn pos_at(&self, p: Point2) -> TextPos {
+        let (w, h) = self.cell;
+        ((p.y.max(0.) / h) as usize, (p.x.max(0.) / w + 0.5) as usize)
+    }
+    /// The selection with the start before the end
+    fn ordered_selection(&self) -> Option<(TextPos, TextPos)> {
+        self.selection.map(|(a, b)| if a <= b { (a, b) } else { (b, a) })
+    }
+    pub(super) fn selected_text(&self) -> Option<String> {
+        let (start, end) = self.ordered_selection()?;
+        let history = self.history_string();
+
+        let lines: Vec<String> = history.split('\n')
+            .enumerate()
+            .skip(start.0)
+            .take(end.0 + 1 - start.0)
+            .map(|(i, line)| {
+                let from = if i == start.0 { start.1 } else { 0 };
+                let to = if i == end.0 { end.1 } else { usize::max_value() };
+                line.chars().skip(from).take(to.saturating_sub(from)).collect()
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+    pub(super) fn draw_selection(&self, ctx: &mut Context) -> GameResult<()> {
+        let (start, end) = match self.ordered_selection() {
+            Some(sel) if sel.0 != sel.1 => sel,
+            _ => return Ok(()),
+        };
+        let (w, h) = self.cell;
+        let history = self.history_string();
+
+        let mut mb = MeshBuilder::new();
+        let mut empty = true;
+        for (i, line) in history.split('\n').enumerate().skip(start.0).take(end.0 + 1 - start.0) {
+            let len = line.chars().count();
+            let from = if i == start.0 { start.1.min(len) } else { 0 };
+            let to = if i == end.0 { end.1.min(len) } else { len };
+            if to > from {
+                mb.rectangle(DrawMode::fill(), Rect::new(from as f32 * w, i as f32 * h, (to - from) as f32 * w, h), SELECTION_COLOUR);
+                empty = false;
+            }
+        }
+        if empty {
+            return Ok(())
+        }
+        let mesh = mb.build(ctx)?;
+        graphics::draw(ctx, &mesh, DrawParam::new())
+    }
+    pub(super) fn execute(&mut self, ctx: &mut Context, state: &mut State, gs: &mut dyn GameState) -> GameResult<()> {
+        self.selection = None;
+        let prompt = &mut self.prompt.text.fragments_mut()[1].text;
+
+        self.history.add(format!("> {}\n", prompt));
+
+        let cap = prompt.capacity();
+        let prompt = mem::replace(prompt, String::with_capacity(cap));
+        self.remember(&prompt, state.cvars.int("con_history"));
+        self.run(ctx, state, gs, &prompt);
+
+        Ok(())
+    }
+    /// Adds a line to the ones that can be recalled, skipping it if it repeats the last one
+    fn remember(&mut self, line: &str, max: i64) {
+        self.recalled = None;
+        if !line.trim().is_empty() && self.entered.back().map_or(true, |l| l != line) {
+            self.entered.push_back(line.to_owned());
+        }
+        while self.entered.len() as i64 > max.max(0) {
+            self.entered.pop_front();
+        }
+    }
+    /// Recalls the line entered before the one in the prompt
+    pub(super) fn recall_older(&mut self) {
+        let i = match self.recalled {
+            Some(0) => return,
+            Some(i) => i - 1,
+            None if self.entered.is_empty() => return,
+            None => {
+                self.draft = self.prompt.text.fragments()[1].text.clone();
+                self.entered.len() - 1
+            }
+        };
+        self.recalled = Some(i);
+        self.prompt.text.fragments_mut()[1].text = self.entered[i].clone();
+    }
+    /// Recalls the line entered after the one in the prompt, going back to the draft after the newest
+    pub(super) fn recall_newer(&mut self) {
+        let line = match self.recalled {
+            None => return,
+            Some(i) if i + 1 < self.entered.len() => {
+                self.recalled = Some(i + 1);
+                self.entered[i + 1].clone()
+            }
+            Some(_) => {
+                self.recalled = None;
+                mem::take(&mut self.draft)
+            }
+        };
+        self.prompt.text.fragments_mut()[1].text = line;
+    }
+    /// Runs every command on a line
+    fn run(&mut self, ctx: &mut Context, state: &mut State, gs: &mut dyn GameState, line: &str) {
+        for cmd in tokenize(line) {
+            let args: Vec<_> = cmd.iter().map(String::as_str).collect();
+
+            if let Err(e) = self.handle(ctx, state, gs, Args::new(&args)) {
+                error!("{}", e);
+                if let CommandError::MissingArg{..} | CommandError::InvalidArg{..} = e {
+                    if let Some(info) = self.info(gs, &cmd[0]) {
+                        info!("  Usage: {} {}", info.name, info.usage);
+                    }
+                }
+            }
+        }
+    }
+    /// Runs the saved config, if there is one
+    pub(super) fn exec_config(&mut self, ctx: &mut Context, state: &mut State, gs: &mut dyn GameState) {
+        let path = state.config_path();
+        if filesystem::exists(ctx, &path) {
+            if let Err(e) = self.exec(ctx, state, gs, &path) {
+                error!("Couldn't run {}: {}", path, e);
+            }
+        }
+    }
+    /// Runs every line of a script file
+    fn exec(&mut self, ctx: &mut Context, state: &mut State, gs: &mut dyn GameState, path: &str) -> Result<(), CommandError> {
+        if self.exec_depth >= MAX_EXEC_DEPTH {
+            warn!("  Scripts nested too deep, not running `{}'", path);
+            return Ok(())
+        }
+        let mut script = String::new();
+        filesystem::open(ctx, format!("/{}", path.trim_start_matches('/')))
+            .and_then(|mut f| f.read_to_string(&mut script).map_err(GameError::from))
+            .map_err(|_| CommandError::NoSuchFile)?;
+
+        self.exec_depth += 1;
+        for line in script.lines() {
+            self.run(ctx, state, gs, line);
+        }
+        self.exec_depth -= 1;
+        Ok(())
+    }
+    fn handle(&mut self, ctx: &mut Context, state: &mut State, gs: &mut dyn GameState, args: Args) -> Result<(), CommandError> {
+        let cmd = args.cmd();
+        if let Some(handler) = self.commands.get(cmd).map(|c| c.handler) {
+            handler(self, ctx, state, gs, args)
+        } else if state.cvars.get(cmd).is_some() {
+            // Typing the name of a cvar shows it, following it with a value sets it
+            if let Some(value) = args.get(1) {
+                state.cvars.set_str(cmd, value, 1)
+            } else {
+                info!("{} = {}", cmd, state.cvars.get(cmd).unwrap().value);
+                Ok(())
+            }
+        } else {
+            match gs.command(state, ctx, args) {
+                Some(res) => res,
+                None => {
+                    warn!("  Unknown command `{}' in {}!", cmd, gs.name());
+                    Ok(())
+                }
+            }
+        }
+    }
+    /// Shows the name of the current state in the prompt
+    pub(super) fn set_context(&mut self, name: &str) {
+        self.prompt.text.fragments_mut()[0].text = format!("[{}]> ", name);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ConsoleStatus {
+    Open {
+        cursor: MouseCursor,
+        cursor_hidden: bool,
+    },
+    Closed
+}
+
+impl ConsoleStatus {
+    pub fn is_open(self) -> bool {
+        matches!(self, ConsoleStatus::Open{..})
+    }
+    pub fn open(&mut self, ctx: &Context) {
+        if let ConsoleStatus::Closed = self {
+            *self = ConsoleStatus::Open{
+                cursor: mouse::cursor_type(ctx),
+                cursor_hidden: mouse::cursor_hidden(ctx),
+            };
+        }
+    }
+    pub fn close(&mut self, ctx: &mut Context) {
+        if let ConsoleStatus::Open{cursor, cursor_hidden} = std::mem::replace(self, ConsoleStatus::Closed) {
+            mouse::set_cursor_type(ctx, cursor);
+            mouse::set_cursor_hidden(ctx, cursor_hidden);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_separators_in_quotes() {
+        assert_eq!(tokenize(r#"echo "a; b # c"; clear"#), vec![vec!["echo", "a; b # c"], vec!["clear"]]);
+        // Quotes join onto the rest of the word and `""` is still an argument
+        assert_eq!(tokenize(r#"say hi" there" """#), vec![vec!["say", "hi there", ""]]);
+    }
+    #[test]
+    fn tokenize_escapes() {
+        assert_eq!(tokenize(r#"echo "a \"b\" \\ c""#), vec![vec!["echo", r#"a "b" \ c"#]]);
+        // Backslashes are only escapes inside quotes
+        assert_eq!(tokenize(r#"exec dir\file"#), vec![vec!["exec", r#"dir\file"#]]);
+    }
+    #[test]
+    fn tokenize_unterminated_quote() {
+        // The quote runs to the end of the line, separators and all
+        assert_eq!(tokenize(r#"echo "a; b # c"#), vec![vec!["echo", "a; b # c"]]);
+    }
+    #[test]
+    fn tokenize_comments() {
+        assert_eq!(tokenize("clear # clear; quit"), vec![vec!["clear"]]);
+        assert!(tokenize("# nothing but a comment").is_empty());
+        // Empty commands between separators are skipped
+        assert_eq!(tokenize(" ;; clear ;\tquit;"), vec![vec!["clear"], vec!["quit"]]);
+    }
+    #[test]
+    fn args_parsed() {
+        let args = Args::new(&["set", "-3", "2.5", "on", "0"]);
+        assert_eq!(args.int(1), Ok(-3));
+        assert_eq!(args.float(2), Ok(2.5));
+        assert_eq!(args.bool(3), Ok(true));
+        assert_eq!(args.bool(4), Ok(false));
+        assert_eq!(args.rest(2), "2.5 on 0");
+    }
+    #[test]
+    fn args_missing() {
+        let args = Args::new(&["set"]);
+        assert_eq!(args.string(1), Err(CommandError::MissingArg{index: 1, expected: ArgType::Str}));
+        assert_eq!(args.int(1), Err(CommandError::MissingArg{index: 1, expected: ArgType::Int}));
+        assert_eq!(args.float(1), Err(CommandError::MissingArg{index: 1, expected: ArgType::Float}));
+        assert_eq!(args.bool(1), Err(CommandError::MissingArg{index: 1, expected: ArgType::Bool}));
+    }
+    #[test]
+    fn args_invalid() {
+        let args = Args::new(&["set", "1.5", "abc", "maybe"]);
+        assert_eq!(args.int(1), Err(CommandError::InvalidArg{index: 1, expected: ArgType::Int}));
+        assert_eq!(args.float(2), Err(CommandError::InvalidArg{index: 2, expected: ArgType::Float}));
+        assert_eq!(args.bool(3), Err(CommandError::InvalidArg{index: 3, expected: ArgType::Bool}));
+    }
+    #[test]
+    fn args_name() {
+        let args = Args::new(&["profile", "my-profile_2", "../saves", ""]);
+        assert_eq!(args.name(1), Ok("my-profile_2"));
+        assert_eq!(args.name(2), Err(CommandError::InvalidArg{index: 2, expected: ArgType::Name}));
+        assert_eq!(args.name(3), Err(CommandError::InvalidArg{index: 3, expected: ArgType::Name}));
+        assert_eq!(args.name(4), Err(CommandError::MissingArg{index: 4, expected: ArgType::Name}));
+    }
+    #[test]
+    fn args_bindings() {
+        use ggez::event::{KeyCode, MouseButton};
+        let args = Args::new(&["bind", "Space", "shoot", "mouse2", "jump", "hyper"]);
+        assert_eq!(args.input(1), Ok(Input::Key(KeyCode::Space)));
+        assert_eq!(args.action(2), Ok(Action::Shoot));
+        assert_eq!(args.input(3), Ok(Input::Mouse(MouseButton::Right)));
+        assert_eq!(args.action(4), Err(CommandError::InvalidArg{index: 4, expected: ArgType::Action}));
+        assert_eq!(args.input(5), Err(CommandError::InvalidArg{index: 5, expected: ArgType::Input}));
+        assert_eq!(args.input(6), Err(CommandError::MissingArg{index: 6, expected: ArgType::Input}));
+        assert_eq!(args.action(6), Err(CommandError::MissingArg{index: 6, expected: ArgType::Action}));
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+
+use super::{ArgType, CommandError};
+use crate::io::snd::DEFAULT_POLYPHONY;
+
+/// The script archived cvars are saved to in each profile, run when the profile is picked
+pub const CONFIG_FILE: &str = "config.cfg";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The value of a console variable
+pub enum CvarValue {
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+}
+
+impl CvarValue {
+    #[inline]
+    fn arg_type(self) -> ArgType {
+        match self {
+            CvarValue::Bool(_) => ArgType::Bool,
+            CvarValue::Int(_) => ArgType::Int,
+            CvarValue::Float(_) => ArgType::Float,
+        }
+    }
+    /// Parses `s` as a value of the same type as `self`
+    fn parse_same(self, s: &str) -> Option<Self> {
+        match self {
+            CvarValue::Bool(_) => match s {
+                "true" | "1" | "on" | "yes" => Some(CvarValue::Bool(true)),
+                "false" | "0" | "off" | "no" => Some(CvarValue::Bool(false)),
+                _ => None,
+            },
+            CvarValue::Int(_) => s.parse().ok().map(CvarValue::Int),
+            CvarValue::Float(_) => s.parse().ok().map(CvarValue::Float),
+        }
+    }
+}
+
+impl Display for CvarValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CvarValue::Bool(b) => b.fmt(f),
+            CvarValue::Int(i) => i.fmt(f),
+            CvarValue::Float(n) => n.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cvar {
+    pub value: CvarValue,
+    pub default: CvarValue,
+    /// Whether the value is saved in the config so it survives restarts
+    pub archive: bool,
+    pub description: &'static str,
+}
+
+impl Cvar {
+    #[inline]
+    pub fn is_modified(&self) -> bool {
+        self.value != self.default
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Console variables, the game's settings that can be changed from the console
+pub struct Cvars {
+    vars: BTreeMap<&'static str, Cvar>,
+}
+
+impl Default for Cvars {
+    fn default() -> Self {
+        use self::CvarValue::*;
+        let mut cvars = Cvars {
+            vars: BTreeMap::new(),
+        };
+        cvars.register("m_raw", Bool(false), "Aim with accumulated mouse movement instead of the cursor position");
+        cvars.register("m_sensitivity", Float(1.), "How far the aim moves with raw mouse aiming");
+        cvars.register_transient("timescale", Float(1.), "How fast game time passes compared to real time");
+        cvars.register("fog_of_war", Bool(true), "Hide the parts of levels that haven't been seen yet and dim those out of sight");
+        cvars.register("editor_grid", Bool(true), "Show lines between the tiles in the level editor");
+        cvars.register("hud_minimap", Bool(true), "Show a map of the level in the corner of the screen");
+        cvars.register("hud_timer", Bool(true), "Show how long the level has been played at the top of the screen");
+        cvars.register("hud_indicators", Bool(true), "Show arrows at the edge of the screen pointing at things off-screen");
+        cvars.register("hud_enemy_range", Float(20.), "How many tiles away off-screen enemies still get an arrow pointing at them");
+        cvars.register("m_grab", Bool(true), "Keep the cursor inside the window while playing");
+        cvars.register("con_history", Int(64), "How many entered lines the console remembers");
+        cvars.register("attract_time", Float(60.), "Seconds the menu sits idle before the game starts playing itself, 0 to never");
+        cvars.register("subtitles", Bool(true), "Show what's said out loud at the bottom of the screen");
+        cvars.register("music_shuffle", Bool(false), "Play every unlocked track shuffled while playing instead of each level's own music");
+        cvars.register_transient("dev_inspector", Bool(false), "Click things in the world while playing to inspect them and drag them around");
+        cvars.register("snd_polyphony", Int(DEFAULT_POLYPHONY as i64), "How many times the same sound can play over itself before the oldest is cut off");
+        cvars
+    }
+}
+
+impl Cvars {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a cvar that is saved in the config
+    pub fn register(&mut self, name: &'static str, default: CvarValue, description: &'static str) {
+        self.insert(name, default, true, description);
+    }
+    /// Adds a cvar that goes back to its default when the game restarts
+    pub fn register_transient(&mut self, name: &'static str, default: CvarValue, description: &'static str) {
+        self.insert(name, default, false, description);
+    }
+    fn insert(&mut self, name: &'static str, default: CvarValue, archive: bool, description: &'static str) {
+        self.vars.insert(name, Cvar {
+            value: default,
+            default,
+            archive,
+            description,
+        });
+    }
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&Cvar> {
+        self.vars.get(name)
+    }
+    pub fn iter(&self) -> impl Iterator<Item=(&'static str, &Cvar)> {
+        self.vars.iter().map(|(&n, c)| (n, c))
+    }
+    /// Gets a boolean cvar, `false` if there's no such boolean
+    pub fn bool(&self, name: &str) -> bool {
+        match self.get(name).map(|c| c.value) {
+            Some(CvarValue::Bool(b)) => b,
+            _ => false,
+        }
+    }
+    /// Gets an integer cvar, `0` if there's no such integer
+    pub fn int(&self, name: &str) -> i64 {
+        match self.get(name).map(|c| c.value) {
+            Some(CvarValue::Int(i)) => i,
+            _ => 0,
+        }
+    }
+    /// Gets a float cvar, `0` if there's no such float
+    pub fn float(&self, name: &str) -> f32 {
+        match self.get(name).map(|c| c.value) {
+            Some(CvarValue::Float(n)) => n,
+            _ => 0.,
+        }
+    }
+    /// Sets a cvar from the text `value`, which must match the cvar's type
+    ///
+    /// `index` is the position of `value` among the command's arguments, for error reporting
+    pub fn set_str(&mut self, name: &str, value: &str, index: usize) -> Result<(), CommandError> {
+        let cvar = self.vars.get_mut(name).ok_or(CommandError::NoSuchCvar)?;
+        let expected = cvar.value.arg_type();
+        cvar.value = cvar.value.parse_same(value).ok_or(CommandError::InvalidArg{index, expected})?;
+        Ok(())
+    }
+    pub fn set(&mut self, name: &str, value: CvarValue) -> Result<(), CommandError> {
+        let cvar = self.vars.get_mut(name).ok_or(CommandError::NoSuchCvar)?;
+        if cvar.value.arg_type() != value.arg_type() {
+            return Err(CommandError::InvalidArg{index: 1, expected: cvar.value.arg_type()});
+        }
+        cvar.value = value;
+        Ok(())
+    }
+    pub fn reset(&mut self, name: &str) -> Result<(), CommandError> {
+        let cvar = self.vars.get_mut(name).ok_or(CommandError::NoSuchCvar)?;
+        cvar.value = cvar.default;
+        Ok(())
+    }
+    pub fn reset_all(&mut self) {
+        for cvar in self.vars.values_mut() {
+            cvar.value = cvar.default;
+        }
+    }
+    /// Writes the archived cvars as a script of `set` commands
+    ///
+    /// Cvars at their default are left out so changing a default reaches existing configs
+    pub fn write_config<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        for (name, cvar) in self.iter().filter(|(_, c)| c.archive && c.is_modified()) {
+            writeln!(w, "set {} {}", name, cvar.value)?;
+        }
+        Ok(())
+    }
+}
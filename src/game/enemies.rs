@@ -0,0 +1,198 @@
+//! Enemy definitions loaded from the TOML files in `/enemies`
+//!
+//! Each file defines one kind of enemy, named after the file. Every key is optional,
+//! the defaults being those of a plain enemy that attacks up close:
+//!
+//! ```toml
+//! name = "Grunt"
+//! health = 100.0
+//! speed = 100.0   # pixels per second
+//! damage = 10.0   # done up close
+//! ```
+//!
+//! Enemies with a `[ranged]` table also shoot at the player from a distance,
+//! winding up first so the player has time to get out of the way:
+//!
+//! ```toml
+//! [ranged]
+//! range = 280.0                  # how close the player has to be
+//! damage = 15.0
+//! cooldown = 2.0                 # seconds between shots
+//! windup = 0.6                   # seconds spent aiming before shooting
+//! windup_sound = "enemy_windup"  # optional
+//! fire_sound = "shot"            # optional
+//! ```
+//!
+//! Their bullets can have the same trajectory keys as weapons in the `[ranged]` table.
+//!
+//! What they say when they spot the player, lose them, get hurt or die goes in a `[barks]`
+//! table, described in the `barks` module.
+
+use std::collections::BTreeMap;
+#[cfg(not(feature = "headless"))]
+use std::io::Read;
+#[cfg(not(feature = "headless"))]
+use std::path::Path;
+
+use crate::game::{
+    world::{Trajectory, ENEMY_HEALTH, ENEMY_SPEED, ATTACK_DAMAGE},
+    weapons::trajectory_from_toml,
+    barks::Barks,
+};
+use ggez::{GameResult, GameError};
+#[cfg(not(feature = "headless"))]
+use ggez::{Context, filesystem};
+use toml::Value;
+
+#[derive(Debug, Clone)]
+/// How an enemy shoots
+pub struct RangedAttack {
+    /// How close the player has to be to be shot at
+    pub range: f32,
+    pub damage: f32,
+    /// Seconds between shots
+    pub cooldown: f32,
+    /// Seconds spent aiming before each shot
+    pub windup: f32,
+    pub windup_sound: Option<String>,
+    pub fire_sound: Option<String>,
+    /// How its bullets fly
+    pub trajectory: Trajectory,
+}
+
+#[derive(Debug, Clone)]
+/// What a kind of enemy is like
+pub struct EnemyDef {
+    pub name: String,
+    pub health: f32,
+    /// Pixels per second it walks
+    pub speed: f32,
+    /// Damage done up close
+    pub damage: f32,
+    pub ranged: Option<RangedAttack>,
+    pub barks: Barks,
+}
+
+impl Default for EnemyDef {
+    fn default() -> Self {
+        EnemyDef {
+            name: "Enemy".to_owned(),
+            health: ENEMY_HEALTH,
+            speed: ENEMY_SPEED,
+            damage: ATTACK_DAMAGE,
+            ranged: None,
+            barks: Barks::default(),
+        }
+    }
+}
+
+fn bad_enemy<T>(id: &str, msg: &str) -> GameResult<T> {
+    Err(GameError::ResourceLoadError(format!("Bad enemy {}: {}", id, msg)))
+}
+
+/// Reads a number that has to be positive, `default` if it's missing
+fn positive(id: &str, value: &Value, key: &str, default: f32) -> GameResult<f32> {
+    let n = match value.get(key) {
+        Some(&Value::Float(n)) => n as f32,
+        Some(&Value::Integer(n)) => n as f32,
+        Some(_) => return bad_enemy(id, &format!("{} should be a number", key)),
+        None => return Ok(default),
+    };
+    if n <= 0. {
+        return bad_enemy(id, &format!("{} should be positive", key))
+    }
+    Ok(n)
+}
+
+impl EnemyDef {
+    /// Every sound it can make
+    pub fn sounds(&self) -> impl Iterator<Item=&str> {
+        let ranged = self.ranged.iter().flat_map(|r| r.windup_sound.iter().chain(r.fire_sound.iter()));
+        ranged.chain(self.barks.sounds()).map(String::as_str)
+    }
+    fn from_toml(id: &str, src: &str) -> GameResult<Self> {
+        let value: Value = match src.parse() {
+            Ok(v) => v,
+            Err(e) => return bad_enemy(id, &e.to_string()),
+        };
+        let string = |value: &Value, key: &str| value.get(key).and_then(Value::as_str).map(str::to_owned);
+        let default = EnemyDef::default();
+
+        let ranged = match value.get("ranged") {
+            Some(ranged @ &Value::Table(_)) => Some(RangedAttack {
+                range: positive(id, ranged, "range", 280.)?,
+                damage: positive(id, ranged, "damage", default.damage)?,
+                cooldown: positive(id, ranged, "cooldown", 2.)?,
+                windup: positive(id, ranged, "windup", 0.5)?,
+                windup_sound: string(ranged, "windup_sound"),
+                fire_sound: string(ranged, "fire_sound"),
+                trajectory: match trajectory_from_toml(ranged) {
+                    Ok(t) => t,
+                    Err(e) => return bad_enemy(id, &e),
+                },
+            }),
+            Some(_) => return bad_enemy(id, "ranged should be a table"),
+            None => None,
+        };
+        let barks = match value.get("barks") {
+            Some(barks @ &Value::Table(_)) => match Barks::from_toml(barks) {
+                Ok(b) => b,
+                Err(e) => return bad_enemy(id, &e),
+            },
+            Some(_) => return bad_enemy(id, "barks should be a table"),
+            None => Barks::default(),
+        };
+        Ok(EnemyDef {
+            name: string(&value, "name").unwrap_or_else(|| id.to_owned()),
+            health: positive(id, &value, "health", default.health)?,
+            speed: positive(id, &value, "speed", default.speed)?,
+            damage: positive(id, &value, "damage", default.damage)?,
+            ranged,
+            barks,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// All the enemy definitions by name
+pub struct Enemies {
+    defs: BTreeMap<String, EnemyDef>,
+}
+
+impl Enemies {
+    /// Loads every enemy in `/enemies`, skipping the ones that can't be loaded
+    #[cfg(not(feature = "headless"))]
+    pub fn load(ctx: &mut Context) -> Self {
+        let mut enemies = Enemies::default();
+        let paths: Vec<_> = match filesystem::read_dir(ctx, "/enemies") {
+            Ok(paths) => paths.filter(|p| p.extension().map_or(false, |e| e == "toml")).collect(),
+            Err(_) => return enemies,
+        };
+        for path in paths {
+            let id = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(id) => id.to_owned(),
+                None => continue,
+            };
+            match Self::load_def(ctx, &id, &path) {
+                Ok(def) => {
+                    enemies.defs.insert(id, def);
+                }
+                Err(e) => error!("Couldn't load enemy {}: {}", id, e),
+            }
+        }
+        enemies
+    }
+    #[cfg(not(feature = "headless"))]
+    fn load_def(ctx: &mut Context, id: &str, path: &Path) -> GameResult<EnemyDef> {
+        let mut src = String::new();
+        filesystem::open(ctx, path)?.read_to_string(&mut src)?;
+        EnemyDef::from_toml(id, &src)
+    }
+    #[inline]
+    pub fn get(&self, id: &str) -> Option<&EnemyDef> {
+        self.defs.get(id)
+    }
+    pub fn ids(&self) -> impl Iterator<Item=&str> {
+        self.defs.keys().map(String::as_str)
+    }
+}
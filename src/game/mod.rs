@@ -1,46 +1,124 @@
-use std::sync::Mutex;
-use std::fmt::{self, Display};
+#[cfg(not(feature = "headless"))]
+use std::mem;
+#[cfg(not(feature = "headless"))]
+use std::path::Path;
+#[cfg(not(feature = "headless"))]
 use crate::{
-    util::{Vector2, Point2, RED, GREEN, BLUE},
+    util::{Point2, frame_time, clamp_to_rect},
     io::{
         snd::MediaPlayer,
-        tex::{Assets, PosText},
+        tex::Assets,
+        notify::Notifications,
+        subtitle::Subtitles,
+        save::{Saves, profile_path},
+        config::Config,
+        input::{InputMap, Input, Action},
     },
 };
+#[cfg(not(feature = "headless"))]
 use ggez::{
-    nalgebra::Matrix4,
+    nalgebra::{Matrix4, Vector3},
     Context, GameResult,
-    graphics::{self, DrawMode, Rect, Mesh, Text, TextFragment, DrawParam, Color},
+    graphics::{self, DrawMode, Rect, Mesh, DrawParam},
+    filesystem,
     timer,
     input::mouse::{self, MouseCursor},
     event::EventHandler
 };
+#[cfg(not(feature = "headless"))]
 use clipboard::{ClipboardContext, ClipboardProvider};
-use log::{Log, Metadata, Record, Level as LogLevel};
-use lazy_static::lazy_static;
 
 /// Stuff related to things in the world
 pub mod world;
+#[cfg(not(feature = "headless"))]
 pub mod states;
-
+#[cfg(not(feature = "headless"))]
+pub mod cvars;
+#[cfg(not(feature = "headless"))]
+pub mod console;
+pub mod weapons;
+pub mod enemies;
+#[cfg(not(feature = "headless"))]
+pub mod campaign;
+#[cfg(not(feature = "headless"))]
+pub mod camera;
+#[cfg(not(feature = "headless"))]
+pub mod particles;
+pub mod barks;
+pub mod rng;
+#[cfg(not(feature = "headless"))]
+pub mod replay;
+#[cfg(not(feature = "headless"))]
+pub mod stats;
+
+#[cfg(not(feature = "headless"))]
 use self::states::menu::Menu;
-
+#[cfg(not(feature = "headless"))]
+use self::cvars::{Cvars, CONFIG_FILE};
+#[cfg(not(feature = "headless"))]
+use self::weapons::Weapons;
+#[cfg(not(feature = "headless"))]
+use self::enemies::Enemies;
+#[cfg(not(feature = "headless"))]
+use self::campaign::Campaign;
+#[cfg(not(feature = "headless"))]
+use self::camera::Camera;
+#[cfg(not(feature = "headless"))]
+use self::rng::GameRng;
+#[cfg(not(feature = "headless"))]
+pub use self::console::{Console, ConsoleStatus, CommandError, CommandInfo, Completion, ArgType, Args};
+#[cfg(not(feature = "headless"))]
+use self::console::{CONSOLE_LOGGER, PROMPT_Y};
+
+#[cfg(not(feature = "headless"))]
 pub enum StateSwitch {
     Menu,
+    /// Loads the assets of the level about to be played, then plays it
+    Loading,
     Play,
+    Attract,
+    Options,
+    /// Lists the cvars so they can be changed without the console
+    Advanced,
+    Jukebox,
+    /// Switches to another local profile, then goes back to the menu
+    Profile(String),
+    /// Watches a replay
+    Replay(replay::Replay),
+    /// Shows how the level that just ended went
+    Results(states::results::Outcome),
+    /// Edits the level with the given name
+    Editor(String),
 }
 
 pub mod event {
     pub use ggez::event::{MouseButton, KeyCode, KeyMods};
-    pub enum Event {
-        Key(KeyCode),
-        Mouse(MouseButton),
-    }
+    pub use crate::io::input::Input as Event;
 }
 
+#[cfg(not(feature = "headless"))]
 use event::*;
 
+#[cfg(not(feature = "headless"))]
 pub trait GameState {
+    /// Short name shown in the console prompt
+    fn name(&self) -> &'static str;
+    /// Called when the game switches to this state
+    fn on_enter(&mut self, _: &mut State, _: &mut Context) -> GameResult<()> {
+        Ok(())
+    }
+    /// Called when the game switches away from this state
+    fn on_exit(&mut self, _: &mut State, _: &mut Context) -> GameResult<()> {
+        Ok(())
+    }
+    /// Called when the state stops updating, like when the console opens
+    fn on_pause(&mut self, _: &mut State, _: &mut Context) -> GameResult<()> {
+        Ok(())
+    }
+    /// Called when the state starts updating again after `on_pause`
+    fn on_resume(&mut self, _: &mut State, _: &mut Context) -> GameResult<()> {
+        Ok(())
+    }
     fn update(&mut self, _: &mut State, _: &mut Context) -> GameResult<()> {
         Ok(())
     }
@@ -53,210 +131,90 @@ pub trait GameState {
     fn draw_hud(&mut self, _: &State, _: &mut Context) -> GameResult<()>;
     fn event_down(&mut self, _: &mut State, _: &mut Context, _: Event) { }
     fn event_up(&mut self, _: &mut State, _: &mut Context, _: Event) { }
-
-    fn get_world(&self) -> Option<&world::World> {
-        None
+    /// Called with each character typed while the console is closed
+    fn text_input(&mut self, _: &mut State, _: &mut Context, _: char) { }
+    /// The commands this state handles in `command`
+    fn commands(&self) -> &'static [CommandInfo] {
+        &[]
     }
-    fn get_mut_world(&mut self) -> Option<&mut world::World> {
+    /// Handles console commands specific to this state
+    ///
+    /// Returns `None` if the command isn't known to this state
+    fn command(&mut self, _: &mut State, _: &mut Context, _: Args) -> Option<Result<(), CommandError>> {
         None
     }
-}
-
-lazy_static! {
-    static ref CONSOLE_LOGGER: ConsoleLogger = ConsoleLogger::default();
-}
-
-#[derive(Debug, Default)]
-struct ConsoleLogger {
-    fragments: Mutex<Vec<TextFragment>>
-}
-
-impl ConsoleLogger {
-    #[inline]
-    fn get_colour(l: LogLevel) -> Option<Color> {
-        use self::LogLevel::*;
-        match l {
-            Trace => Some(BLUE),
-            Info => None,
-            Debug => Some(GREEN),
-            Warn => Some(Color{r:1.,g:1.,b:0.,a:1.}),
-            Error => Some(RED),
-        }
-    }
-    fn empty(&self) -> impl Iterator<Item=TextFragment> {
-        let frags = std::mem::replace(&mut *self.fragments.lock().unwrap(), Vec::new());
-        
-        frags.into_iter()
-    }
-}
-
-impl Log for ConsoleLogger {
-    #[inline]
-    fn enabled(&self, metadata: &Metadata) -> bool {
-        // Only want to deal with logs from this crate
-        metadata.target().starts_with("cowfarg")
-    }
-    fn log(&self, record: &Record) {
-        if self.enabled(record.metadata()) {
-            print!("{}: ", record.level());
-
-            println!("{}", record.args());
-
-            let frag: TextFragment = format!("{}\n", record.args()).into();
-
-            let mut frags = self.fragments.lock().unwrap();
-
-            if let Some(color) = Self::get_colour(record.level()) {
-                frags.push(frag.color(color));
-            } else {
-                frags.push(frag);
-            }
-        }
-    }
-    fn flush(&self) {}
-}
-
-const PROMPT_Y: f32 = 196.;
-
-#[derive(Debug)]
-pub struct Console {
-    history: Text,
-    prompt: PosText,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum CommandError {
-    NoWorld,
-    NoCampaign,
-    InvalidArg,
-    NoSuchLevel,
-    NoSuchWeapon,
-}
-
-impl Display for CommandError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use self::CommandError::*;
-        match *self {
-            NoWorld => "No world".fmt(f),
-            NoCampaign => "No campaign loaded".fmt(f),
-            InvalidArg => "Invalid argument".fmt(f),
-            NoSuchLevel => "No such level".fmt(f),
-            NoSuchWeapon => "No such weapon".fmt(f),
-        }
-    }
-}
-
-impl Console {
-    fn new(_ctx: &mut Context, assets: &Assets) -> GameResult<Self> {
-        log::set_logger(&*CONSOLE_LOGGER).expect("to be first logger");
-        log::set_max_level(log::LevelFilter::Trace);
-
-        Ok(Console {
-            history: assets.raw_text_with("Acheivements disabled.\n", 18.),
-            prompt: assets.text(Point2::new(0., PROMPT_Y)).and_text("> ").and_text(String::with_capacity(32)),
-        })
-    }
-    fn execute(&mut self, ctx: &mut Context, state: &mut State, gs: &mut dyn GameState) -> GameResult<()> {
-        let prompt = &mut self.prompt.text.fragments_mut()[1].text;
-
-        self.history.add(format!("> {}\n", prompt));
 
-        let cap = prompt.capacity();
-        let prompt = mem::replace(prompt, String::with_capacity(cap));
-        let args: Vec<_> = prompt.split(<char>::is_whitespace).collect();
-        
-        if let Err(s) = self.handle(ctx, state, gs, args) {
-            error!("{}", s);
-        }
-
-        Ok(())
-    }
-    fn handle(&mut self, ctx: &mut Context, state: &mut State, gs: &mut dyn GameState, args: Vec<&str>) -> Result<(), CommandError> {
-        // use self::CommandError::*;
-
-        match args[0] {
-            "" => (),
-            "clear" => self.history = state.assets.raw_text_with("", 18.),
-            "reload" => {
-                state.switch(StateSwitch::Menu);
-            }
-            "hello" => {
-                info!("Hello!");
-            },
-            "quit" => {
-                ctx.continuing = false;
-            }
-            cmd => {
-                warn!("  Unknown command `{}'!", cmd);
-            }
-        }
-        Ok(())
+    /// Whether the state aims with the mouse, so raw mouse aiming should be used
+    fn uses_aim(&self) -> bool {
+        false
     }
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum ConsoleStatus {
-    Open {
-        cursor: MouseCursor,
-        cursor_hidden: bool,
-    },
-    Closed
-}
 
-impl ConsoleStatus {
-    pub fn is_open(self) -> bool {
-        if let ConsoleStatus::Open{..} = self {
-            true
-        } else {
-            false
-        }
-    }
-    pub fn open(&mut self, ctx: &Context) {
-        if let ConsoleStatus::Closed = self {
-            *self = ConsoleStatus::Open{
-                cursor: mouse::cursor_type(ctx),
-                cursor_hidden: mouse::cursor_hidden(ctx),
-            };
-        }
+    fn get_world(&self) -> Option<&world::World> {
+        None
     }
-    pub fn close(&mut self, ctx: &mut Context) {
-        if let ConsoleStatus::Open{cursor, cursor_hidden} = std::mem::replace(self, ConsoleStatus::Closed) {
-            mouse::set_cursor_type(ctx, cursor);
-            mouse::set_cursor_hidden(ctx, cursor_hidden);
-        }
+    fn get_mut_world(&mut self) -> Option<&mut world::World> {
+        None
     }
 }
 
+#[cfg(not(feature = "headless"))]
 pub struct Master {
     gs: Box<dyn GameState>,
     state: State,
     console_status: ConsoleStatus,
     console: Console,
+    /// Whether the cursor is currently confined to the window
+    cursor_grabbed: bool,
 }
 
+#[cfg(not(feature = "headless"))]
 /// The state of the game
 pub struct State {
     assets: Assets,
     mplayer: MediaPlayer,
+    notifications: Notifications,
+    subtitles: Subtitles,
+    saves: Saves,
+    cvars: Cvars,
+    weapons: Weapons,
+    enemies: Enemies,
+    /// Where the randomness of the gameplay comes from
+    rng: GameRng,
+    /// The settings from the options menu
+    config: Config,
+    /// What the keys and mouse buttons do
+    input: InputMap,
+    /// The campaign being played, if any
+    campaign: Option<Campaign>,
     width: f32,
     height: f32,
     mouse: Point2,
-    offset: Vector2,
+    /// Simulation ticks run since the game started
+    ticks: u64,
+    /// The point on the screen being aimed at
+    ///
+    /// Follows the cursor unless raw mouse aiming is on
+    aim: Point2,
+    camera: Camera,
     switch_state: Option<StateSwitch>,
+    /// Whether the game runs unattended, so players can't quit, open the console or change settings
+    kiosk: bool,
 }
 
 const DESIRED_FPS: u32 = 60;
 
 pub(crate) const DELTA: f32 = 1. / DESIRED_FPS as f32;
 
+#[cfg(not(feature = "headless"))]
 impl Master {
     /// Make a new state object
-    pub fn new(ctx: &mut Context, arg: &str) -> GameResult<Self> {
+    pub fn new(ctx: &mut Context, arg: &str, profile: &str, kiosk: bool, config: Config) -> GameResult<Self> {
         // Initialise assets
         let assets = Assets::new(ctx)?;
+        world::load_materials(ctx);
         let mut mplayer = MediaPlayer::new();
-        mplayer.register_music(ctx, "music", true)?;
-        mplayer.register_music(ctx, "victory", false)?;
+        mplayer.set_volumes(&config);
+        mplayer.load_manifest(ctx)?;
 
         // Get the window's dimensions
         let Rect {w: width, h: height, ..} = graphics::screen_coordinates(ctx);
@@ -265,46 +223,203 @@ impl Master {
             switch_state: None,
             assets,
             mplayer,
+            notifications: Notifications::new(),
+            subtitles: Subtitles::new(),
+            saves: Saves::new(ctx, profile),
+            cvars: Cvars::new(),
+            weapons: Weapons::load(ctx),
+            enemies: Enemies::load(ctx),
+            rng: GameRng::default(),
+            config,
+            input: InputMap::new(),
+            campaign: None,
             width,
             height,
             mouse: Point2::new(0., 0.),
-            offset: Vector2::new(0., 0.),
+            aim: Point2::new(0., 0.),
+            ticks: 0,
+            camera: Camera::new(width, height),
+            kiosk,
         };
 
+        let mut gs = Menu::new(ctx, &mut state)?;
+        gs.on_enter(&mut state, ctx)?;
+        let mut console = Console::new(ctx, &state.assets)?;
+        campaign::register_commands(&mut console);
+        states::replay::register_commands(&mut console);
+        stats::register_commands(&mut console);
+        states::editor::register_commands(&mut console);
+        console.set_context(gs.name());
+        console.exec_config(ctx, &mut state, &mut *gs);
+
         Ok(Master {
-            console: Console::new(ctx, &state.assets)?,
+            console,
             console_status: ConsoleStatus::Closed,
-            gs: Menu::new(ctx, &mut state)?,
+            cursor_grabbed: false,
+            gs,
             state,
         })
     }
 }
 
+#[cfg(not(feature = "headless"))]
+impl Master {
+    /// Makes `profile` the current profile, using its saves and settings from now on
+    ///
+    /// The cvars and bindings of the old profile are saved first and start from their defaults again
+    fn switch_profile(&mut self, ctx: &mut Context, profile: &str) {
+        if let Err(e) = self.save_config(ctx) {
+            error!("Couldn't save config: {}", e);
+        }
+        match self.state.saves.set_profile(ctx, profile) {
+            Ok(()) => {
+                self.load_config(ctx);
+                self.state.cvars.reset_all();
+                self.state.input.reset();
+                self.console.exec_config(ctx, &mut self.state, &mut *self.gs);
+                self.state.notify(&format!("Switched to profile {}", profile));
+            }
+            Err(e) => error!("Couldn't switch to profile {}: {}", profile, e),
+        }
+    }
+    /// Loads and applies the options of the current profile
+    fn load_config(&mut self, ctx: &mut Context) {
+        let s = &mut self.state;
+        s.config = Config::load(ctx, s.saves.profile());
+        if let Err(e) = s.config.apply(ctx) {
+            error!("Couldn't apply config: {}", e);
+        }
+        s.width = s.config.width;
+        s.height = s.config.height;
+        s.mplayer.set_volumes(&s.config);
+    }
+    /// Saves the cvars and bindings, as `State::save_config` does
+    pub fn save_config(&self, ctx: &mut Context) -> GameResult<()> {
+        self.state.save_config(ctx)
+    }
+    fn open_console(&mut self, ctx: &mut Context) {
+        if !self.console_status.is_open() {
+            self.console_status.open(ctx);
+            if let Err(e) = self.gs.on_pause(&mut self.state, ctx) {
+                error!("Couldn't pause {}: {}", self.gs.name(), e);
+            }
+        }
+    }
+    fn close_console(&mut self, ctx: &mut Context) {
+        if self.console_status.is_open() {
+            self.console_status.close(ctx);
+            if let Err(e) = self.gs.on_resume(&mut self.state, ctx) {
+                error!("Couldn't resume {}: {}", self.gs.name(), e);
+            }
+        }
+    }
+    /// Confines the cursor to the window while aiming in a state that uses it
+    fn update_cursor_grab(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let grab = self.state.cvars.bool("m_grab") && self.gs.uses_aim() && !self.console_status.is_open();
+        if grab != self.cursor_grabbed {
+            mouse::set_cursor_grabbed(ctx, grab)?;
+            self.cursor_grabbed = grab;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "headless"))]
 impl State {
-    /// Sets the offset so that the given point will be centered on the screen
-    fn focus_on(&mut self, p: Point2) {
-        self.offset = -p.coords + 0.5 * Vector2::new(self.width, self.height);
+    /// Converts a point on the screen to where it is in the world
+    ///
+    /// Everything converting between the two should go through this and `world_to_screen`
+    #[inline]
+    pub fn screen_to_world(&self, p: Point2) -> Point2 {
+        self.camera.screen_to_world(p)
+    }
+    /// Converts a point in the world to where it is drawn on the screen
+    #[inline]
+    pub fn world_to_screen(&self, p: Point2) -> Point2 {
+        self.camera.world_to_screen(p)
+    }
+    /// The part of the world that can be seen on the screen
+    #[inline]
+    pub fn view_rect(&self) -> Rect {
+        self.camera.view_rect()
+    }
+    /// Seconds of game time that pass in each `update`, taking the timescale into account
+    #[inline]
+    pub fn delta(&self) -> f32 {
+        DELTA * self.cvars.float("timescale")
     }
     fn switch(&mut self, ss: StateSwitch) {
         self.switch_state = Some(ss);
     }
+    /// Where the config script of the current profile is
+    fn config_path(&self) -> String {
+        profile_path(self.saves.profile(), CONFIG_FILE)
+    }
+    /// Saves the archived cvars and the bindings so they are set again next time the profile is picked
+    pub fn save_config(&self, ctx: &mut Context) -> GameResult<()> {
+        // Settings are locked in kiosk mode, so there's nothing to save
+        if self.kiosk {
+            return Ok(())
+        }
+        let path = self.config_path();
+        if let Some(dir) = Path::new(&path).parent() {
+            filesystem::create_dir(ctx, dir)?;
+        }
+        let mut file = filesystem::create(ctx, path)?;
+        self.cvars.write_config(&mut file)?;
+        self.input.write_config(&mut file)?;
+        Ok(())
+    }
+    /// Shows a message sliding in at the top right of the screen
+    pub fn notify(&mut self, msg: &str) {
+        let text = self.assets.raw_text_with(msg, 18.);
+        self.notifications.push(text);
+    }
+    /// Shows a line said by `speaker` at the bottom of the screen, if subtitles are on
+    pub fn subtitle(&mut self, speaker: &str, line: &str) {
+        if self.cvars.bool("subtitles") {
+            let text = self.assets.raw_text_with(&format!("{}: {}", speaker, line), 20.);
+            self.subtitles.push(text);
+        }
+    }
 }
 
-use std::mem;
-
+#[cfg(not(feature = "headless"))]
 impl EventHandler for Master {
     // Handle the game logic
     fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
         if let Some(gsb) = mem::replace(&mut self.state.switch_state, None) {
-            mouse::set_cursor_hidden(ctx, false);
-            mouse::set_cursor_type(ctx, MouseCursor::Default);
+            self.gs.on_exit(&mut self.state, ctx)?;
 
             use self::StateSwitch::*;
             self.gs = match gsb {
+                Loading => states::loading::Loading::new(ctx, &mut self.state),
                 Play => states::play::Play::new(ctx, &mut self.state),
                 Menu => states::menu::Menu::new(ctx, &mut self.state),
+                Attract => states::attract::Attract::new(ctx, &mut self.state),
+                Options => states::options::Options::new(ctx, &mut self.state),
+                Advanced => states::advanced::Advanced::new(ctx, &mut self.state),
+                Profile(profile) => {
+                    self.switch_profile(ctx, &profile);
+                    states::menu::Menu::new(ctx, &mut self.state)
+                }
+                Jukebox => states::jukebox::Jukebox::new(ctx, &mut self.state),
+                Replay(replay) => states::replay::Playback::new(ctx, &mut self.state, replay),
+                Results(outcome) => states::results::Results::new(ctx, &mut self.state, outcome),
+                Editor(level) => states::editor::Editor::new(ctx, &mut self.state, level),
             }?;
+            self.gs.on_enter(&mut self.state, ctx)?;
+            self.console.set_context(self.gs.name());
         }
+        let dt = frame_time(ctx);
+        self.state.notifications.update(dt);
+        self.state.subtitles.update(dt);
+        self.state.camera.resize(self.state.width, self.state.height);
+        self.console.slide.set_target(if self.console_status.is_open() { 1. } else { 0. });
+        self.console.slide.update(dt);
+        self.update_cursor_grab(ctx)?;
+        self.state.mplayer.update(ctx, dt)?;
+
         if self.console_status.is_open() {
             while timer::check_update_time(ctx, DESIRED_FPS) {}
 
@@ -325,9 +440,15 @@ impl EventHandler for Master {
             // Run this for every 1/60 of a second has passed since last update
             // Can in theory become slow
             while timer::check_update_time(ctx, DESIRED_FPS) {
+                self.state.ticks += 1;
                 self.gs.update(&mut self.state, ctx)?;
             }
-            self.gs.logic(&mut self.state, ctx)
+            self.gs.logic(&mut self.state, ctx)?;
+            self.state.camera.update(dt);
+            self.state.mplayer.set_listener(self.state.camera.center());
+            let polyphony = self.state.cvars.int("snd_polyphony").max(1) as usize;
+            self.state.mplayer.set_polyphony(polyphony);
+            Ok(())
         }
     }
 
@@ -336,25 +457,36 @@ impl EventHandler for Master {
         // Clear the screen first
         graphics::clear(ctx, (33, 33, 255, 255).into());
 
-        // Offset the current drawing with a translation from the `offset`
-        graphics::push_transform(ctx, Some(Matrix4::new_translation(&self.state.offset.fixed_resize(0.))));
+        // Draw the world as seen from the camera
+        graphics::push_transform(ctx, Some(self.state.camera.transform()));
         graphics::apply_transformations(ctx)?;
 
         self.gs.draw(&self.state, ctx)?;
 
-        // Pop the offset tranformation to draw the UI on the screen
+        // Pop the camera tranformation to draw the UI on the screen
         graphics::pop_transform(ctx);
         graphics::apply_transformations(ctx)?;
 
         self.gs.draw_hud(&self.state, ctx)?;
+        self.state.notifications.draw(ctx, self.state.width)?;
+        self.state.subtitles.draw(ctx, self.state.width, self.state.height)?;
 
-        if self.console_status.is_open() {
-            let console_bg = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect::new(0., 0., self.state.width as f32, self.state.height as f32 / 3.), graphics::BLACK)?;
-            graphics::draw(ctx, &console_bg, DrawParam::new())?;
+        let slide = self.console.slide.value();
+        if slide > 0. {
+            let console_height = self.state.height as f32 / 3.;
+            // Slide the console down from above the screen
+            graphics::push_transform(ctx, Some(Matrix4::new_translation(&Vector3::new(0., (slide - 1.) * console_height, 0.))));
+            graphics::apply_transformations(ctx)?;
 
+            let console_bg = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect::new(0., 0., self.state.width as f32, console_height), graphics::BLACK)?;
+            graphics::draw(ctx, &console_bg, DrawParam::new())?;
 
+            self.console.draw_selection(ctx)?;
             graphics::draw(ctx, &self.console.history, DrawParam::default())?;
             self.console.prompt.draw_text(ctx)?;
+
+            graphics::pop_transform(ctx);
+            graphics::apply_transformations(ctx)?;
         }
 
         // Flip the buffers to see what we just drew
@@ -373,33 +505,65 @@ impl EventHandler for Master {
 
         use self::KeyCode::*;
         match keycode {
-            Escape if km.contains(KeyMods::SHIFT) => ctx.continuing = false,
+            Escape if km.contains(KeyMods::SHIFT) && !self.state.kiosk => ctx.continuing = false,
+            keycode if self.console.capturing_key => {
+                let input = Input::Key(keycode);
+                self.state.input.unbind_action(Action::Console);
+                self.state.input.bind(input, Action::Console);
+                self.console.capturing_key = false;
+                self.console.swallow_char = true;
+                info!("Console key set to {}", input);
+            }
+            keycode if self.state.input.is(Input::Key(keycode), Action::Console) && !self.state.kiosk => {
+                if self.console_status.is_open() {
+                    self.close_console(ctx);
+                } else {
+                    self.open_console(ctx);
+                }
+                // The key's character comes in after this event and shouldn't end up in the prompt
+                self.console.swallow_char = true;
+            }
             keycode if !self.console_status.is_open() => self.gs.event_down(&mut self.state, ctx, Event::Key(keycode)),
             _ => (),
         }
     }
     /// Handle key release events
     fn key_up_event(&mut self, ctx: &mut Context, keycode: KeyCode, _: KeyMods) {
-        if !self.console_status.is_open() {
+        if self.state.input.is(Input::Key(keycode), Action::Console) {
+            self.console.swallow_char = false;
+        } else if self.console_status.is_open() {
+            // Arrow keys don't make characters, so they never reach `text_input_event`
             match keycode {
-                KeyCode::Tab => self.console_status.open(ctx),
-                keycode => self.gs.event_up(&mut self.state, ctx, Event::Key(keycode))
+                KeyCode::Up => self.console.recall_older(),
+                KeyCode::Down => self.console.recall_newer(),
+                _ => (),
             }
+        } else {
+            self.gs.event_up(&mut self.state, ctx, Event::Key(keycode))
         }
     }
     /// Handle mouse down event
-    fn mouse_button_down_event(&mut self, ctx: &mut Context, btn: MouseButton, _x: f32, _y: f32) {
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, btn: MouseButton, x: f32, y: f32) {
         if !self.console_status.is_open() {
             self.gs.event_down(&mut self.state, ctx, Event::Mouse(btn))
+        } else if btn == MouseButton::Left && y < PROMPT_Y {
+            let pos = self.console.pos_at(Point2::new(x, y));
+            self.console.selection = Some((pos, pos));
+            self.console.selecting = true;
         }
     }
     /// Handle mouse release events
     fn mouse_button_up_event(&mut self, ctx: &mut Context, btn: MouseButton, _x: f32, _y: f32) {
         if !self.console_status.is_open() {
             self.gs.event_up(&mut self.state, ctx, Event::Mouse(btn))
+        } else if btn == MouseButton::Left {
+            self.console.selecting = false;
         }
     }
     fn text_input_event(&mut self, ctx: &mut Context, c: char) {
+        if mem::replace(&mut self.console.swallow_char, false) {
+            return
+        }
         if self.console_status.is_open() {
             if c.is_control() {
                 match c {
@@ -408,9 +572,16 @@ impl EventHandler for Master {
                     // Delete
                     '\u{7f}' => (),
                     // Escape
-                    '\u{1b}' => self.console_status.close(ctx),
-                    '\t' => {
-                        // Do tab completion
+                    '\u{1b}' => self.close_console(ctx),
+                    '\t' => self.console.complete(ctx, &self.state, &*self.gs),
+                    // Copy
+                    '\u{3}' => {
+                        if let Some(selected) = self.console.selected_text() {
+                            let mut cc = ClipboardContext::new().unwrap();
+                            if cc.set_contents(selected).is_err() {
+                                error!("Couldn't copy to clipboard");
+                            }
+                        }
                     }
                     // Paste
                     '\u{16}' => {
@@ -426,11 +597,36 @@ impl EventHandler for Master {
             } else {
                 self.console.prompt.text.fragments_mut()[1].text.push(c);
             }
+        } else {
+            self.gs.text_input(&mut self.state, ctx, c);
         }
     }
     /// Handles mouse movement events
     fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, _: f32, _: f32) {
-        self.state.mouse = Point2::new(x, y);
+        let cursor = Point2::new(x, y);
+        if self.state.cvars.bool("m_raw") && self.gs.uses_aim() && !self.console_status.is_open() {
+            // Move the aim by how much the cursor moved and put the cursor back in the center,
+            // so it never hits the edge of the window
+            let delta = cursor - self.state.mouse;
+            let aim = self.state.aim + delta * self.state.cvars.float("m_sensitivity");
+            self.state.aim = clamp_to_rect(aim, Rect::new(0., 0., self.state.width, self.state.height));
+
+            let center = Point2::new(self.state.width / 2., self.state.height / 2.);
+            if mouse::set_position(ctx, center).is_ok() {
+                self.state.mouse = center;
+            } else {
+                self.state.mouse = cursor;
+            }
+        } else {
+            self.state.mouse = cursor;
+            self.state.aim = cursor;
+        }
+        if self.console.selecting {
+            let pos = self.console.pos_at(Point2::new(x, y.min(PROMPT_Y)));
+            if let Some((_, ref mut end)) = self.console.selection {
+                *end = pos;
+            }
+        }
         if let ConsoleStatus::Open{cursor, cursor_hidden} = self.console_status {
             if y > PROMPT_Y {
                 mouse::set_cursor_type(ctx, cursor);
@@ -441,7 +637,14 @@ impl EventHandler for Master {
             }
         }
     }
+    /// Zooms the camera when scrolling over a world
+    fn mouse_wheel_event(&mut self, _ctx: &mut Context, _x: f32, y: f32) {
+        if !self.console_status.is_open() && self.gs.get_world().is_some() {
+            self.state.camera.zoom_by(y);
+        }
+    }
     fn quit_event(&mut self, _ctx: &mut Context) -> bool {
-        false
+        // Returning true keeps the game running
+        self.state.kiosk
     }
 }
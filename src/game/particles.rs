@@ -0,0 +1,173 @@
+//! Short-lived particles for effects like muzzle flashes, blood and debris
+//!
+//! Every particle is the same white sprite, tinted and scaled over its life,
+//! so all of them can be drawn with one sprite batch.
+
+use crate::{
+    util::{Point2, Vector2, lerp, angle_to_vec, angle_from_vec},
+    io::tex::Assets,
+};
+use ggez::{
+    Context, GameResult,
+    graphics::{self, Color, DrawParam, spritebatch::SpriteBatch},
+};
+use rand::{thread_rng, Rng};
+
+/// Most particles alive at once, new ones not being spawned past it
+const MAX_PARTICLES: usize = 8192;
+
+#[derive(Debug, Clone, Copy)]
+/// What the particles of an effect are like
+pub struct EmitterDef {
+    /// Particles spawned at once by `burst`
+    pub burst: u16,
+    /// Particles spawned per second by `emit`
+    pub rate: f32,
+    /// Shortest and longest seconds a particle lives
+    pub lifetime: (f32, f32),
+    /// Slowest and fastest pixels per second a particle starts out with
+    pub speed: (f32, f32),
+    /// Radians particles can stray to either side of the direction they're emitted in
+    pub spread: f32,
+    /// Pixels per second squared particles are pulled down the screen with
+    pub gravity: f32,
+    /// Share of its speed a particle loses each second
+    pub drag: f32,
+    /// Scale of the sprite at the start and end of a particle's life
+    pub size: (f32, f32),
+    /// Colour at the start and end of a particle's life, fading in between
+    pub colour: (Color, Color),
+}
+
+/// A short bright flash in front of a gun
+pub const MUZZLE_FLASH: EmitterDef = EmitterDef {
+    burst: 10,
+    rate: 0.,
+    lifetime: (0.05, 0.12),
+    speed: (80., 260.),
+    spread: 0.35,
+    gravity: 0.,
+    drag: 6.,
+    size: (0.6, 0.2),
+    colour: (Color{r:1.,g:0.95,b:0.6,a:1.}, Color{r:1.,g:0.4,b:0.,a:0.}),
+};
+/// Sprayed out of something that's been shot
+pub const BLOOD: EmitterDef = EmitterDef {
+    burst: 18,
+    rate: 12.,
+    lifetime: (0.25, 0.6),
+    speed: (40., 200.),
+    spread: 0.6,
+    gravity: 120.,
+    drag: 4.,
+    size: (0.5, 0.3),
+    colour: (Color{r:0.7,g:0.,b:0.,a:1.}, Color{r:0.4,g:0.,b:0.,a:0.}),
+};
+/// Chips knocked off walls and tiles
+pub const DEBRIS: EmitterDef = EmitterDef {
+    burst: 12,
+    rate: 0.,
+    lifetime: (0.3, 0.8),
+    speed: (60., 220.),
+    spread: 0.9,
+    gravity: 200.,
+    drag: 3.,
+    size: (0.4, 0.25),
+    colour: (Color{r:0.55,g:0.45,b:0.35,a:1.}, Color{r:0.4,g:0.35,b:0.3,a:0.}),
+};
+
+#[inline]
+fn lerp_colour(a: Color, b: Color, t: f32) -> Color {
+    Color::new(lerp(a.r, b.r, t), lerp(a.g, b.g, t), lerp(a.b, b.b, t), lerp(a.a, b.a, t))
+}
+
+#[inline]
+fn random_between(rng: &mut impl Rng, (min, max): (f32, f32)) -> f32 {
+    if max > min { rng.gen_range(min, max) } else { min }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Particle {
+    pos: Point2,
+    vel: Vector2,
+    /// Seconds it's been alive
+    age: f32,
+    lifetime: f32,
+    def: &'static EmitterDef,
+}
+
+impl Particle {
+    /// How far through its life it is, from 0 to 1
+    #[inline]
+    fn life(&self) -> f32 {
+        (self.age / self.lifetime).min(1.)
+    }
+}
+
+/// All the particles in the world
+pub struct Particles {
+    particles: Vec<Particle>,
+    batch: SpriteBatch,
+}
+
+impl Particles {
+    pub fn new(ctx: &mut Context, assets: &Assets) -> Self {
+        Particles {
+            particles: Vec::with_capacity(1024),
+            batch: SpriteBatch::new(assets.get_img(ctx, "common/particle").clone()),
+        }
+    }
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+    fn spawn(&mut self, def: &'static EmitterDef, pos: Point2, dir: Vector2, count: u32) {
+        let count = (count as usize).min(MAX_PARTICLES - self.particles.len());
+        let mut rng = thread_rng();
+        let angle = angle_from_vec(dir);
+        for _ in 0..count {
+            let stray = if def.spread > 0. { rng.gen_range(-def.spread, def.spread) } else { 0. };
+            self.particles.push(Particle {
+                pos,
+                vel: angle_to_vec(angle + stray) * random_between(&mut rng, def.speed),
+                age: 0.,
+                lifetime: random_between(&mut rng, def.lifetime),
+                def,
+            });
+        }
+    }
+    /// Spawns a burst of particles at `pos` flying out towards `dir`
+    pub fn burst(&mut self, def: &'static EmitterDef, pos: Point2, dir: Vector2) {
+        self.spawn(def, pos, dir, u32::from(def.burst));
+    }
+    /// Spawns as many particles as the emitter's rate gives for `dt` seconds,
+    /// for effects that last a while and are emitted every update
+    pub fn emit(&mut self, def: &'static EmitterDef, pos: Point2, dir: Vector2, dt: f32) {
+        let n = def.rate * dt;
+        // The fraction of a particle left over is spawned by chance, so low rates still spawn some
+        let count = n as u32 + (thread_rng().gen::<f32>() < n.fract()) as u32;
+        self.spawn(def, pos, dir, count);
+    }
+    /// Moves the particles for `dt` seconds and removes the ones that have died
+    pub fn update(&mut self, dt: f32) {
+        for p in &mut self.particles {
+            p.age += dt;
+            p.vel.y += p.def.gravity * dt;
+            p.vel *= (1. - p.def.drag * dt).max(0.);
+            p.pos += p.vel * dt;
+        }
+        self.particles.retain(|p| p.age < p.lifetime);
+    }
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        self.batch.clear();
+        for p in &self.particles {
+            let t = p.life();
+            let size = lerp(p.def.size.0, p.def.size.1, t);
+            self.batch.add(DrawParam::new()
+                .dest(p.pos)
+                .offset(Point2::new(0.5, 0.5))
+                .scale(Vector2::new(size, size))
+                .color(lerp_colour(p.def.colour.0, p.def.colour.1, t)));
+        }
+        graphics::draw(ctx, &self.batch, DrawParam::new())
+    }
+}
@@ -0,0 +1,164 @@
+//! Recordings of a level being played, which play out the same way again when played back
+//!
+//! Since the game's randomness starts over from its seed when a level starts, the level,
+//! the companion, the seed and what the player did each tick are all it takes. All numbers are little endian:
+//!
+//! - `CFRP` and a version byte
+//! - the seed as a `u64`
+//! - the length of the level as a `u32`, then the level in the level format
+//! - since version 2, a byte that's 1 if the player had a companion, then the name of its kind
+//!   as a `u16` length and UTF-8 bytes and its health as an `f32`
+//! - for each tick until the end of the file, its length in seconds as an `f32`, the actions
+//!   held, pressed and released as `u16` bit sets, and where the crosshair was as two `f32`s
+//!
+//! Replays are saved in `/replays` in the user directory.
+
+use std::io::{Read, Write};
+
+use crate::{
+    util::Point2,
+    io::input::{ActionSet, TickInput},
+    game::{world::{World, Companion, CompanionKind}, enemies::Enemies},
+};
+use ggez::{Context, GameResult, GameError, filesystem};
+
+const MAGIC: &[u8; 4] = b"CFRP";
+const VERSION: u8 = 2;
+/// Bytes each tick takes up
+const TICK_SIZE: usize = 4 + 3 * 2 + 2 * 4;
+
+/// Path of the replay file with the given name
+pub fn replay_path(name: &str) -> String {
+    format!("/replays/{}.rpl", name)
+}
+
+fn bad_replay<T>(msg: &str) -> GameResult<T> {
+    Err(GameError::ResourceLoadError(format!("Bad replay: {}", msg)))
+}
+
+#[derive(Debug, Clone)]
+/// A level being played, tick by tick
+pub struct Replay {
+    pub seed: u64,
+    /// The level as it was when it started, in the level format
+    level: Vec<u8>,
+    /// The kind and health of the companion the player brought along, since it isn't part of the level
+    companion: Option<(CompanionKind, f32)>,
+    /// How long each tick was and what the player did during it
+    pub ticks: Vec<(f32, TickInput)>,
+}
+
+impl Replay {
+    /// Starts recording `world` being played with the randomness starting from `seed`
+    pub fn new(seed: u64, world: &World) -> Self {
+        Replay {
+            seed,
+            level: world.to_bytes(),
+            companion: world.companion.as_ref().map(|c| (c.kind, c.health.hp)),
+            ticks: Vec::new(),
+        }
+    }
+    #[inline]
+    pub fn record(&mut self, dt: f32, input: TickInput) {
+        self.ticks.push((dt, input));
+    }
+    /// The level as it was when the recording started, with the companion the player had then
+    pub fn world(&self, defs: &Enemies) -> GameResult<World> {
+        let mut world = World::from_bytes(defs, &self.level)?;
+        world.companion = self.companion.map(|(kind, hp)| Companion::recruited(kind, hp, world.spawn));
+        Ok(world)
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(17 + self.level.len() + self.ticks.len() * TICK_SIZE);
+        buf.extend_from_slice(MAGIC);
+        buf.push(VERSION);
+        buf.extend_from_slice(&self.seed.to_le_bytes());
+        buf.extend_from_slice(&(self.level.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&self.level);
+        match self.companion {
+            Some((kind, hp)) => {
+                buf.push(1);
+                buf.extend_from_slice(&(kind.name().len() as u16).to_le_bytes());
+                buf.extend_from_slice(kind.name().as_bytes());
+                buf.extend_from_slice(&hp.to_bits().to_le_bytes());
+            }
+            None => buf.push(0),
+        }
+        for &(dt, ref input) in &self.ticks {
+            buf.extend_from_slice(&dt.to_bits().to_le_bytes());
+            for set in &[input.held, input.pressed, input.released] {
+                buf.extend_from_slice(&set.bits().to_le_bytes());
+            }
+            buf.extend_from_slice(&input.aim.x.to_bits().to_le_bytes());
+            buf.extend_from_slice(&input.aim.y.to_bits().to_le_bytes());
+        }
+        buf
+    }
+    pub fn from_bytes(bytes: &[u8]) -> GameResult<Self> {
+        if bytes.len() < 17 || &bytes[..4] != MAGIC {
+            return bad_replay("not a replay file")
+        }
+        let version = bytes[4];
+        if version == 0 || version > VERSION {
+            return bad_replay(&format!("unsupported version {}", version))
+        }
+        let mut seed = [0; 8];
+        seed.copy_from_slice(&bytes[5..13]);
+        let mut len = [0; 4];
+        len.copy_from_slice(&bytes[13..17]);
+        let len = u32::from_le_bytes(len) as usize;
+        if bytes.len() < 17 + len {
+            return bad_replay("unexpected end of file")
+        }
+        let (level, mut ticks) = bytes[17..].split_at(len);
+        let mut companion = None;
+        if version >= 2 {
+            let (&has_companion, rest) = ticks.split_first().map_or_else(|| bad_replay("unexpected end of file"), Ok)?;
+            ticks = rest;
+            if has_companion == 1 {
+                if ticks.len() < 2 {
+                    return bad_replay("unexpected end of file")
+                }
+                let name_len = usize::from(u16::from_le_bytes([ticks[0], ticks[1]]));
+                if ticks.len() < 2 + name_len + 4 {
+                    return bad_replay("unexpected end of file")
+                }
+                let name = String::from_utf8_lossy(&ticks[2..2 + name_len]);
+                let kind = CompanionKind::from_name(&name).map_or_else(|| bad_replay(&format!("unknown companion {}", name)), Ok)?;
+                let hp = &ticks[2 + name_len..2 + name_len + 4];
+                companion = Some((kind, f32::from_bits(u32::from_le_bytes([hp[0], hp[1], hp[2], hp[3]]))));
+                ticks = &ticks[2 + name_len + 4..];
+            }
+        }
+        if ticks.len() % TICK_SIZE != 0 {
+            return bad_replay("unexpected end of file")
+        }
+
+        let f32_at = |b: &[u8], i: usize| f32::from_bits(u32::from_le_bytes([b[i], b[i+1], b[i+2], b[i+3]]));
+        let set_at = |b: &[u8], i: usize| ActionSet::from_bits(u16::from_le_bytes([b[i], b[i+1]]));
+        let ticks = ticks.chunks(TICK_SIZE).map(|b| (f32_at(b, 0), TickInput {
+            held: set_at(b, 4),
+            pressed: set_at(b, 6),
+            released: set_at(b, 8),
+            aim: Point2::new(f32_at(b, 10), f32_at(b, 14)),
+        })).collect();
+
+        Ok(Replay {
+            seed: u64::from_le_bytes(seed),
+            level: level.to_vec(),
+            companion,
+            ticks,
+        })
+    }
+    /// Saves the replay in the user directory
+    pub fn save(&self, ctx: &mut Context, name: &str) -> GameResult<()> {
+        let mut file = filesystem::create(ctx, replay_path(name))?;
+        file.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+    pub fn load(ctx: &mut Context, name: &str) -> GameResult<Self> {
+        let mut bytes = Vec::new();
+        filesystem::open(ctx, replay_path(name))?.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+}
@@ -0,0 +1,61 @@
+//! The randomness of the gameplay, like bullet spread and what enemies say
+//!
+//! All of it comes from one seeded generator, started over from its seed at the start of each level,
+//! so a level plays out the same way every time with the same seed and input. Randomness that only
+//! changes how things look, like particles and screen shake, doesn't use it.
+
+use rand::{thread_rng, Rng, RngCore, SeedableRng, Error, rngs::StdRng};
+
+#[derive(Debug, Clone)]
+/// A seeded random number generator, usable like any other `Rng`
+pub struct GameRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl Default for GameRng {
+    /// Makes a generator with a random seed
+    fn default() -> Self {
+        GameRng::seeded(thread_rng().gen())
+    }
+}
+
+impl GameRng {
+    pub fn seeded(seed: u64) -> Self {
+        GameRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+    #[inline]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// Starts over from another seed
+    pub fn reseed(&mut self, seed: u64) {
+        *self = GameRng::seeded(seed);
+    }
+    /// Starts over from the seed, giving the same numbers as it did the first time
+    pub fn restart(&mut self) {
+        self.rng = StdRng::seed_from_u64(self.seed);
+    }
+}
+
+impl RngCore for GameRng {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+    #[inline]
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+    #[inline]
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{
+    util::{Point2, frame_time},
+    io::{
+        tex::PosText,
+        btn::Button,
+    },
+    game::{
+        State, GameState, StateSwitch,
+        cvars::CvarValue,
+        event::{Event::{self, Mouse, Key}, MouseButton, KeyCode},
+    },
+};
+use ggez::{
+    Context, GameResult,
+    graphics::{self, Rect, DrawParam, Color},
+};
+
+const TOP: f32 = 96.;
+const ROW_HEIGHT: f32 = 52.;
+const DESCRIPTION_COLOR: Color = Color{r: 0.8, g: 0.8, b: 0.8, a: 1.};
+
+#[derive(Clone, Copy)]
+enum Callback {
+    /// The value of the cvar shown in the row
+    Value(usize),
+    Back,
+}
+
+/// One cvar in the list
+struct Row {
+    name: PosText,
+    description: PosText,
+}
+
+/// Lists every cvar with its value, default and description so they can be found and changed
+/// without knowing the console
+///
+/// Typing searches the names and descriptions. Clicking a value flips it if it's a boolean
+/// and lets a new one be typed otherwise, and right clicking it puts it back to its default.
+pub struct Advanced {
+    title_txt: PosText,
+    search_txt: PosText,
+    query: String,
+    /// Names of the cvars matching the search
+    matches: Vec<&'static str>,
+    /// Index in `matches` of the first cvar shown
+    scroll: usize,
+    rows: Vec<Row>,
+    buttons: Vec<Button<Callback>>,
+    /// The cvar whose value is being typed and what's been typed so far
+    editing: Option<(&'static str, String)>,
+}
+
+impl Advanced {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(ctx: &mut Context, s: &mut State) -> GameResult<Box<dyn GameState>> {
+        let (w, h) = (s.width, s.height);
+        // Room is left at the bottom for the back button
+        let row_count = ((h - TOP - 80.) / ROW_HEIGHT).max(1.) as usize;
+
+        let mut rows = Vec::with_capacity(row_count);
+        let mut buttons = Vec::with_capacity(row_count + 1);
+        for i in 0..row_count {
+            let y = TOP + i as f32 * ROW_HEIGHT;
+            rows.push(Row {
+                name: s.assets.text(Point2::new(16., y)).and_text(""),
+                description: s.assets.text_sized(Point2::new(16., y + 22.), 14.).and_text(""),
+            });
+            let rect = Rect{x: 0.65 * w, y, w: 0.3 * w, h: ROW_HEIGHT - 8.};
+            buttons.push(Button::new(ctx, &s.assets, rect, "", Callback::Value(i))?);
+        }
+        buttons.push(Button::new(ctx, &s.assets, Rect{x: 2. * w / 7., y: h - 72., w: 3. * w / 7., h: 64.}, "Back", Callback::Back)?);
+
+        let mut advanced = Advanced {
+            title_txt: s.assets.text_sized(Point2::new(w / 2., 16.), 32.).and_text("Advanced"),
+            search_txt: s.assets.text(Point2::new(16., 48.)).and_text(""),
+            query: String::new(),
+            matches: Vec::new(),
+            scroll: 0,
+            rows,
+            buttons,
+            editing: None,
+        };
+        advanced.search(s);
+        Ok(Box::new(advanced))
+    }
+    /// Finds the cvars matching the search again
+    fn search(&mut self, s: &State) {
+        let query = self.query.to_lowercase();
+        self.matches = s.cvars.iter()
+            .filter(|(name, cvar)| name.contains(&query) || cvar.description.to_lowercase().contains(&query))
+            .map(|(name, _)| name)
+            .collect();
+        self.scroll = 0;
+        self.update_labels(s);
+    }
+    fn scroll_by(&mut self, s: &State, rows: isize) {
+        let max = self.matches.len().saturating_sub(self.rows.len());
+        self.scroll = (self.scroll as isize + rows).max(0).min(max as isize) as usize;
+        self.update_labels(s);
+    }
+    /// The cvar shown in a row
+    fn cvar_at(&self, row: usize) -> Option<&'static str> {
+        self.matches.get(self.scroll + row).copied()
+    }
+    fn update_labels(&mut self, s: &State) {
+        let search = if self.query.is_empty() { "Type to search".to_owned() } else { format!("Search: {}_", self.query) };
+        self.search_txt.text.fragments_mut()[0] = search.into();
+        for i in 0..self.rows.len() {
+            let cvar = self.cvar_at(i).and_then(|name| s.cvars.get(name).map(|c| (name, c)));
+            let row = &mut self.rows[i];
+            let (name, description) = match cvar {
+                Some((name, cvar)) => (
+                    format!("{} (default {})", name, cvar.default),
+                    cvar.description.to_owned(),
+                ),
+                None => (String::new(), String::new()),
+            };
+            row.name.text.fragments_mut()[0] = name.into();
+            row.description.text.fragments_mut()[0] = description.into();
+        }
+        let labels: Vec<_> = self.buttons.iter().map(|button| match button.callback {
+            Callback::Value(i) => match self.cvar_at(i) {
+                Some(name) => match self.editing {
+                    Some((editing, ref typed)) if editing == name => format!("{}_", typed),
+                    _ => s.cvars.get(name).map_or_else(String::new, |c| c.value.to_string()),
+                },
+                None => String::new(),
+            },
+            Callback::Back => "Back".to_owned(),
+        }).collect();
+        for (button, label) in self.buttons.iter_mut().zip(labels) {
+            button.set_text(&label);
+        }
+    }
+    fn click(&mut self, s: &mut State, callback: Callback) {
+        match callback {
+            Callback::Value(i) => {
+                let name = match self.cvar_at(i) {
+                    Some(name) => name,
+                    None => return,
+                };
+                self.finish_editing(s);
+                match s.cvars.get(name).map(|c| c.value) {
+                    Some(CvarValue::Bool(b)) => {
+                        if let Err(e) = s.cvars.set(name, CvarValue::Bool(!b)) {
+                            error!("Couldn't set {}: {}", name, e);
+                        }
+                    }
+                    Some(value) => self.editing = Some((name, value.to_string())),
+                    None => (),
+                }
+            }
+            Callback::Back => {
+                s.switch(StateSwitch::Options);
+                return
+            }
+        }
+        self.update_labels(s);
+    }
+    /// Sets the cvar being typed to what's been typed
+    fn finish_editing(&mut self, s: &mut State) {
+        if let Some((name, typed)) = self.editing.take() {
+            if let Err(e) = s.cvars.set_str(name, typed.trim(), 1) {
+                s.notify(&format!("Couldn't set {}: {}", name, e));
+            }
+        }
+    }
+    /// The button under the cursor
+    fn hovered(&self, s: &State) -> Option<Callback> {
+        self.buttons.iter().find(|b| b.in_bounds(s.mouse)).map(|b| b.callback)
+    }
+}
+
+impl GameState for Advanced {
+    fn name(&self) -> &'static str {
+        "advanced"
+    }
+    fn logic(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        let dt = frame_time(ctx);
+        for button in &mut self.buttons {
+            button.update(dt, s.mouse);
+        }
+        Ok(())
+    }
+    fn draw_hud(&mut self, _s: &State, ctx: &mut Context) -> GameResult<()> {
+        self.title_txt.draw_center(ctx)?;
+        self.search_txt.draw_text(ctx)?;
+        for row in &self.rows {
+            row.name.draw_text(ctx)?;
+            graphics::draw(ctx, &row.description.text, DrawParam::new().dest(row.description.pos).color(DESCRIPTION_COLOR))?;
+        }
+        for button in &self.buttons {
+            button.draw(ctx)?;
+        }
+        Ok(())
+    }
+    fn event_up(&mut self, s: &mut State, _ctx: &mut Context, event: Event) {
+        match event {
+            Mouse(MouseButton::Left) => if let Some(callback) = self.hovered(s) {
+                self.click(s, callback);
+            }
+            Mouse(MouseButton::Right) => if let Some(Callback::Value(i)) = self.hovered(s) {
+                if let Some(name) = self.cvar_at(i) {
+                    self.editing = None;
+                    if let Err(e) = s.cvars.reset(name) {
+                        error!("Couldn't reset {}: {}", name, e);
+                    }
+                    self.update_labels(s);
+                }
+            }
+            Key(KeyCode::Return) => {
+                self.finish_editing(s);
+                self.update_labels(s);
+            }
+            Key(KeyCode::Escape) => {
+                if self.editing.take().is_some() {
+                    self.update_labels(s);
+                } else if !self.query.is_empty() {
+                    self.query.clear();
+                    self.search(s);
+                } else {
+                    self.click(s, Callback::Back);
+                }
+            }
+            Key(KeyCode::Up) => self.scroll_by(s, -1),
+            Key(KeyCode::Down) => self.scroll_by(s, 1),
+            Key(KeyCode::PageUp) => self.scroll_by(s, -(self.rows.len() as isize)),
+            Key(KeyCode::PageDown) => self.scroll_by(s, self.rows.len() as isize),
+            _ => (),
+        }
+    }
+    fn text_input(&mut self, s: &mut State, _ctx: &mut Context, c: char) {
+        let typed = match self.editing {
+            Some((_, ref mut typed)) => typed,
+            None => &mut self.query,
+        };
+        match c {
+            // Backspace
+            '\u{8}' => {typed.pop();}
+            c if c.is_control() => return,
+            c => typed.push(c),
+        }
+        if self.editing.is_some() {
+            self.update_labels(s);
+        } else {
+            self.search(s);
+        }
+    }
+}
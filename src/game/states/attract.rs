@@ -0,0 +1,91 @@
+use std::f32::consts::PI;
+
+use crate::{
+    util::{Point2, Vector2, angle_to_vec},
+    io::tex::PosText,
+    ext::Timer,
+    game::{
+        State, GameState, StateSwitch,
+        world::World,
+        event::Event,
+    },
+};
+use ggez::{Context, GameResult};
+use rand::{thread_rng, Rng};
+
+/// Seconds the player walks in one direction before turning
+const WANDER_TIME: f32 = 2.;
+
+/// Shows the game playing itself while nobody is using it
+pub struct Attract {
+    world: World,
+    prompt: PosText,
+    /// Direction the player is walking in
+    dir: Vector2,
+    turn: Timer,
+}
+
+fn random_dir() -> Vector2 {
+    angle_to_vec(thread_rng().gen_range(-PI, PI))
+}
+
+impl Attract {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(ctx: &mut Context, s: &mut State) -> GameResult<Box<dyn GameState>> {
+        Ok(Box::new(Attract {
+            world: World::load_default(ctx, &s.enemies)?,
+            prompt: s.assets.text_sized(Point2::new(s.width / 2., s.height - 64.), 32.).and_text("Press any key"),
+            dir: random_dir(),
+            turn: Timer::started(WANDER_TIME),
+        }))
+    }
+}
+
+impl GameState for Attract {
+    fn name(&self) -> &'static str {
+        "attract"
+    }
+    fn on_enter(&mut self, s: &mut State, _ctx: &mut Context) -> GameResult<()> {
+        s.camera.snap_to(self.world.player.obj.pos);
+        Ok(())
+    }
+    fn update(&mut self, s: &mut State, _ctx: &mut Context) -> GameResult<()> {
+        let dt = s.delta();
+        let before = self.world.player.obj.pos;
+        self.world.player.update(&self.world.grid, self.dir, dt);
+        self.world.update(dt, &mut s.rng);
+
+        // Turn every now and then, and right away when stuck against a wall
+        if self.turn.update(dt) || self.world.player.obj.pos == before {
+            self.dir = random_dir();
+            self.turn.restart();
+        }
+        Ok(())
+    }
+    fn logic(&mut self, s: &mut State, _ctx: &mut Context) -> GameResult<()> {
+        let p = self.world.player.obj.pos;
+        s.camera.follow(p);
+        self.world.player.obj.look_at(p + self.dir);
+        Ok(())
+    }
+    fn draw(&mut self, s: &State, ctx: &mut Context) -> GameResult<()> {
+        self.world.draw(ctx, &s.assets, s.view_rect())
+    }
+    fn draw_hud(&mut self, s: &State, ctx: &mut Context) -> GameResult<()> {
+        // Blink the prompt once a second
+        if s.ticks / 30 % 2 == 0 {
+            self.prompt.draw_center(ctx)?;
+        }
+        Ok(())
+    }
+    fn event_down(&mut self, s: &mut State, _ctx: &mut Context, _: Event) {
+        s.switch(StateSwitch::Menu);
+    }
+
+    fn get_world(&self) -> Option<&World> {
+        Some(&self.world)
+    }
+    fn get_mut_world(&mut self) -> Option<&mut World> {
+        Some(&mut self.world)
+    }
+}
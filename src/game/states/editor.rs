@@ -0,0 +1,730 @@
+use crate::{
+    util::{Point2, Vector2, frame_time},
+    io::{tex::PosText, input::{Action, TickInput}},
+    game::{
+        State, GameState, StateSwitch, Console, Args, ArgType, CommandError, CommandInfo, Completion,
+        cvars::CvarValue,
+        world::{World, Grid, Tile, Material, Player, Sign, Enemy, Anchor, Transform, level_path},
+        states::play::Play,
+        event::{Event::{self, Mouse, Key}, MouseButton, KeyCode},
+    },
+};
+use ggez::{
+    Context, GameResult,
+    graphics::{self, DrawMode, DrawParam, Rect, Mesh, MeshBuilder, Color},
+    filesystem,
+};
+
+mod resize;
+use resize::{ResizeDialog, Choice, parse_size};
+
+/// Pixels per second the view moves at, unzoomed
+const PAN_SPEED: f32 = 600.;
+/// Size in tiles of levels that don't exist yet
+const NEW_SIZE: u16 = 32;
+/// Colour of the lines painting is mirrored about
+const SYMMETRY_COLOUR: Color = Color{r: 1., g: 0.4, b: 0.8, a: 0.6};
+const RULER_COLOUR: Color = Color{r: 1., g: 1., b: 0.2, a: 0.9};
+const GRID_COLOUR: Color = Color{r: 1., g: 1., b: 1., a: 0.15};
+const HOVER_COLOUR: Color = Color{r: 1., g: 1., b: 1., a: 0.8};
+const STATUS_BAR_COLOUR: Color = Color{r: 0., g: 0., b: 0., a: 0.6};
+/// Fewest pixels on the screen between grid lines, below which they're not drawn
+const MIN_GRID_SPACING: f32 = 6.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Which mirrored copies of painted tiles are painted too, mirrored about the middle of the level
+enum Symmetry {
+    Off,
+    /// Mirrored left to right
+    Horizontal,
+    /// Mirrored top to bottom
+    Vertical,
+    /// Mirrored both ways, painting all four corners
+    Quad,
+}
+
+const SYMMETRIES: [(&str, Symmetry); 4] = [
+    ("off", Symmetry::Off),
+    ("horizontal", Symmetry::Horizontal),
+    ("vertical", Symmetry::Vertical),
+    ("quad", Symmetry::Quad),
+];
+
+impl Symmetry {
+    fn name(self) -> &'static str {
+        SYMMETRIES.iter().find(|&&(_, s)| s == self).map_or("", |&(name, _)| name)
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        SYMMETRIES.iter().find(|&&(n, _)| n == name).map(|&(_, s)| s)
+    }
+    fn next(self) -> Self {
+        let i = SYMMETRIES.iter().position(|&(_, s)| s == self).unwrap_or(0);
+        SYMMETRIES[(i + 1) % SYMMETRIES.len()].1
+    }
+    fn mirrors_x(self) -> bool {
+        self == Symmetry::Horizontal || self == Symmetry::Quad
+    }
+    fn mirrors_y(self) -> bool {
+        self == Symmetry::Vertical || self == Symmetry::Quad
+    }
+    /// A tile in the grid and its mirrored copies, which can be the same tile in the middle
+    fn tiles(self, (x, y): Tile, grid: &Grid) -> Vec<Tile> {
+        let (mx, my) = (grid.width() - 1 - x, grid.height() - 1 - y);
+        let mut tiles = vec![(x, y)];
+        if self.mirrors_x() {
+            tiles.push((mx, y));
+        }
+        if self.mirrors_y() {
+            tiles.push((x, my));
+        }
+        if self.mirrors_x() && self.mirrors_y() {
+            tiles.push((mx, my));
+        }
+        tiles
+    }
+}
+
+/// Names of where the tiles already there end up when resizing, for the `resize` command
+const ANCHORS: [(&str, Anchor, Anchor); 9] = [
+    ("topleft", Anchor::Start, Anchor::Start),
+    ("top", Anchor::Center, Anchor::Start),
+    ("topright", Anchor::End, Anchor::Start),
+    ("left", Anchor::Start, Anchor::Center),
+    ("center", Anchor::Center, Anchor::Center),
+    ("right", Anchor::End, Anchor::Center),
+    ("bottomleft", Anchor::Start, Anchor::End),
+    ("bottom", Anchor::Center, Anchor::End),
+    ("bottomright", Anchor::End, Anchor::End),
+];
+
+#[derive(Debug, Default)]
+/// Measures the distance between two clicked points and the area of the rectangle between them,
+/// for checking things like weapon ranges against a level
+struct Ruler {
+    start: Option<Point2>,
+    end: Option<Point2>,
+}
+
+impl Ruler {
+    /// The points measured between, the end following `cursor` until it's been clicked
+    fn points(&self, cursor: Point2) -> Option<(Point2, Point2)> {
+        Some((self.start?, self.end.unwrap_or(cursor)))
+    }
+    /// Puts down the end if only the start is down, and otherwise starts measuring again from `p`
+    fn click(&mut self, p: Point2) {
+        if self.start.is_some() && self.end.is_none() {
+            self.end = Some(p);
+        } else {
+            self.start = Some(p);
+            self.end = None;
+        }
+    }
+    fn readout(&self, cursor: Point2) -> String {
+        let (a, b) = match self.points(cursor) {
+            Some(points) => points,
+            None => return "Ruler: click to start measuring".to_owned(),
+        };
+        let dist = (b - a).norm();
+        let (w, h) = ((b.x - a.x).abs(), (b.y - a.y).abs());
+        // Counting every tile the rectangle touches
+        let ((x0, y0), (x1, y1)) = (Grid::snap(a), Grid::snap(b));
+        let tiles_w = (i32::from(x1) - i32::from(x0)).abs() + 1;
+        let tiles_h = (i32::from(y1) - i32::from(y0)).abs() + 1;
+        format!(
+            "Distance {:.1} tiles ({:.0} px)\nArea {}x{} tiles ({} tiles), {:.0}x{:.0} px",
+            dist / 32., dist, tiles_w, tiles_h, tiles_w * tiles_h, w, h,
+        )
+    }
+    /// Draws the line measured along and the rectangle between its ends
+    fn draw(&self, ctx: &mut Context, cursor: Point2) -> GameResult<()> {
+        let (a, b) = match self.points(cursor) {
+            Some(points) => points,
+            None => return Ok(()),
+        };
+        let rect = Rect::new(a.x.min(b.x), a.y.min(b.y), (b.x - a.x).abs(), (b.y - a.y).abs());
+        let mut ruler = MeshBuilder::new();
+        ruler.rectangle(DrawMode::stroke(1.), rect, Color{a: 0.4, .. RULER_COLOUR});
+        if a != b {
+            ruler.line(&[a, b], 2., RULER_COLOUR)?;
+        }
+        ruler.circle(DrawMode::fill(), a, 3., 0.5, RULER_COLOUR);
+        ruler.circle(DrawMode::fill(), b, 3., 0.5, RULER_COLOUR);
+        let ruler = ruler.build(ctx)?;
+        graphics::draw(ctx, &ruler, DrawParam::new())
+    }
+}
+
+/// Loads a level to edit, or makes a new one if there's none with the name
+fn load_level(ctx: &mut Context, s: &State, level: &str) -> GameResult<World> {
+    if level == "default" {
+        World::load_default(ctx, &s.enemies)
+    } else if filesystem::exists(ctx, level_path(level)) {
+        World::load(ctx, &s.enemies, level_path(level))
+    } else {
+        Ok(World::new(NEW_SIZE, NEW_SIZE))
+    }
+}
+
+/// Edits levels by painting materials onto the grid and placing things at the cursor
+///
+/// The view is moved with the movement keys. Left clicking paints the material picked with
+/// the number keys or the brackets, and right clicking clears tiles, M going through the ways
+/// painting can be mirrored. G shows or hides the lines between tiles. R opens the resize
+/// dialog. T switches to the ruler, which clicking measures with instead of painting. The
+/// restart key plays the level from the cursor, which pause goes back from. Pausing while
+/// editing goes back to the menu.
+pub struct Editor {
+    world: World,
+    /// Name of the level, which it's saved as
+    level: String,
+    /// Whether the level has changed since it was saved
+    modified: bool,
+    /// Whether leaving has been asked about since the level was changed, so pausing again leaves anyway
+    warned: bool,
+    /// Center of the view
+    view: Point2,
+    palette: Vec<Material>,
+    /// Index in `palette` of the material painted with
+    mat: usize,
+    /// The material being painted while a mouse button is held
+    painting: Option<Material>,
+    symmetry: Symmetry,
+    /// The ruler while it's being used instead of painting
+    ruler: Option<Ruler>,
+    ruler_text: PosText,
+    status_text: PosText,
+    /// Background of the status text along the top of the screen
+    status_bar: Mesh,
+    /// The level being played to test it and where it's played from, the world being edited left as it was
+    test: Option<(Play, Point2)>,
+    /// The dialog for resizing the level while it's open
+    resizing: Option<ResizeDialog>,
+}
+
+impl Editor {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(ctx: &mut Context, s: &mut State, level: String) -> GameResult<Box<dyn GameState>> {
+        let world = load_level(ctx, s, &level)?;
+        Ok(Box::new(Editor {
+            view: world.spawn,
+            world,
+            level,
+            modified: false,
+            warned: false,
+            palette: Material::palette(),
+            mat: 0,
+            painting: None,
+            symmetry: Symmetry::Off,
+            ruler: None,
+            ruler_text: s.assets.text(Point2::new(4., 28.)).and_text(""),
+            status_text: s.assets.text(Point2::new(4., 4.)).and_text(""),
+            status_bar: Mesh::new_rectangle(ctx, DrawMode::fill(), Rect::new(0., 0., s.width, 24.), STATUS_BAR_COLOUR)?,
+            test: None,
+            resizing: None,
+        }))
+    }
+    /// The point in the world under the cursor
+    #[inline]
+    fn cursor(s: &State) -> Point2 {
+        s.screen_to_world(s.mouse)
+    }
+    fn pick(&mut self, i: usize) {
+        if !self.palette.is_empty() {
+            self.mat = i % self.palette.len();
+        }
+    }
+    /// Plays the level from `from`, leaving the world being edited as it is
+    fn start_test(&mut self, s: &mut State, ctx: &mut Context, from: Point2) -> GameResult<()> {
+        if let Some((mut play, _)) = self.test.take() {
+            play.on_exit(s, ctx)?;
+        }
+        // Going through the level format makes a copy, like saving and loading it would
+        let mut world = World::from_bytes(&s.enemies, &self.world.to_bytes())?;
+        world.spawn = from;
+        world.player = Player::new(from);
+        let mut play = Play::with_world(ctx, s, world, true)?;
+        play.on_enter(s, ctx)?;
+        self.painting = None;
+        self.test = Some((play, from));
+        Ok(())
+    }
+    /// Goes back to editing the world as it was before the test
+    fn stop_test(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        if let Some((mut play, _)) = self.test.take() {
+            play.on_exit(s, ctx)?;
+            s.camera.snap_to(self.view);
+        }
+        Ok(())
+    }
+    /// Ends the test if the level being tested tried to leave, like when it's won or lost
+    fn catch_exit(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        if self.test.is_some() && s.switch_state.take().is_some() {
+            self.stop_test(s, ctx)?;
+        }
+        Ok(())
+    }
+    /// Resizes the level, keeping the view on the same tiles
+    fn resize(&mut self, width: u16, height: u16, anchor_x: Anchor, anchor_y: Anchor) {
+        let (old_width, old_height) = (self.world.grid.width(), self.world.grid.height());
+        let before = self.world.spawn;
+        self.world.resize(width, height, anchor_x, anchor_y);
+        self.view += self.world.spawn - before;
+        if (width, height) != (old_width, old_height) {
+            self.modified = true;
+            self.warned = false;
+        }
+    }
+    fn open_resize_dialog(&mut self, ctx: &mut Context, s: &State) {
+        match ResizeDialog::new(ctx, &s.assets, &self.world.grid, s.width, s.height) {
+            Ok(dialog) => self.resizing = Some(dialog),
+            Err(e) => error!("Couldn't open the resize dialog: {}", e),
+        }
+    }
+    fn resize_command(&mut self, ctx: &mut Context, s: &State, args: Args) -> Result<(), CommandError> {
+        if args.len() < 2 {
+            self.open_resize_dialog(ctx, s);
+            return Ok(())
+        }
+        let size = |i| parse_size(args.string(i)?).ok_or(CommandError::InvalidArg{index: i, expected: ArgType::Int});
+        let (width, height) = (size(1)?, size(2)?);
+        let (_, anchor_x, anchor_y) = match args.get(3) {
+            Some(name) => *ANCHORS.iter().find(|&&(n, _, _)| n == name)
+                .ok_or(CommandError::InvalidArg{index: 3, expected: ArgType::Str})?,
+            None => ANCHORS[0],
+        };
+        self.resize(width, height, anchor_x, anchor_y);
+        Ok(())
+    }
+    /// Draws the lines between the tiles in view and outlines the tile under the cursor
+    ///
+    /// They're drawn on the screen instead of in the world, so they stay thin however far it's zoomed.
+    fn draw_overlay(&self, s: &State, ctx: &mut Context) -> GameResult<()> {
+        let grid = &self.world.grid;
+        let size = grid.size();
+        let mut overlay = MeshBuilder::new();
+        let mut empty = true;
+
+        // The part of the view with tiles in it
+        let view = s.view_rect();
+        let (left, right) = (view.x.max(0.), (view.x + view.w).min(size.x));
+        let (top, bottom) = (view.y.max(0.), (view.y + view.h).min(size.y));
+        if s.cvars.bool("editor_grid") && s.camera.zoom() * 32. >= MIN_GRID_SPACING && left < right && top < bottom {
+            let (x0, x1) = ((left / 32.).ceil() as u32, (right / 32.).floor() as u32);
+            let (y0, y1) = ((top / 32.).ceil() as u32, (bottom / 32.).floor() as u32);
+            for x in (x0..=x1).map(|x| x as f32 * 32.) {
+                let line = [s.world_to_screen(Point2::new(x, top)), s.world_to_screen(Point2::new(x, bottom))];
+                overlay.line(&line, 1., GRID_COLOUR)?;
+                empty = false;
+            }
+            for y in (y0..=y1).map(|y| y as f32 * 32.) {
+                let line = [s.world_to_screen(Point2::new(left, y)), s.world_to_screen(Point2::new(right, y))];
+                overlay.line(&line, 1., GRID_COLOUR)?;
+                empty = false;
+            }
+        }
+        let (x, y) = Grid::snap(Self::cursor(s));
+        if grid.get(x, y).is_some() {
+            let corner = s.world_to_screen(Point2::new(f32::from(x) * 32., f32::from(y) * 32.));
+            let far_corner = s.world_to_screen(Point2::new(f32::from(x) * 32. + 32., f32::from(y) * 32. + 32.));
+            let rect = Rect::new(corner.x, corner.y, far_corner.x - corner.x, far_corner.y - corner.y);
+            overlay.rectangle(DrawMode::stroke(2.), rect, HOVER_COLOUR);
+            empty = false;
+        }
+        // Meshes can't be built without anything in them
+        if empty {
+            return Ok(())
+        }
+        let overlay = overlay.build(ctx)?;
+        graphics::draw(ctx, &overlay, DrawParam::new())
+    }
+    fn toggle_ruler(&mut self) {
+        self.ruler = match self.ruler {
+            Some(_) => None,
+            None => Some(Ruler::default()),
+        };
+    }
+    /// Draws the lines painting is mirrored about
+    fn draw_symmetry(&self, ctx: &mut Context) -> GameResult<()> {
+        if self.symmetry == Symmetry::Off {
+            return Ok(())
+        }
+        let size = self.world.grid.size();
+        let mut lines = MeshBuilder::new();
+        if self.symmetry.mirrors_x() {
+            lines.line(&[Point2::new(size.x / 2., 0.), Point2::new(size.x / 2., size.y)], 2., SYMMETRY_COLOUR)?;
+        }
+        if self.symmetry.mirrors_y() {
+            lines.line(&[Point2::new(0., size.y / 2.), Point2::new(size.x, size.y / 2.)], 2., SYMMETRY_COLOUR)?;
+        }
+        let lines = lines.build(ctx)?;
+        graphics::draw(ctx, &lines, DrawParam::new())
+    }
+    fn symmetry_command(&mut self, args: Args) -> Result<(), CommandError> {
+        self.symmetry = Symmetry::from_name(args.string(1)?)
+            .ok_or(CommandError::InvalidArg{index: 1, expected: ArgType::Str})?;
+        Ok(())
+    }
+    fn transform_command(&mut self, args: Args) -> Result<(), CommandError> {
+        let transform = match (args.cmd(), args.string(1)?) {
+            ("rotate", "cw") => Transform::RotateCw,
+            ("rotate", "ccw") => Transform::RotateCcw,
+            ("mirror", "horizontal") => Transform::MirrorHorizontal,
+            ("mirror", "vertical") => Transform::MirrorVertical,
+            _ => return Err(CommandError::InvalidArg{index: 1, expected: ArgType::Str}),
+        };
+        // The view stays on the same tiles
+        self.view = transform.point(self.view, self.world.grid.size());
+        self.world.transform(transform);
+        Ok(())
+    }
+    fn save_command(&mut self, ctx: &mut Context, args: Args) -> Result<(), CommandError> {
+        let name = match args.get(1) {
+            Some(_) => args.name(1)?.to_owned(),
+            None => self.level.clone(),
+        };
+        match self.world.save(ctx, level_path(&name)) {
+            Ok(()) => {
+                info!("Saved level {}", name);
+                self.level = name;
+                self.modified = false;
+            }
+            Err(e) => error!("Couldn't save level {}: {}", name, e),
+        }
+        Ok(())
+    }
+    fn enemy_command(&mut self, s: &State, args: Args, pos: Point2) -> Result<(), CommandError> {
+        let enemy = match args.get(1) {
+            Some(kind) => {
+                let def = s.enemies.get(kind).ok_or(CommandError::NoSuchEnemy)?;
+                Enemy::with_def(pos, kind.to_owned(), def.clone())
+            }
+            None => Enemy::new(pos),
+        };
+        self.world.enemies.push(enemy);
+        Ok(())
+    }
+    fn load_command(&mut self, ctx: &mut Context, s: &mut State, args: Args) -> Result<(), CommandError> {
+        let name = args.name(1)?;
+        match load_level(ctx, s, name) {
+            Ok(world) => {
+                self.view = world.spawn;
+                s.camera.snap_to(self.view);
+                self.world = world;
+                self.level = name.to_owned();
+                self.modified = false;
+                Ok(())
+            }
+            Err(e) => {
+                error!("Couldn't load level {}: {}", name, e);
+                Err(CommandError::NoSuchLevel)
+            }
+        }
+    }
+}
+
+const COMMANDS: &[CommandInfo] = &[
+    CommandInfo::new("savelevel", "[name]", "Saves the level, under another name if one is given").completing(Completion::Levels),
+    CommandInfo::new("loadlevel", "<name>", "Edits another level, making it if it doesn't exist").completing(Completion::Levels),
+    CommandInfo::new("spawn", "", "Moves the spawn point to the cursor"),
+    CommandInfo::new("exit", "", "Moves the level's exit to the cursor"),
+    CommandInfo::new("clearexit", "", "Removes the level's exit"),
+    CommandInfo::new("sign", "<text>", "Puts a sign at the cursor"),
+    CommandInfo::new("enemy", "[kind]", "Puts an enemy at the cursor").completing(Completion::Enemies),
+    CommandInfo::new("resize", "[width] [height] [anchor]", "Resizes the level, keeping the top left or another edge or the center in place, or opens the resize dialog"),
+    CommandInfo::new("rotate", "<cw|ccw>", "Turns the whole level a quarter turn clockwise or counterclockwise"),
+    CommandInfo::new("mirror", "<horizontal|vertical>", "Flips the whole level left to right or top to bottom"),
+    CommandInfo::new("symmetry", "<off|horizontal|vertical|quad>", "Paints mirrored copies of what's painted, mirrored about the middle of the level"),
+    CommandInfo::new("ruler", "", "Switches between painting and measuring distances and areas with the mouse"),
+    CommandInfo::new("playtest", "", "Plays the level from the cursor, or goes back to editing it"),
+];
+
+impl GameState for Editor {
+    fn name(&self) -> &'static str {
+        "editor"
+    }
+    fn on_enter(&mut self, s: &mut State, _ctx: &mut Context) -> GameResult<()> {
+        s.camera.snap_to(self.view);
+        Ok(())
+    }
+    fn on_exit(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        self.stop_test(s, ctx)
+    }
+    fn on_pause(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        self.painting = None;
+        match self.test {
+            Some((ref mut play, _)) => play.on_pause(s, ctx),
+            None => Ok(()),
+        }
+    }
+    fn update(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        if let Some((ref mut play, _)) = self.test {
+            play.update(s, ctx)?;
+            return self.catch_exit(s, ctx)
+        }
+        Ok(())
+    }
+    fn logic(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        if let Some((ref mut play, _)) = self.test {
+            return play.logic(s, ctx)
+        }
+        if let Some(ref mut dialog) = self.resizing {
+            dialog.update(frame_time(ctx), s.mouse);
+            return Ok(())
+        }
+        let held = TickInput { held: s.input.held(ctx), .. TickInput::default() };
+        let pan = Vector2::new(
+            held.axis(Action::MoveLeft, Action::MoveRight),
+            held.axis(Action::MoveUp, Action::MoveDown),
+        );
+        self.view += pan * PAN_SPEED / s.camera.zoom() * frame_time(ctx);
+        s.camera.snap_to(self.view);
+
+        let (x, y) = Grid::snap(Self::cursor(s));
+        if let Some(mat) = self.painting.filter(|_| self.world.grid.get(x, y).is_some()) {
+            for (x, y) in self.symmetry.tiles((x, y), &self.world.grid) {
+                if self.world.grid.get(x, y).map_or(false, |m| m != mat) {
+                    self.world.grid.insert(x, y, mat);
+                    self.modified = true;
+                    self.warned = false;
+                }
+            }
+        }
+        let mat = self.palette.get(self.mat).map_or("", |m| m.name());
+        let modified = if self.modified { "*" } else { "" };
+        let symmetry = match self.symmetry {
+            Symmetry::Off => String::new(),
+            symmetry => format!("  mirrored {}", symmetry.name()),
+        };
+        let hovered = match self.world.grid.get(x, y) {
+            Some(m) => format!("({}, {}) {}", x, y, m.name()),
+            None => "outside the level".to_owned(),
+        };
+        self.status_text.update(0, format!("{}{}  painting {}{}  |  {}", self.level, modified, mat, symmetry, hovered))?;
+        if let Some(ref ruler) = self.ruler {
+            self.ruler_text.update(0, ruler.readout(Self::cursor(s)))?;
+        }
+        Ok(())
+    }
+    fn draw(&mut self, s: &State, ctx: &mut Context) -> GameResult<()> {
+        match self.test {
+            Some((ref mut play, _)) => play.draw(s, ctx),
+            None => {
+                self.world.draw(ctx, &s.assets, s.view_rect())?;
+                self.draw_symmetry(ctx)?;
+                match self.ruler {
+                    Some(ref ruler) => ruler.draw(ctx, Self::cursor(s)),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+    fn draw_hud(&mut self, s: &State, ctx: &mut Context) -> GameResult<()> {
+        match self.test {
+            Some((ref mut play, _)) => play.draw_hud(s, ctx),
+            None => {
+                self.draw_overlay(s, ctx)?;
+                graphics::draw(ctx, &self.status_bar, DrawParam::new())?;
+                self.status_text.draw_text(ctx)?;
+                if self.ruler.is_some() {
+                    self.ruler_text.draw_text(ctx)?;
+                }
+                match self.resizing {
+                    Some(ref dialog) => dialog.draw(ctx),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+    fn event_down(&mut self, s: &mut State, ctx: &mut Context, event: Event) {
+        let result = match self.test {
+            // The test is played over from the same spot, and ended instead of paused
+            Some((_, from)) if s.input.is(event, Action::Restart) => self.start_test(s, ctx, from),
+            Some(_) if s.input.is(event, Action::Pause) => self.stop_test(s, ctx),
+            Some((ref mut play, _)) => {
+                play.event_down(s, ctx, event);
+                self.catch_exit(s, ctx)
+            }
+            // Anything else waits until the dialog is closed
+            None if self.resizing.is_some() => {
+                if s.input.is(event, Action::Pause) {
+                    self.resizing = None;
+                }
+                Ok(())
+            }
+            None if s.input.is(event, Action::Restart) => self.start_test(s, ctx, Self::cursor(s)),
+            None if s.input.is(event, Action::Pause) => {
+                if self.modified && !self.warned {
+                    s.notify("The level has unsaved changes, pause again to leave anyway");
+                    self.warned = true;
+                } else {
+                    s.switch(StateSwitch::Menu);
+                }
+                Ok(())
+            }
+            None => {
+                match event {
+                    Mouse(MouseButton::Left) => match self.ruler {
+                        Some(ref mut ruler) => ruler.click(Self::cursor(s)),
+                        None => self.painting = self.palette.get(self.mat).copied(),
+                    }
+                    // Right clicking with the ruler clears what it measured
+                    Mouse(MouseButton::Right) => match self.ruler {
+                        Some(ref mut ruler) => *ruler = Ruler::default(),
+                        None => self.painting = Some(Material::default()),
+                    }
+                    _ => (),
+                }
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            error!("Couldn't play the level: {}", e);
+        }
+    }
+    fn event_up(&mut self, s: &mut State, ctx: &mut Context, event: Event) {
+        if let Some((ref mut play, _)) = self.test {
+            play.event_up(s, ctx, event);
+            if let Err(e) = self.catch_exit(s, ctx) {
+                error!("Couldn't stop playing the level: {}", e);
+            }
+            return
+        }
+        if let Some(ref mut dialog) = self.resizing {
+            let choice = match event {
+                Mouse(MouseButton::Left) => dialog.click(s.mouse),
+                Key(KeyCode::Return) => dialog.confirm(),
+                Key(KeyCode::Tab) => {
+                    dialog.next_field();
+                    None
+                }
+                _ => None,
+            };
+            match choice {
+                Some(Choice::Resize(width, height, anchor_x, anchor_y)) => {
+                    self.resizing = None;
+                    self.resize(width, height, anchor_x, anchor_y);
+                }
+                Some(Choice::Cancel) => self.resizing = None,
+                None => (),
+            }
+            return
+        }
+        match event {
+            Mouse(MouseButton::Left) | Mouse(MouseButton::Right) => self.painting = None,
+            Key(KeyCode::R) => self.open_resize_dialog(ctx, s),
+            Key(KeyCode::M) => self.symmetry = self.symmetry.next(),
+            Key(KeyCode::T) => self.toggle_ruler(),
+            Key(KeyCode::G) => {
+                let shown = s.cvars.bool("editor_grid");
+                if let Err(e) = s.cvars.set("editor_grid", CvarValue::Bool(!shown)) {
+                    error!("Couldn't toggle the grid: {}", e);
+                }
+            }
+            Key(KeyCode::LBracket) => self.pick(self.mat + self.palette.len().saturating_sub(1)),
+            Key(KeyCode::RBracket) => self.pick(self.mat + 1),
+            Key(key) => {
+                let numbers = [
+                    KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, KeyCode::Key5,
+                    KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+                ];
+                if let Some(i) = numbers.iter().position(|&k| k == key).filter(|&i| i < self.palette.len()) {
+                    self.mat = i;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn text_input(&mut self, _s: &mut State, _ctx: &mut Context, c: char) {
+        if let Some(ref mut dialog) = self.resizing {
+            dialog.text_input(c);
+        }
+    }
+
+    fn commands(&self) -> &'static [CommandInfo] {
+        COMMANDS
+    }
+    fn command(&mut self, s: &mut State, ctx: &mut Context, args: Args) -> Option<Result<(), CommandError>> {
+        if args.cmd() == "playtest" {
+            let result = if self.test.is_some() {
+                self.stop_test(s, ctx)
+            } else {
+                self.start_test(s, ctx, Self::cursor(s))
+            };
+            if let Err(e) = result {
+                error!("Couldn't play the level: {}", e);
+            }
+            return Some(Ok(()))
+        }
+        // Everything else edits, which isn't done while testing
+        if let Some((ref mut play, _)) = self.test {
+            return play.command(s, ctx, args)
+        }
+        let pos = Self::cursor(s);
+        let result = match args.cmd() {
+            "savelevel" => self.save_command(ctx, args),
+            "loadlevel" => self.load_command(ctx, s, args),
+            "spawn" => {
+                self.world.spawn = pos;
+                self.world.player = Player::new(pos);
+                Ok(())
+            }
+            "exit" => {
+                self.world.exit = Some(pos);
+                Ok(())
+            }
+            "clearexit" => {
+                self.world.exit = None;
+                Ok(())
+            }
+            "sign" => {
+                self.world.signs.push(Sign::new(pos, &args.rest(1)));
+                Ok(())
+            }
+            "enemy" => self.enemy_command(s, args, pos),
+            "rotate" | "mirror" => self.transform_command(args),
+            // Doesn't change the level itself
+            "symmetry" => return Some(self.symmetry_command(args)),
+            "ruler" => {
+                self.toggle_ruler();
+                return Some(Ok(()))
+            }
+            // Marks the level modified itself, since just opening the dialog doesn't change it
+            "resize" => return Some(self.resize_command(ctx, s, args)),
+            _ => return None,
+        };
+        if result.is_ok() && args.cmd() != "savelevel" {
+            self.modified = true;
+            self.warned = false;
+        }
+        Some(result)
+    }
+
+    fn uses_aim(&self) -> bool {
+        self.test.as_ref().map_or(false, |(play, _)| play.uses_aim())
+    }
+    fn get_world(&self) -> Option<&World> {
+        match self.test {
+            Some((ref play, _)) => play.get_world(),
+            None => Some(&self.world),
+        }
+    }
+    fn get_mut_world(&mut self) -> Option<&mut World> {
+        match self.test {
+            Some((ref mut play, _)) => play.get_mut_world(),
+            None => Some(&mut self.world),
+        }
+    }
+}
+
+/// Adds the editor commands to the console
+pub fn register_commands(console: &mut Console) {
+    console.register(CommandInfo::new("edit", "[level]", "Edits a level, the default one if none is given").completing(Completion::Levels), |_, _, state, _, args| {
+        let level = match args.get(1) {
+            Some(_) => args.name(1)?,
+            None => "default",
+        };
+        state.switch(StateSwitch::Editor(level.to_owned()));
+        Ok(())
+    });
+}
@@ -0,0 +1,159 @@
+use crate::{
+    util::Point2,
+    io::{tex::{Assets, PosText}, btn::Button},
+    game::world::{Grid, Anchor},
+};
+use ggez::{
+    Context, GameResult,
+    graphics::{self, DrawMode, DrawParam, Rect, Mesh, Color},
+};
+
+/// Most tiles along each side a level can be resized to
+pub const MAX_SIZE: u16 = 512;
+const BACKGROUND: Color = Color{r: 0., g: 0., b: 0., a: 0.7};
+/// Side length of the anchor buttons
+const ANCHOR_SIZE: f32 = 40.;
+const ANCHORS: [Anchor; 3] = [Anchor::Start, Anchor::Center, Anchor::End];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Width,
+    Height,
+}
+
+#[derive(Clone, Copy)]
+enum Callback {
+    Field(Field),
+    Anchor(Anchor, Anchor),
+    Apply,
+    Cancel,
+}
+
+/// What was chosen in the dialog
+pub enum Choice {
+    Resize(u16, u16, Anchor, Anchor),
+    Cancel,
+}
+
+/// Parses a side length typed into the dialog
+pub fn parse_size(s: &str) -> Option<u16> {
+    s.trim().parse().ok().filter(|n| (1..=MAX_SIZE).contains(n))
+}
+
+/// Asks what size to make the level and which of its edges or its center to keep in place
+///
+/// The width and height are typed after clicking them, tab going between them. The 3×3 buttons
+/// pick where the tiles already there end up, like the top left corner or the middle.
+pub struct ResizeDialog {
+    title_txt: PosText,
+    width: String,
+    height: String,
+    editing: Field,
+    anchor: (Anchor, Anchor),
+    buttons: Vec<Button<Callback>>,
+    background: Mesh,
+}
+
+impl ResizeDialog {
+    pub fn new(ctx: &mut Context, assets: &Assets, grid: &Grid, screen_width: f32, screen_height: f32) -> GameResult<Self> {
+        let (cx, cy) = (screen_width / 2., screen_height / 2.);
+        let mut buttons = vec![
+            Button::new(ctx, assets, Rect{x: cx - 208., y: cy - 120., w: 200., h: 48.}, "", Callback::Field(Field::Width))?,
+            Button::new(ctx, assets, Rect{x: cx + 8., y: cy - 120., w: 200., h: 48.}, "", Callback::Field(Field::Height))?,
+        ];
+        for (j, &anchor_y) in ANCHORS.iter().enumerate() {
+            for (i, &anchor_x) in ANCHORS.iter().enumerate() {
+                let rect = Rect {
+                    x: cx - ANCHOR_SIZE / 2. + (i as f32 - 1.) * (ANCHOR_SIZE + 4.),
+                    y: cy - 56. + j as f32 * (ANCHOR_SIZE + 4.),
+                    w: ANCHOR_SIZE,
+                    h: ANCHOR_SIZE,
+                };
+                buttons.push(Button::new(ctx, assets, rect, "", Callback::Anchor(anchor_x, anchor_y))?);
+            }
+        }
+        buttons.push(Button::new(ctx, assets, Rect{x: cx - 208., y: cy + 96., w: 200., h: 48.}, "Resize", Callback::Apply)?);
+        buttons.push(Button::new(ctx, assets, Rect{x: cx + 8., y: cy + 96., w: 200., h: 48.}, "Cancel", Callback::Cancel)?);
+
+        let mut dialog = ResizeDialog {
+            title_txt: assets.text_sized(Point2::new(cx, cy - 168.), 32.).and_text("Resize level"),
+            width: grid.width().to_string(),
+            height: grid.height().to_string(),
+            editing: Field::Width,
+            anchor: (Anchor::Start, Anchor::Start),
+            buttons,
+            background: Mesh::new_rectangle(ctx, DrawMode::fill(), Rect::new(0., 0., screen_width, screen_height), BACKGROUND)?,
+        };
+        dialog.update_labels();
+        Ok(dialog)
+    }
+    fn update_labels(&mut self) {
+        for button in &mut self.buttons {
+            let label = match button.callback {
+                Callback::Field(field) => {
+                    let (name, value) = match field {
+                        Field::Width => ("Width", &self.width),
+                        Field::Height => ("Height", &self.height),
+                    };
+                    let cursor = if field == self.editing { "_" } else { "" };
+                    format!("{} {}{}", name, value, cursor)
+                }
+                Callback::Anchor(x, y) => if (x, y) == self.anchor { "X".to_owned() } else { String::new() },
+                Callback::Apply => "Resize".to_owned(),
+                Callback::Cancel => "Cancel".to_owned(),
+            };
+            button.set_text(&label);
+        }
+    }
+    pub fn update(&mut self, dt: f32, mouse: Point2) {
+        for button in &mut self.buttons {
+            button.update(dt, mouse);
+        }
+    }
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        graphics::draw(ctx, &self.background, DrawParam::new())?;
+        self.title_txt.draw_center(ctx)?;
+        for button in &self.buttons {
+            button.draw(ctx)?;
+        }
+        Ok(())
+    }
+    /// Resizes with what's been typed, if it's a size a level can be
+    pub fn confirm(&self) -> Option<Choice> {
+        let (anchor_x, anchor_y) = self.anchor;
+        Some(Choice::Resize(parse_size(&self.width)?, parse_size(&self.height)?, anchor_x, anchor_y))
+    }
+    /// Clicks the button at `p`, returning what was chosen if it was the resize or cancel button
+    pub fn click(&mut self, p: Point2) -> Option<Choice> {
+        let callback = self.buttons.iter().find(|b| b.in_bounds(p))?.callback;
+        match callback {
+            Callback::Field(field) => self.editing = field,
+            Callback::Anchor(x, y) => self.anchor = (x, y),
+            Callback::Apply => return self.confirm(),
+            Callback::Cancel => return Some(Choice::Cancel),
+        }
+        self.update_labels();
+        None
+    }
+    /// Goes to the other field
+    pub fn next_field(&mut self) {
+        self.editing = match self.editing {
+            Field::Width => Field::Height,
+            Field::Height => Field::Width,
+        };
+        self.update_labels();
+    }
+    pub fn text_input(&mut self, c: char) {
+        let typed = match self.editing {
+            Field::Width => &mut self.width,
+            Field::Height => &mut self.height,
+        };
+        match c {
+            // Backspace
+            '\u{8}' => {typed.pop();}
+            c if c.is_ascii_digit() && typed.len() < 4 => typed.push(c),
+            _ => return,
+        }
+        self.update_labels();
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{
+    util::{Point2, Vector2, GREEN, RED},
+    io::tex::Assets,
+    game::world::{World, PLAYER_RADIUS, ENEMY_RADIUS, COMPANION_RADIUS},
+};
+use ggez::{
+    Context, GameResult,
+    graphics::{self, DrawMode, DrawParam, Rect, Mesh, MeshBuilder, Color},
+};
+
+/// Pixels from the center of the selection to the ends of the gizmo's axis handles
+const HANDLE_LENGTH: f32 = 40.;
+/// How close to the end of a handle the crosshair has to be to grab it
+const HANDLE_RADIUS: f32 = 6.;
+/// How close to a sign the crosshair has to be to pick it
+const SIGN_RADIUS: f32 = 16.;
+/// Most lines of the inspector panel shown, the rest being cut off
+const MAX_LINES: usize = 40;
+const SELECTION_COLOR: Color = Color{r: 1., g: 1., b: 0., a: 1.};
+const PANEL_BACKGROUND: Color = Color{r: 0., g: 0., b: 0., a: 0.7};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Something in the world that can be inspected
+///
+/// Enemies and signs are picked by their index, which belongs to another one if one before it goes away.
+enum Selection {
+    Player,
+    Companion,
+    Enemy(usize),
+    Sign(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Which way a grabbed selection can be dragged
+enum Axis {
+    Free,
+    X,
+    Y,
+}
+
+fn position(world: &World, sel: Selection) -> Option<Point2> {
+    match sel {
+        Selection::Player => Some(world.player.obj.pos),
+        Selection::Companion => world.companion.as_ref().map(|c| c.obj.pos),
+        Selection::Enemy(i) => world.enemies.get(i).map(|e| e.obj.pos),
+        Selection::Sign(i) => world.signs.get(i).map(|s| s.pos),
+    }
+}
+
+fn position_mut(world: &mut World, sel: Selection) -> Option<&mut Point2> {
+    match sel {
+        Selection::Player => Some(&mut world.player.obj.pos),
+        Selection::Companion => world.companion.as_mut().map(|c| &mut c.obj.pos),
+        Selection::Enemy(i) => world.enemies.get_mut(i).map(|e| &mut e.obj.pos),
+        Selection::Sign(i) => world.signs.get_mut(i).map(|s| &mut s.pos),
+    }
+}
+
+fn radius(sel: Selection) -> f32 {
+    match sel {
+        Selection::Player => PLAYER_RADIUS,
+        Selection::Companion => COMPANION_RADIUS,
+        Selection::Enemy(_) => ENEMY_RADIUS,
+        Selection::Sign(_) => SIGN_RADIUS,
+    }
+}
+
+/// Everything about the selection, as the lines of the panel
+fn describe(world: &World, sel: Selection) -> String {
+    match sel {
+        Selection::Player => format!("Player {:#?}", world.player),
+        Selection::Companion => world.companion.as_ref().map_or_else(String::new, |c| format!("Companion {:#?}", c)),
+        Selection::Enemy(i) => world.enemies.get(i).map_or_else(String::new, |e| format!("Enemy #{} {:#?}", i, e)),
+        Selection::Sign(i) => world.signs.get(i).map_or_else(String::new, |s| format!("Sign #{} {:#?}", i, s)),
+    }
+}
+
+#[derive(Debug, Default)]
+/// Picks things in the world with the crosshair to show what they're made of and drag them around,
+/// for debugging
+pub struct Inspector {
+    selected: Option<Selection>,
+    /// The handle being dragged and how far from the crosshair the selection is
+    dragging: Option<(Axis, Vector2)>,
+}
+
+impl Inspector {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Grabs a handle of the selection or selects whatever is at `p`, returning whether anything was
+    pub fn grab(&mut self, world: &World, p: Point2) -> bool {
+        if let Some(center) = self.selected.and_then(|sel| position(world, sel)) {
+            let handles = [
+                (Axis::X, center + Vector2::new(HANDLE_LENGTH, 0.)),
+                (Axis::Y, center + Vector2::new(0., HANDLE_LENGTH)),
+            ];
+            if let Some(&(axis, _)) = handles.iter().find(|&&(_, h)| (p - h).norm() < HANDLE_RADIUS) {
+                self.dragging = Some((axis, center - p));
+                return true
+            }
+        }
+        let picked = Some(Selection::Player).into_iter()
+            .chain(world.companion.as_ref().map(|_| Selection::Companion))
+            .chain((0..world.enemies.len()).map(Selection::Enemy))
+            .chain((0..world.signs.len()).map(Selection::Sign))
+            .filter_map(|sel| position(world, sel).map(|pos| (sel, pos)))
+            .find(|&(sel, pos)| (p - pos).norm() < radius(sel));
+        self.selected = picked.map(|(sel, _)| sel);
+        self.dragging = picked.map(|(_, pos)| (Axis::Free, pos - p));
+        picked.is_some()
+    }
+    /// Lets go of what's being dragged
+    #[inline]
+    pub fn release(&mut self) {
+        self.dragging = None;
+    }
+    /// Moves what's being dragged along with the crosshair at `p`, and forgets the selection
+    /// if it's gone from the world
+    pub fn update(&mut self, world: &mut World, p: Point2) {
+        let sel = match self.selected {
+            Some(sel) => sel,
+            None => return,
+        };
+        let pos = match position_mut(world, sel) {
+            Some(pos) => pos,
+            None => {
+                self.selected = None;
+                self.dragging = None;
+                return
+            }
+        };
+        if let Some((axis, offset)) = self.dragging {
+            let target = p + offset;
+            match axis {
+                Axis::Free => *pos = target,
+                Axis::X => pos.x = target.x,
+                Axis::Y => pos.y = target.y,
+            }
+        }
+    }
+    /// Draws the gizmo on the selection in the world
+    pub fn draw(&self, ctx: &mut Context, world: &World) -> GameResult<()> {
+        let (sel, center) = match self.selected.and_then(|sel| position(world, sel).map(|pos| (sel, pos))) {
+            Some(selected) => selected,
+            None => return Ok(()),
+        };
+        let x_end = center + Vector2::new(HANDLE_LENGTH, 0.);
+        let y_end = center + Vector2::new(0., HANDLE_LENGTH);
+        let gizmo = MeshBuilder::new()
+            .circle(DrawMode::stroke(2.), center, radius(sel) + 4., 0.5, SELECTION_COLOR)
+            .line(&[center, x_end], 2., RED)?
+            .circle(DrawMode::fill(), x_end, HANDLE_RADIUS, 0.5, RED)
+            .line(&[center, y_end], 2., GREEN)?
+            .circle(DrawMode::fill(), y_end, HANDLE_RADIUS, 0.5, GREEN)
+            .build(ctx)?;
+        graphics::draw(ctx, &gizmo, DrawParam::new())
+    }
+    /// Draws the panel showing everything about the selection at the right of the screen
+    pub fn draw_panel(&self, ctx: &mut Context, assets: &Assets, world: &World, screen_width: f32) -> GameResult<()> {
+        let description = match self.selected {
+            Some(sel) => describe(world, sel),
+            None => return Ok(()),
+        };
+        let mut lines: Vec<_> = description.lines().take(MAX_LINES + 1).collect();
+        if lines.len() > MAX_LINES {
+            lines[MAX_LINES] = "...";
+        }
+        let text = assets.raw_text_with(&lines.join("\n"), 12.);
+        let (w, h) = text.dimensions(ctx);
+        let (w, h) = (w as f32, h as f32);
+        let pos = Point2::new(screen_width - w - 8., 8.);
+        let background = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect::new(pos.x - 4., pos.y - 4., w + 8., h + 8.), PANEL_BACKGROUND)?;
+        graphics::draw(ctx, &background, DrawParam::new())?;
+        graphics::draw(ctx, &text, DrawParam::new().dest(pos))
+    }
+}
@@ -0,0 +1,199 @@
+//! A menu for listening to the music tracks that have been heard while playing
+//!
+//! Which tracks have been heard is kept in the current profile's saves, one name per line.
+
+use std::collections::BTreeSet;
+
+use crate::{
+    util::{Point2, frame_time},
+    io::{
+        tex::PosText,
+        btn::Button,
+        save::Saves,
+        snd::{Playlist, PlaylistMode, SoundCategory},
+    },
+    game::{
+        State, GameState, StateSwitch,
+        cvars::CvarValue,
+        event::{Event::{self, Mouse, Key}, MouseButton, KeyCode},
+    },
+};
+use ggez::{
+    Context, GameResult,
+    graphics::Rect,
+    filesystem,
+};
+
+/// Name of the save the unlocked tracks are kept in
+const SAVE_NAME: &str = "jukebox";
+/// Seconds a track takes to fade over to another one picked in the jukebox
+const PREVIEW_FADE: f32 = 0.5;
+
+/// The tracks that have been heard, and so can be played in the jukebox
+pub fn unlocked(saves: &mut Saves) -> BTreeSet<String> {
+    match saves.read(SAVE_NAME) {
+        Ok(Some(bytes)) => String::from_utf8_lossy(&bytes).lines().map(str::to_owned).collect(),
+        Ok(None) => BTreeSet::new(),
+        Err(e) => {
+            error!("Couldn't read the unlocked tracks: {}", e);
+            BTreeSet::new()
+        }
+    }
+}
+
+/// Unlocks tracks in the jukebox, for when they're heard
+pub fn unlock<'a, I: IntoIterator<Item=&'a str>>(saves: &mut Saves, tracks: I) {
+    let mut tracks_unlocked = unlocked(saves);
+    let before = tracks_unlocked.len();
+    tracks_unlocked.extend(tracks.into_iter().map(str::to_owned));
+    if tracks_unlocked.len() == before {
+        return
+    }
+    let text = tracks_unlocked.into_iter().collect::<Vec<_>>().join("\n");
+    if let Err(e) = saves.write(SAVE_NAME, text.as_bytes()) {
+        error!("Couldn't save the unlocked tracks: {}", e);
+    }
+}
+
+/// The playlist to play while playing, every unlocked track shuffled if the `music_shuffle` cvar is on
+/// and the level's own music otherwise
+pub fn ingame_playlist(s: &mut State, level: &Playlist) -> Playlist {
+    if !s.cvars.bool("music_shuffle") {
+        return level.clone()
+    }
+    let tracks: Vec<_> = unlocked(&mut s.saves).into_iter().collect();
+    if tracks.is_empty() {
+        return level.clone()
+    }
+    Playlist {
+        tracks,
+        mode: PlaylistMode::Shuffle,
+        .. Playlist::default()
+    }
+}
+
+#[derive(Clone)]
+enum Callback {
+    /// Plays the track, or stops it if it's playing
+    Track(String),
+    Shuffle,
+    Back,
+}
+
+fn button_rect(w: f32, i: f32) -> Rect {
+    Rect{x: 2. * w / 7., y: 64. + i * 68., w: 3. * w / 7., h: 64.}
+}
+
+/// Lists the music tracks so they can be listened to
+pub struct Jukebox {
+    title_txt: PosText,
+    buttons: Vec<Button<Callback>>,
+    unlocked: BTreeSet<String>,
+}
+
+impl Jukebox {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(ctx: &mut Context, s: &mut State) -> GameResult<Box<dyn GameState>> {
+        let w = s.width;
+        let listed = s.mplayer.manifest().iter()
+            .filter(|(_, e)| e.category == SoundCategory::Music)
+            .map(|(name, _)| name.to_owned());
+        let tracks: BTreeSet<String> = filesystem::read_dir(ctx, "/sounds")?
+            .filter(|p| p.extension().map_or(false, |e| e == "ogg"))
+            .filter_map(|p| p.file_stem()?.to_str().map(str::to_owned))
+            .chain(listed)
+            .collect();
+        let mut tracks: Vec<_> = tracks.into_iter().collect();
+        // Only as many as fit on the screen along with the other buttons
+        let rows = ((s.height - 64.) / 68.).max(2.) as usize - 2;
+        tracks.truncate(rows);
+
+        let mut buttons = Vec::with_capacity(tracks.len() + 2);
+        let callbacks = tracks.into_iter().map(Callback::Track).chain(vec![Callback::Shuffle, Callback::Back]);
+        for (i, callback) in callbacks.enumerate() {
+            buttons.push(Button::new(ctx, &s.assets, button_rect(w, i as f32), "", callback)?);
+        }
+
+        let mut jukebox = Jukebox {
+            title_txt: s.assets.text_sized(Point2::new(w / 2., 16.), 32.).and_text("Jukebox"),
+            buttons,
+            unlocked: unlocked(&mut s.saves),
+        };
+        jukebox.update_labels(s);
+        Ok(Box::new(jukebox))
+    }
+    fn update_labels(&mut self, s: &State) {
+        let playing = s.mplayer.playing_track();
+        for button in &mut self.buttons {
+            let label = match button.callback {
+                Callback::Track(ref track) if !self.unlocked.contains(track) => "???".to_owned(),
+                Callback::Track(ref track) if playing == Some(track.as_str()) => format!("■ {}", track),
+                Callback::Track(ref track) => format!("▶ {}", track),
+                Callback::Shuffle => {
+                    let shuffle = s.cvars.bool("music_shuffle");
+                    format!("In-game: {}", if shuffle { "Shuffle all" } else { "Level music" })
+                }
+                Callback::Back => "Back".to_owned(),
+            };
+            button.set_text(&label);
+        }
+    }
+    fn click(&mut self, s: &mut State, ctx: &mut Context, callback: Callback) {
+        let played = match callback {
+            Callback::Track(ref track) if !self.unlocked.contains(track) => return,
+            Callback::Track(ref track) if s.mplayer.playing_track() == Some(track.as_str()) => {
+                s.mplayer.stop_music(ctx, PREVIEW_FADE)
+            }
+            Callback::Track(track) => s.mplayer.play_music(ctx, Playlist::single(track), PREVIEW_FADE),
+            Callback::Shuffle => {
+                let shuffle = s.cvars.bool("music_shuffle");
+                if let Err(e) = s.cvars.set("music_shuffle", CvarValue::Bool(!shuffle)) {
+                    error!("Couldn't set music_shuffle: {}", e);
+                }
+                Ok(())
+            }
+            Callback::Back => {
+                s.switch(StateSwitch::Menu);
+                return
+            }
+        };
+        if let Err(e) = played {
+            error!("Couldn't play the music: {}", e);
+        }
+        self.update_labels(s);
+    }
+}
+
+impl GameState for Jukebox {
+    fn name(&self) -> &'static str {
+        "jukebox"
+    }
+    fn logic(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        let dt = frame_time(ctx);
+        for button in &mut self.buttons {
+            button.update(dt, s.mouse);
+        }
+        // Tracks stop by themselves too
+        self.update_labels(s);
+        Ok(())
+    }
+    fn draw_hud(&mut self, _s: &State, ctx: &mut Context) -> GameResult<()> {
+        self.title_txt.draw_center(ctx)?;
+        for button in &self.buttons {
+            button.draw(ctx)?;
+        }
+        Ok(())
+    }
+    fn event_up(&mut self, s: &mut State, ctx: &mut Context, event: Event) {
+        match event {
+            Mouse(MouseButton::Left) => {
+                let clicked = self.buttons.iter().find(|b| b.in_bounds(s.mouse)).map(|b| b.callback.clone());
+                if let Some(callback) = clicked {
+                    self.click(s, ctx, callback);
+                }
+            }
+            Key(KeyCode::Escape) => self.click(s, ctx, Callback::Back),
+            _ => (),
+        }
+    }
+}
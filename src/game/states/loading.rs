@@ -0,0 +1,115 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    util::{Point2, Vector2, GREEN},
+    io::tex::PosText,
+    game::{
+        State, GameState, StateSwitch,
+        states::play::{load_world, level_music},
+    },
+};
+use ggez::{
+    Context, GameResult,
+    graphics::{self, DrawMode, DrawParam, Rect, Mesh, Color},
+};
+
+/// Longest time spent loading in one frame, so the progress bar keeps moving
+const FRAME_BUDGET: Duration = Duration::from_millis(12);
+const BAR_WIDTH: f32 = 400.;
+const BAR_HEIGHT: f32 = 16.;
+const BAR_BACKGROUND: Color = Color{r: 0.2, g: 0.2, b: 0.2, a: 1.};
+/// Sprites used by every level, outside of what's in it
+const COMMON_SPRITES: &[&str] = &["common/hole", "common/crosshair", "common/shield", "common/particle"];
+
+enum Asset {
+    Sprite(String),
+    Sound(String),
+    Music(String),
+}
+
+/// Loads everything the level about to be played uses, so sprites and sounds aren't loaded
+/// in the middle of playing it, then switches to playing it
+///
+/// ggez's context can't be used outside the main thread, so instead of on a background thread,
+/// the assets are loaded a few at a time each frame while a progress bar is shown.
+pub struct Loading {
+    /// Assets left to load, the next one last
+    queue: Vec<Asset>,
+    total: usize,
+    text: PosText,
+    /// Top left corner of the progress bar
+    bar_pos: Point2,
+    background: Mesh,
+    bar: Mesh,
+}
+
+impl Loading {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(ctx: &mut Context, s: &mut State) -> GameResult<Box<dyn GameState>> {
+        let world = load_world(ctx, s)?;
+        let mut list = world.assets();
+        // The campaign's music is played in levels without their own
+        let (music, ambience) = level_music(s, &world);
+        list.music.extend(music.tracks.into_iter().chain(music.combat).chain(ambience));
+        let mut queue: Vec<_> = COMMON_SPRITES.iter().map(|&spr| Asset::Sprite(spr.to_owned()))
+            .chain(list.sprites.into_iter().map(Asset::Sprite))
+            .chain(list.sounds.into_iter().map(Asset::Sound))
+            .chain(list.music.into_iter().map(Asset::Music))
+            .collect();
+        queue.reverse();
+
+        let center = Point2::new(s.width / 2., s.height / 2.);
+        let bar_rect = Rect::new(center.x - BAR_WIDTH / 2., center.y, BAR_WIDTH, BAR_HEIGHT);
+        Ok(Box::new(Loading {
+            total: queue.len(),
+            queue,
+            text: s.assets.text_sized(center - Vector2::new(0., 32.), 32.).and_text("Loading"),
+            bar_pos: Point2::new(bar_rect.x, bar_rect.y),
+            background: Mesh::new_rectangle(ctx, DrawMode::fill(), bar_rect, BAR_BACKGROUND)?,
+            // Stretched horizontally to show the progress
+            bar: Mesh::new_rectangle(ctx, DrawMode::fill(), Rect::new(0., 0., BAR_WIDTH, BAR_HEIGHT), GREEN)?,
+        }))
+    }
+    fn progress(&self) -> f32 {
+        if self.total == 0 {
+            1.
+        } else {
+            1. - self.queue.len() as f32 / self.total as f32
+        }
+    }
+}
+
+impl GameState for Loading {
+    fn name(&self) -> &'static str {
+        "loading"
+    }
+    fn logic(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        let start = Instant::now();
+        while start.elapsed() < FRAME_BUDGET {
+            let loaded = match self.queue.pop() {
+                Some(Asset::Sprite(spr)) => {
+                    s.assets.get_img(ctx, &spr);
+                    Ok(())
+                }
+                Some(Asset::Sound(snd)) => s.mplayer.preload_effect(ctx, &snd),
+                Some(Asset::Music(track)) => s.mplayer.preload_music(ctx, &track),
+                None => {
+                    s.switch(StateSwitch::Play);
+                    break
+                }
+            };
+            // Whatever's missing is just not played, like it would be without loading it first
+            if let Err(e) = loaded {
+                warn!("Couldn't preload: {}", e);
+            }
+        }
+        Ok(())
+    }
+    fn draw_hud(&mut self, _s: &State, ctx: &mut Context) -> GameResult<()> {
+        self.text.draw_center(ctx)?;
+        graphics::draw(ctx, &self.background, DrawParam::new())?;
+        graphics::draw(ctx, &self.bar, DrawParam::new()
+            .dest(self.bar_pos)
+            .scale(Vector2::new(self.progress(), 1.)))
+    }
+}
@@ -1,11 +1,14 @@
 use crate::{
-    util::Point2,
+    util::{Point2, frame_time},
     io::{
         tex::PosText,
         btn::Button,
+        save::list_profiles,
+        snd::{Playlist, MUSIC_FADE},
     },
     game::{
-        State, GameState, StateSwitch,
+        State, GameState, StateSwitch, Args, CommandError, CommandInfo,
+        states::jukebox,
         event::{Event::{self, Mouse}, MouseButton}
     },
 };
@@ -19,10 +22,20 @@ pub struct Menu {
     title_txt: PosText,
     buttons: Vec<Button<Callback>>,
     corner_text: Option<PosText>,
+    /// Seconds since the menu was last used
+    idle: f32,
+    last_mouse: Point2,
+    /// Whether the console is open over the menu
+    paused: bool,
 }
 
 enum Callback {
     SwitchPlay,
+    SwitchOptions,
+    /// Switches to the next local profile
+    SwitchProfile,
+    SwitchJukebox,
+    SwitchEditor,
 }
 
 // ↓
@@ -30,25 +43,89 @@ fn button_rect(w: f32, i: f32) -> Rect {
     Rect{x:3. * w / 7., y: 64. + i * 68., w:w / 7., h:64.}
 }
 
+/// The profile after `current` in the list of profiles, going back to the first one after the last
+///
+/// `None` if there are no other profiles to switch to
+fn next_profile(ctx: &Context, current: &str) -> Option<String> {
+    let names = match list_profiles(ctx) {
+        Ok(names) => names,
+        Err(e) => {
+            error!("Couldn't list the profiles: {}", e);
+            return None
+        }
+    };
+    let i = names.iter().position(|name| name == current).map_or(0, |i| (i + 1) % names.len());
+    Some(names[i].clone()).filter(|name| name != current)
+}
+
 impl Menu {
     #[allow(clippy::new_ret_no_self)]
     pub fn new(ctx: &mut Context, s: &mut State) -> GameResult<Box<dyn GameState>> {
         let w = s.width as f32;
 
         let corner_text = None;
-        s.mplayer.play(ctx, "music")?;
 
-        let buttons = vec![Button::new(ctx, &s.assets, button_rect(w, 0.), "Play", Callback::SwitchPlay)?];
+        let mut buttons = vec![Button::new(ctx, &s.assets, button_rect(w, 0.), "Play", Callback::SwitchPlay)?];
+        // A kiosk keeps the settings and profile it was started with
+        if !s.kiosk {
+            buttons.push(Button::new(ctx, &s.assets, button_rect(w, 1.), "Options", Callback::SwitchOptions)?);
+            let profile = format!("Profile: {}", s.saves.profile());
+            buttons.push(Button::new(ctx, &s.assets, button_rect(w, 2.), &profile, Callback::SwitchProfile)?);
+            buttons.push(Button::new(ctx, &s.assets, button_rect(w, 3.), "Jukebox", Callback::SwitchJukebox)?);
+            buttons.push(Button::new(ctx, &s.assets, button_rect(w, 4.), "Editor", Callback::SwitchEditor)?);
+        }
 
         Ok(Box::new(Menu {
             title_txt: s.assets.text_sized(Point2::new(w / 2., 16.), 32.).and_text("Main Menu"),
             buttons,
             corner_text,
+            idle: 0.,
+            last_mouse: s.mouse,
+            paused: false,
         }))
     }
 }
 
+const COMMANDS: &[CommandInfo] = &[
+    CommandInfo::new("play", "", "Starts playing"),
+];
+
 impl GameState for Menu {
+    fn name(&self) -> &'static str {
+        "menu"
+    }
+    fn on_enter(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        // Keeps playing into whatever's next, which fades over to its own music
+        jukebox::unlock(&mut s.saves, Some("music"));
+        s.mplayer.play_music(ctx, Playlist::single("music"), MUSIC_FADE)
+    }
+    fn on_pause(&mut self, _s: &mut State, _ctx: &mut Context) -> GameResult<()> {
+        self.paused = true;
+        Ok(())
+    }
+    fn on_resume(&mut self, _s: &mut State, _ctx: &mut Context) -> GameResult<()> {
+        self.paused = false;
+        self.idle = 0.;
+        Ok(())
+    }
+    fn logic(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        let dt = frame_time(ctx);
+        for button in &mut self.buttons {
+            button.update(dt, s.mouse);
+        }
+
+        if self.paused || s.mouse != self.last_mouse {
+            self.idle = 0.;
+            self.last_mouse = s.mouse;
+        } else {
+            self.idle += dt;
+        }
+        let attract_time = s.cvars.float("attract_time");
+        if attract_time > 0. && self.idle >= attract_time {
+            s.switch(StateSwitch::Attract);
+        }
+        Ok(())
+    }
     fn draw_hud(&mut self, _s: &State, ctx: &mut Context) -> GameResult<()> {
         self.title_txt.draw_center(ctx)?;
         if let Some(ref txt) = self.corner_text {
@@ -59,18 +136,45 @@ impl GameState for Menu {
         }
         Ok(())
     }
+    fn event_down(&mut self, _s: &mut State, _ctx: &mut Context, _: Event) {
+        self.idle = 0.;
+    }
     fn event_up(&mut self, s: &mut State, ctx: &mut Context, event: Event) {
         if let Mouse(MouseButton::Left) = event {
             for button in &self.buttons {
                 if button.in_bounds(s.mouse) {
-                    s.mplayer.stop(ctx, "music").unwrap();
                     match &button.callback {
                         Callback::SwitchPlay => {
-                            s.switch(StateSwitch::Play);
+                            s.switch(StateSwitch::Loading);
+                        },
+                        Callback::SwitchOptions => {
+                            s.switch(StateSwitch::Options);
+                        },
+                        Callback::SwitchProfile => match next_profile(ctx, s.saves.profile()) {
+                            Some(profile) => s.switch(StateSwitch::Profile(profile)),
+                            None => s.notify("No other profiles, make one with the profile command"),
+                        },
+                        Callback::SwitchJukebox => {
+                            s.switch(StateSwitch::Jukebox);
+                        },
+                        Callback::SwitchEditor => {
+                            s.switch(StateSwitch::Editor("default".to_owned()));
                         },
                     }
                 }
             }
         }
     }
+    fn commands(&self) -> &'static [CommandInfo] {
+        COMMANDS
+    }
+    fn command(&mut self, s: &mut State, _ctx: &mut Context, args: Args) -> Option<Result<(), CommandError>> {
+        match args.cmd() {
+            "play" => {
+                s.switch(StateSwitch::Loading);
+            }
+            _ => return None,
+        }
+        Some(Ok(()))
+    }
 }
@@ -1,2 +1,12 @@
 pub mod play;
-pub mod menu;
\ No newline at end of file
+pub mod loading;
+pub mod menu;
+pub mod attract;
+pub mod pause;
+pub mod inspector;
+pub mod results;
+pub mod editor;
+pub mod options;
+pub mod advanced;
+pub mod jukebox;
+pub mod replay;
@@ -0,0 +1,221 @@
+use crate::{
+    util::{Point2, frame_time},
+    io::{
+        tex::PosText,
+        btn::Button,
+        config::RESOLUTIONS,
+        input::Action,
+    },
+    game::{
+        State, GameState, StateSwitch,
+        event::{Event::{self, Mouse, Key}, MouseButton, KeyCode},
+    },
+};
+use ggez::{
+    Context, GameResult,
+    graphics::Rect,
+};
+
+/// Lets the player change the settings in the config and what the keys do
+pub struct Options {
+    title_txt: PosText,
+    buttons: Vec<Button<Callback>>,
+    /// The action whose binding is being changed to the next key or mouse button let go of
+    rebinding: Option<Action>,
+}
+
+#[derive(Clone, Copy)]
+enum Callback {
+    Resolution,
+    Fullscreen,
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    /// Goes to the list of cvars
+    Advanced,
+    Back,
+    Bind(Action),
+}
+
+/// The actions that can be rebound here and what they're called
+const BINDINGS: &[(Action, &str)] = &[
+    (Action::MoveUp, "Move up"),
+    (Action::MoveDown, "Move down"),
+    (Action::MoveLeft, "Move left"),
+    (Action::MoveRight, "Move right"),
+    (Action::Shoot, "Shoot"),
+    (Action::Block, "Block"),
+    (Action::Reload, "Reload"),
+    (Action::Mark, "Mark"),
+    (Action::Command, "Command"),
+    (Action::Emote, "Emote"),
+    (Action::Pause, "Pause"),
+    (Action::Console, "Console"),
+    (Action::Restart, "Restart"),
+    (Action::Minimap, "Minimap"),
+];
+
+fn button_rect(w: f32, i: f32) -> Rect {
+    Rect{x: w / 14., y: 64. + i * 68., w: 5. * w / 14., h: 64.}
+}
+/// Where the button for the `i`th binding goes, in a column of smaller buttons next to the settings
+fn binding_rect(w: f32, i: f32) -> Rect {
+    Rect{x: 8. * w / 14., y: 64. + i * 40., w: 5. * w / 14., h: 36.}
+}
+
+/// Turns the volume up by a tenth, going back to silent after full
+fn next_volume(v: f32) -> f32 {
+    let next = (v * 10.).round() + 1.;
+    if next > 10. { 0. } else { next / 10. }
+}
+
+impl Options {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(ctx: &mut Context, s: &mut State) -> GameResult<Box<dyn GameState>> {
+        let w = s.width;
+        let callbacks = [
+            Callback::Resolution,
+            Callback::Fullscreen,
+            Callback::MasterVolume,
+            Callback::MusicVolume,
+            Callback::SfxVolume,
+            Callback::Advanced,
+            Callback::Back,
+        ];
+        let mut buttons = Vec::with_capacity(callbacks.len() + BINDINGS.len());
+        for (i, &callback) in callbacks.iter().enumerate() {
+            buttons.push(Button::new(ctx, &s.assets, button_rect(w, i as f32), "", callback)?);
+        }
+        for (i, &(action, _)) in BINDINGS.iter().enumerate() {
+            buttons.push(Button::new(ctx, &s.assets, binding_rect(w, i as f32), "", Callback::Bind(action))?);
+        }
+
+        let mut options = Options {
+            title_txt: s.assets.text_sized(Point2::new(w / 2., 16.), 32.).and_text("Options"),
+            buttons,
+            rebinding: None,
+        };
+        options.update_labels(s);
+        Ok(Box::new(options))
+    }
+    fn update_labels(&mut self, s: &State) {
+        let c = &s.config;
+        for button in &mut self.buttons {
+            let label = match button.callback {
+                Callback::Resolution => format!("Resolution: {}×{}", c.width, c.height),
+                Callback::Fullscreen => format!("Fullscreen: {}", if c.fullscreen { "On" } else { "Off" }),
+                Callback::MasterVolume => format!("Volume: {:.0}%", c.master_volume * 100.),
+                Callback::MusicVolume => format!("Music: {:.0}%", c.music_volume * 100.),
+                Callback::SfxVolume => format!("Sound effects: {:.0}%", c.sfx_volume * 100.),
+                Callback::Advanced => "Advanced".to_owned(),
+                Callback::Back => "Back".to_owned(),
+                Callback::Bind(action) => {
+                    let name = BINDINGS.iter().find(|&&(a, _)| a == action).map_or("", |&(_, name)| name);
+                    if self.rebinding == Some(action) {
+                        format!("{}: press a key or button", name)
+                    } else {
+                        let inputs: Vec<_> = s.input.binds().into_iter()
+                            .filter(|&(_, a)| a == action)
+                            .map(|(input, _)| input.to_string())
+                            .collect();
+                        format!("{}: {}", name, inputs.join(", "))
+                    }
+                }
+            };
+            button.set_text(&label);
+        }
+    }
+    fn click(&mut self, s: &mut State, ctx: &mut Context, callback: Callback) {
+        let c = &mut s.config;
+        match callback {
+            Callback::Resolution => {
+                let i = RESOLUTIONS.iter().position(|&(w, h)| w == c.width && h == c.height);
+                let (w, h) = RESOLUTIONS[i.map_or(0, |i| (i + 1) % RESOLUTIONS.len())];
+                c.width = w;
+                c.height = h;
+            }
+            Callback::Fullscreen => c.fullscreen = !c.fullscreen,
+            Callback::MasterVolume => c.master_volume = next_volume(c.master_volume),
+            Callback::MusicVolume => c.music_volume = next_volume(c.music_volume),
+            Callback::SfxVolume => c.sfx_volume = next_volume(c.sfx_volume),
+            Callback::Bind(action) => {
+                self.rebinding = Some(action);
+                self.update_labels(s);
+                return
+            }
+            Callback::Advanced | Callback::Back => {
+                if let Err(e) = s.config.save(ctx, s.saves.profile()) {
+                    error!("Couldn't save the config: {}", e);
+                }
+                s.switch(if let Callback::Advanced = callback { StateSwitch::Advanced } else { StateSwitch::Menu });
+                return
+            }
+        }
+        match callback {
+            Callback::Resolution | Callback::Fullscreen => {
+                if let Err(e) = s.config.apply(ctx) {
+                    error!("Couldn't change the window: {}", e);
+                }
+                s.width = s.config.width;
+                s.height = s.config.height;
+                // Lay the buttons out again for the new size
+                s.switch(StateSwitch::Options);
+            }
+            _ => s.mplayer.set_volumes(&s.config),
+        }
+        self.update_labels(s);
+    }
+    /// Binds the action being rebound to `input` instead of what it was bound to before
+    fn rebind(&mut self, s: &mut State, ctx: &mut Context, input: Event) {
+        if let Some(action) = self.rebinding.take() {
+            s.input.unbind_action(action);
+            s.input.bind(input, action);
+            if let Err(e) = s.save_config(ctx) {
+                error!("Couldn't save the bindings: {}", e);
+            }
+            self.update_labels(s);
+        }
+    }
+}
+
+impl GameState for Options {
+    fn name(&self) -> &'static str {
+        "options"
+    }
+    fn logic(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        let dt = frame_time(ctx);
+        for button in &mut self.buttons {
+            button.update(dt, s.mouse);
+        }
+        Ok(())
+    }
+    fn draw_hud(&mut self, _s: &State, ctx: &mut Context) -> GameResult<()> {
+        self.title_txt.draw_center(ctx)?;
+        for button in &self.buttons {
+            button.draw(ctx)?;
+        }
+        Ok(())
+    }
+    fn event_up(&mut self, s: &mut State, ctx: &mut Context, event: Event) {
+        if self.rebinding.is_some() {
+            // Escape stops rebinding, leaving the binding as it was
+            if let Key(KeyCode::Escape) = event {
+                self.rebinding = None;
+                self.update_labels(s);
+            } else {
+                self.rebind(s, ctx, event);
+            }
+            return
+        }
+        match event {
+            Mouse(MouseButton::Left) => {
+                let clicked = self.buttons.iter().find(|b| b.in_bounds(s.mouse)).map(|b| b.callback);
+                if let Some(callback) = clicked {
+                    self.click(s, ctx, callback);
+                }
+            }
+            Key(KeyCode::Escape) => self.click(s, ctx, Callback::Back),
+            _ => (),
+        }
+    }
+}
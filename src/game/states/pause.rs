@@ -0,0 +1,62 @@
+use crate::{
+    util::Point2,
+    io::btn::Button,
+};
+use ggez::{
+    Context, GameResult,
+    graphics::{self, DrawMode, DrawParam, Rect, Mesh, Color},
+};
+
+use crate::game::{State, StateSwitch};
+
+const DIM_COLOUR: Color = Color{r: 0., g: 0., b: 0., a: 0.6};
+
+pub enum Callback {
+    Resume,
+    Restart,
+    Quit,
+}
+
+/// The menu shown over Play while it's paused
+pub struct Pause {
+    dim: Mesh,
+    buttons: Vec<Button<Callback>>,
+}
+
+impl Pause {
+    pub fn new(ctx: &mut Context, s: &State) -> GameResult<Self> {
+        let w = s.width;
+        let button_rect = |i: f32| Rect{x: 3. * w / 7., y: s.height / 2. - 100. + i * 68., w: w / 7., h: 64.};
+
+        Ok(Pause {
+            dim: Mesh::new_rectangle(ctx, DrawMode::fill(), Rect::new(0., 0., s.width, s.height), DIM_COLOUR)?,
+            buttons: vec![
+                Button::new(ctx, &s.assets, button_rect(0.), "Resume", Callback::Resume)?,
+                Button::new(ctx, &s.assets, button_rect(1.), "Restart Level", Callback::Restart)?,
+                Button::new(ctx, &s.assets, button_rect(2.), "Quit to Menu", Callback::Quit)?,
+            ],
+        })
+    }
+    pub fn update(&mut self, dt: f32, mouse: Point2) {
+        for button in &mut self.buttons {
+            button.update(dt, mouse);
+        }
+    }
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        graphics::draw(ctx, &self.dim, DrawParam::new())?;
+        for button in &self.buttons {
+            button.draw(ctx)?;
+        }
+        Ok(())
+    }
+    /// Handles a click at the mouse position, returning whether the game should resume
+    pub fn click(&self, s: &mut State) -> bool {
+        match self.buttons.iter().find(|b| b.in_bounds(s.mouse)).map(|b| &b.callback) {
+            Some(Callback::Resume) => return true,
+            Some(Callback::Restart) => s.switch(StateSwitch::Play),
+            Some(Callback::Quit) => s.switch(StateSwitch::Menu),
+            None => (),
+        }
+        false
+    }
+}
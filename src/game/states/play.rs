@@ -1,80 +1,757 @@
+use std::mem;
+
 use crate::{
     util::{
         BLUE, GREEN, RED,
-        angle_from_vec,
-        ver, hor,
+        angle_from_vec, rect_contains,
+        frame_time,
         Vector2, Point2
     },
-    io::tex::PosText,
+    io::{tex::PosText, input::{Action, TickInput}, wheel::Wheel, snd::{Playlist, PlaylistMode, MUSIC_FADE}},
+    ext::{Tween, Timer, ease},
     game::{
-        State, GameState, world::{World, Grid},
-        event::Event
+        State, GameState, StateSwitch, Args, ArgType, CommandError, CommandInfo, Completion, world::{World, WorldEvent, Sign, Enemy, Bullet, Hit, Companion, CompanionKind, Order, ORDERS, Emote, EMOTES, Emoting, Material, Visibility, PLAYER_RADIUS, EXIT_COLOUR, level_path},
+        cvars::CvarValue,
+        weapons::Weapon,
+        particles::{Particles, MUZZLE_FLASH, BLOOD, DEBRIS},
+        campaign::next_level,
+        replay::Replay,
+        stats::{self, Stats, format_time},
+        states::{pause::Pause, jukebox, inspector::Inspector, results::Outcome},
+        event::{Event, MouseButton}
     },
 };
 use ggez::{
     Context, GameResult,
     graphics::{
-        self, Drawable, DrawMode, Rect,
+        self, Drawable, DrawMode, DrawParam, Rect, Color, Image, FilterMode,
         MeshBuilder, Mesh,
         spritebatch::SpriteBatch,
     },
     input::mouse,
+    filesystem,
 };
 
+/// Loads the level being played, the campaign's current one if there is a campaign
+pub fn load_world(ctx: &mut Context, s: &mut State) -> GameResult<World> {
+    let mut world = match s.campaign {
+        Some(ref campaign) => World::load(ctx, &s.enemies, level_path(campaign.level()))?,
+        None => World::load_default(ctx, &s.enemies)?,
+    };
+    world.companion = Companion::load(&mut s.saves, world.spawn);
+    Ok(world)
+}
+
+/// Hides what the player hasn't seen in a world about to be played, if the `fog_of_war` cvar is on
+fn enable_fog(s: &State, world: &mut World) {
+    world.visibility = if s.cvars.bool("fog_of_war") { Some(Visibility::new()) } else { None };
+}
+
+/// Track played in levels without music of their own, outside campaigns that have some
+const DEFAULT_MUSIC: &str = "music";
+
+/// The music and ambience played in a level, falling back to the campaign's and then the default music
+pub fn level_music(s: &State, world: &World) -> (Playlist, Option<String>) {
+    let mut music = world.music.clone();
+    if music.tracks.is_empty() {
+        music.tracks = match s.campaign {
+            Some(ref campaign) if !campaign.music.is_empty() => campaign.music.clone(),
+            _ => vec![DEFAULT_MUSIC.to_owned()],
+        };
+    }
+    let ambience = world.ambience.clone().or_else(|| s.campaign.as_ref().and_then(|c| c.ambience.clone()));
+    (music, ambience)
+}
+
+/// The music tracks named from argument `first` on, as long as they all exist
+fn music_args(s: &State, ctx: &mut Context, args: &Args, first: usize) -> Result<Vec<String>, CommandError> {
+    let tracks: Vec<String> = (first..=args.len()).filter_map(|i| args.get(i)).map(str::to_owned).collect();
+    let manifest = s.mplayer.manifest();
+    let exists = |t: &String| manifest.get(t).is_some() || filesystem::exists(ctx, format!("/sounds/{}.ogg", t));
+    if !tracks.iter().all(exists) {
+        return Err(CommandError::NoSuchFile)
+    }
+    Ok(tracks)
+}
+
 /// The state of the game
 pub struct Play {
     top_text: PosText,
     status_text: PosText,
+    timer_text: PosText,
     hud: Hud,
     world: World,
     holes: SpriteBatch,
+    particles: Particles,
     cur_pickup: Option<usize>,
+    /// World time when the level was won, 0 if it hasn't been
     victory_time: f32,
-    time: usize,
+    /// Whether the level can be won by killing all the enemies, for levels without an exit
+    has_enemies: bool,
+    /// Arrow pointing right used for off-screen indicators
+    arrow: Mesh,
+    /// Whether the fire button is held down
+    firing: bool,
+    /// Pixels walked since the last footstep
+    stride: f32,
+    /// Counts down from the player dying to leaving the level
+    game_over: Option<Timer>,
+    /// The pause menu while the game is paused
+    pause: Option<Pause>,
+    /// The command wheel for giving the companion orders
+    orders: Wheel<Order>,
+    emotes: Wheel<Emote>,
+    /// Actions pressed and released since the last tick, done at the start of the next one
+    pending: TickInput,
+    /// The name of the replay being recorded and the replay
+    recording: Option<(String, Replay)>,
+    /// Whether this doesn't count towards any progress, like a replay being watched or a level tested in the editor
+    practice: bool,
+    /// Picks things in the world to inspect while the `dev_inspector` cvar is on
+    inspector: Inspector,
+    /// Name of the level being played, which its stats are kept under
+    level: String,
+    /// What the player did in the level, its time counting until it's won or lost
+    stats: Stats,
 }
 
 impl Play {
     #[allow(clippy::new_ret_no_self)]
     pub fn new(ctx: &mut Context, s: &mut State) -> GameResult<Box<dyn GameState>> {
-        mouse::set_cursor_hidden(ctx, true);
+        let world = load_world(ctx, s)?;
+        Ok(Box::new(Play::with_world(ctx, s, world, false)?))
+    }
+    /// Starts playing `world` from the start, as practice when watching a replay of it or testing it
+    pub fn with_world(ctx: &mut Context, s: &mut State, mut world: World, practice: bool) -> GameResult<Self> {
+        // Every attempt at a level gets the same randomness
+        s.rng.restart();
+        enable_fog(s, &mut world);
 
-        Ok(Box::new(
+        Ok(
             Play {
                 top_text: s.assets.text(Point2::new(4., 4.)).and_text("100"),
                 status_text: s.assets.text(Point2::new(s.width as f32 / 2., s.height as f32 / 2. + 32.)).and_text(""),
+                timer_text: s.assets.text(Point2::new(s.width as f32 / 2., 16.)).and_text(format_time(0.)),
                 hud: Hud::new(ctx)?,
-                time: 0,
                 victory_time: 0.,
+                has_enemies: !world.enemies.is_empty(),
                 cur_pickup: None,
-                world: World { grid: Grid::new(16, 16) },
+                world,
                 holes: SpriteBatch::new(s.assets.get_img(ctx, "common/hole").clone()),
+                particles: Particles::new(ctx, &s.assets),
+                arrow: Mesh::new_polygon(ctx, DrawMode::fill(), &[Point2::new(10., 0.), Point2::new(-6., -7.), Point2::new(-6., 7.)], graphics::WHITE)?,
+                firing: false,
+                stride: 0.,
+                game_over: None,
+                pause: None,
+                orders: Wheel::new(ORDERS.iter().map(|&o| (o.name().to_owned(), o)).collect()),
+                emotes: Wheel::new(EMOTES.iter().map(|&e| (e.name().to_owned(), e)).collect()),
+                pending: TickInput::default(),
+                recording: None,
+                practice,
+                inspector: Inspector::new(),
+                level: s.campaign.as_ref().map_or("default", |c| c.level()).to_owned(),
+                stats: Stats::default(),
             }
-        ))
+        )
     }
 }
 
-impl GameState for Play {
+/// Seconds "Game over" is shown before leaving the level
+const GAME_OVER_TIME: f32 = 3.;
+/// Seconds of game time between winning a level and going to the next one
+const VICTORY_TIME: f32 = 4.;
+/// Seconds the level's music takes to fade over to the victory music
+const VICTORY_FADE: f32 = 0.5;
+/// Pixels walked between footsteps
+const STRIDE: f32 = 40.;
+/// How hard the screen shakes when the player loses all their health at once
+const SHAKE_PER_HP: f32 = 2.;
+
+/// Distance from the edges of the screen to off-screen indicators
+const INDICATOR_MARGIN: f32 = 24.;
+const ENEMY_INDICATOR_COLOUR: Color = Color{r: 1., g: 0.5, b: 0., a: 1.};
+
+/// Draws an arrow at the edge of the screen pointing towards `target` if it is off-screen
+///
+/// Returns where the arrow was drawn
+fn draw_edge_arrow(ctx: &mut Context, arrow: &Mesh, s: &State, target: Point2, color: Color) -> GameResult<Option<Point2>> {
+    if rect_contains(Rect::new(0., 0., s.width, s.height), target) {
+        return Ok(None)
+    }
+    let center = Point2::new(s.width / 2., s.height / 2.);
+    let dir = target - center;
+    // Scale the direction so it reaches the closest edge
+    let t = ((center.x - INDICATOR_MARGIN) / dir.x.abs()).min((center.y - INDICATOR_MARGIN) / dir.y.abs());
+    let pos = center + dir * t;
+
+    arrow.draw(ctx, DrawParam::new().dest(pos).rotation(angle_from_vec(dir)).color(color))?;
+    Ok(Some(pos))
+}
+
+impl Play {
+    /// Things in the world that should be pointed out when they're off-screen
+    ///
+    /// Enemies are only pointed out if they can be seen and are closer than `enemy_range` pixels to the player.
+    fn indicator_targets(&self, enemy_range: f32) -> Vec<(Point2, Color)> {
+        let companion = self.world.companion.as_ref()
+            .filter(|c| c.recruited)
+            .map(|c| (c.obj.pos, if c.is_downed() { RED } else { GREEN }));
+        let exit = self.world.exit.map(|exit| (exit, EXIT_COLOUR));
+        let player = self.world.player.obj.pos;
+        let enemies = self.world.enemies.iter()
+            .map(|e| e.obj.pos)
+            .filter(|&pos| (pos - player).norm() < enemy_range && self.world.can_see(pos))
+            .map(|pos| (pos, ENEMY_INDICATOR_COLOUR));
+        self.world.markers.iter().map(|m| (m.pos, m.colour())).chain(companion).chain(exit).chain(enemies).collect()
+    }
+    /// Whether the level has been won, by reaching its exit if it has one
+    /// and otherwise by killing all its enemies
+    fn is_won(&self) -> bool {
+        if self.world.exit.is_some() {
+            self.world.at_exit()
+        } else {
+            self.has_enemies && self.world.enemies.is_empty()
+        }
+    }
+    /// Leaves the level once it's been won or lost, adding it to the stats and showing how it went
+    fn finish(&mut self, s: &mut State, won: bool) {
+        if self.practice {
+            s.switch(StateSwitch::Menu);
+            return
+        }
+        let best = stats::record(&mut s.saves, &self.level, &self.stats, won);
+        if s.kiosk {
+            // Unattended games don't wait for a click, going on to the next level or back to playing themselves
+            if !(won && next_level(s).is_some()) {
+                s.switch(StateSwitch::Attract);
+            }
+        } else {
+            s.switch(StateSwitch::Results(Outcome { won, stats: self.stats, best }));
+        }
+    }
+    /// Gives the companion an order picked on the command wheel
+    fn give_order(&mut self, order: Order, aim: Point2) {
+        let fetch_from = self.world.markers.last().map_or(aim, |m| m.pos);
+        if let Some(ref mut companion) = self.world.companion {
+            let spot = match order {
+                Order::Stay => companion.obj.pos,
+                Order::Fetch => fetch_from,
+                Order::Follow | Order::Attack => self.world.player.obj.pos,
+            };
+            companion.command(order, spot);
+        }
+    }
+    /// Spawns a stray companion at the crosshair for the player to recruit
+    fn companion_command(&mut self, s: &State, args: Args) -> Result<(), CommandError> {
+        let kind = CompanionKind::from_name(args.string(1)?)
+            .ok_or(CommandError::InvalidArg{index: 1, expected: ArgType::Str})?;
+        self.world.companion = Some(Companion::new(kind, s.screen_to_world(s.aim)));
+        Ok(())
+    }
+    /// Draws arrows with distances at the edge of the screen for each off-screen target
+    fn draw_indicators(&self, s: &State, ctx: &mut Context) -> GameResult<()> {
+        let view_center = s.screen_to_world(Point2::new(s.width / 2., s.height / 2.));
+        // The range is in tiles
+        let enemy_range = s.cvars.float("hud_enemy_range") * 32.;
+        for (pos, color) in self.indicator_targets(enemy_range) {
+            if let Some(arrow_pos) = draw_edge_arrow(ctx, &self.arrow, s, s.world_to_screen(pos), color)? {
+                // Distance in tiles
+                let dist = (pos - view_center).norm() / 32.;
+                let text = s.assets.raw_text_with(&format!("{:.0}", dist), 14.);
+                let (w, h) = text.dimensions(ctx);
+                // Put the text on the inner side of the arrow
+                let towards_center = (Point2::new(s.width / 2., s.height / 2.) - arrow_pos).normalize() * 20.;
+                let dest = arrow_pos + towards_center - Vector2::new(w as f32 / 2., h as f32 / 2.);
+                graphics::draw(ctx, &text, DrawParam::new().dest(dest).color(color))?;
+            }
+        }
+        Ok(())
+    }
+    fn load_command(&mut self, ctx: &mut Context, s: &mut State, args: Args) -> Result<(), CommandError> {
+        let name = args.string(1)?;
+        match World::load(ctx, &s.enemies, level_path(name)) {
+            Ok(mut world) => {
+                self.has_enemies = !world.enemies.is_empty();
+                self.victory_time = 0.;
+                self.level = name.to_owned();
+                self.stats = Stats::default();
+                s.camera.snap_to(world.player.obj.pos);
+                enable_fog(s, &mut world);
+                // The companion comes along, strays stay behind
+                world.companion = self.world.companion.take().filter(|c| c.recruited);
+                if let Some(ref mut companion) = world.companion {
+                    companion.place(world.spawn);
+                }
+                self.world = world;
+                self.particles.clear();
+                s.mplayer.set_combat(self.world.in_combat());
+                if let Err(e) = self.play_level_music(s, ctx, MUSIC_FADE) {
+                    error!("Couldn't play the level's music: {}", e);
+                }
+            }
+            Err(e) => {
+                error!("Couldn't load level {}: {}", name, e);
+                return Err(CommandError::NoSuchLevel)
+            }
+        }
+        Ok(())
+    }
+    fn music_command(&mut self, s: &mut State, ctx: &mut Context, args: Args) -> Result<(), CommandError> {
+        let shuffle = args.get(1) == Some("shuffle");
+        let first = if shuffle { 2 } else { 1 };
+        let tracks = music_args(s, ctx, &args, first)?;
+        self.world.music = Playlist {
+            tracks,
+            mode: if shuffle { PlaylistMode::Shuffle } else { PlaylistMode::Sequence },
+            looping: true,
+            combat: self.world.music.combat.clone(),
+        };
+        if let Err(e) = self.play_level_music(s, ctx, MUSIC_FADE) {
+            error!("Couldn't play the level's music: {}", e);
+        }
+        Ok(())
+    }
+    fn ambience_command(&mut self, s: &mut State, ctx: &mut Context, args: Args) -> Result<(), CommandError> {
+        self.world.ambience = music_args(s, ctx, &args, 1)?.into_iter().next();
+        if let Err(e) = self.play_level_music(s, ctx, MUSIC_FADE) {
+            error!("Couldn't play the level's ambience: {}", e);
+        }
+        Ok(())
+    }
+    fn combat_music_command(&mut self, s: &mut State, ctx: &mut Context, args: Args) -> Result<(), CommandError> {
+        self.world.music.combat = music_args(s, ctx, &args, 1)?;
+        // Played over from the start so the new stems are in sync
+        let restarted = s.mplayer.stop_music(ctx, 0.);
+        if let Err(e) = restarted.and_then(|()| self.play_level_music(s, ctx, 0.)) {
+            error!("Couldn't play the level's music: {}", e);
+        }
+        Ok(())
+    }
+    /// Fades over to the music played in the level, unlocking its tracks in the jukebox
+    fn play_level_music(&self, s: &mut State, ctx: &mut Context, fade: f32) -> GameResult<()> {
+        let (music, ambience) = level_music(s, &self.world);
+        jukebox::unlock(&mut s.saves, music.tracks.iter().map(String::as_str));
+        let playlist = jukebox::ingame_playlist(s, &music);
+        s.mplayer.play_music(ctx, playlist, fade)?;
+        s.mplayer.play_ambience(ctx, ambience.as_deref(), fade)
+    }
+    /// Fires the player's weapon, or a plain bullet if they have none
+    fn fire(&mut self, s: &mut State, ctx: &mut Context) {
+        let player = &mut self.world.player;
+        if !player.can_shoot() {
+            return
+        }
+        let muzzle = player.obj.pos + player.obj.dir() * PLAYER_RADIUS;
+        match player.weapon {
+            Some(ref mut weapon) => if let Some(stray) = weapon.fire(&mut s.rng) {
+                let mut bullet = Bullet::with_trajectory(muzzle, player.obj.rot + stray, weapon.def.trajectory);
+                bullet.damage = weapon.def.damage;
+                self.world.bullets.push(bullet);
+                self.stats.shots += 1;
+                self.particles.burst(&MUZZLE_FLASH, muzzle, player.obj.dir());
+                if let Some(ref sound) = weapon.def.fire_sound {
+                    if let Err(e) = s.mplayer.play(ctx, sound) {
+                        warn!("Couldn't play {}: {}", sound, e);
+                    }
+                }
+            }
+            None => {
+                self.world.bullets.push(Bullet::new(muzzle, player.obj.rot));
+                self.stats.shots += 1;
+                self.particles.burst(&MUZZLE_FLASH, muzzle, player.obj.dir());
+            }
+        }
+    }
+    fn give_command(&mut self, s: &State, args: Args) -> Result<(), CommandError> {
+        let def = s.weapons.get(args.string(1)?).ok_or(CommandError::NoSuchWeapon)?;
+        info!("Got a {}", def.name);
+        self.world.player.weapon = Some(Weapon::new(def.clone()));
+        Ok(())
+    }
+    /// Opens or closes the pause menu
+    fn set_paused(&mut self, s: &State, ctx: &mut Context, paused: bool) -> GameResult<()> {
+        if paused {
+            self.pause = Some(Pause::new(ctx, s)?);
+            self.firing = false;
+        } else {
+            self.pause = None;
+        }
+        // The pause menu is clicked with the cursor instead of aimed at with the crosshair
+        mouse::set_cursor_hidden(ctx, !paused);
+        Ok(())
+    }
+    /// Spawns an enemy of the given kind at the crosshair, the built-in one if no kind is given
+    fn enemy_command(&mut self, s: &State, args: Args) -> Result<(), CommandError> {
+        let pos = s.screen_to_world(s.aim);
+        let enemy = match args.get(1) {
+            Some(kind) => {
+                let def = s.enemies.get(kind).ok_or(CommandError::NoSuchEnemy)?;
+                Enemy::with_def(pos, kind.to_owned(), def.clone())
+            }
+            None => Enemy::new(pos),
+        };
+        self.world.enemies.push(enemy);
+        Ok(())
+    }
+    /// Starts recording the level over from the start, or stops recording and saves the replay
+    fn record_command(&mut self, s: &mut State, ctx: &mut Context, args: Args) -> Result<(), CommandError> {
+        if args.is_empty() {
+            if self.recording.is_none() {
+                return Err(CommandError::NotRecording)
+            }
+            self.save_recording(ctx);
+            return Ok(())
+        }
+        let name = args.name(1)?.to_owned();
+        self.save_recording(ctx);
+        let restarted = load_world(ctx, s).and_then(|world| {
+            let replay = Replay::new(s.rng.seed(), &world);
+            Ok((Play::with_world(ctx, s, world, false)?, replay))
+        });
+        match restarted {
+            Ok((play, replay)) => {
+                *self = play;
+                s.camera.snap_to(self.world.player.obj.pos);
+                info!("Recording {}", name);
+                self.recording = Some((name, replay));
+                Ok(())
+            }
+            Err(e) => {
+                error!("Couldn't restart the level: {}", e);
+                Err(CommandError::NoSuchLevel)
+            }
+        }
+    }
+    /// Plays a level over from the start, loading it again so changes made to it are picked up
+    ///
+    /// The player keeps their weapon, freshly loaded
+    fn restart(&mut self, s: &mut State, ctx: &mut Context, level: &str) -> GameResult<()> {
+        let mut world = if level == "default" {
+            World::load_default(ctx, &s.enemies)?
+        } else {
+            World::load(ctx, &s.enemies, level_path(level))?
+        };
+        world.companion = Companion::load(&mut s.saves, world.spawn);
+        world.player.weapon = self.world.player.weapon.as_ref().map(|w| Weapon::new(w.def.clone()));
+        if let Some(ref mut campaign) = s.campaign {
+            campaign.select(level);
+        }
+
+        self.save_recording(ctx);
+        let mut play = Play::with_world(ctx, s, world, false)?;
+        play.level = level.to_owned();
+        *self = play;
+        self.on_enter(s, ctx)
+    }
+    fn restart_command(&mut self, s: &mut State, ctx: &mut Context, level: &str) -> Result<(), CommandError> {
+        self.restart(s, ctx, level).map_err(|e| {
+            error!("Couldn't load level {}: {}", level, e);
+            CommandError::NoSuchLevel
+        })
+    }
+    /// Stops recording, saving the replay
+    fn save_recording(&mut self, ctx: &mut Context) {
+        if let Some((name, replay)) = self.recording.take() {
+            match replay.save(ctx, &name) {
+                Ok(()) => info!("Saved replay {}", name),
+                Err(e) => error!("Couldn't save replay {}: {}", name, e),
+            }
+        }
+    }
+    /// Does what an action does when it's pressed
+    fn press(&mut self, s: &mut State, ctx: &mut Context, action: Action, aim: Point2) {
+        match action {
+            // The crosshair picks from a wheel while one is open, so nothing else is done
+            _ if self.orders.is_open() || self.emotes.is_open() => (),
+            Action::Command if self.world.companion.as_ref().map_or(false, |c| c.recruited) => {
+                self.orders.open(s.world_to_screen(self.world.player.obj.pos));
+                self.firing = false;
+            }
+            Action::Emote if self.game_over.is_none() => {
+                self.emotes.open(s.world_to_screen(self.world.player.obj.pos));
+                self.firing = false;
+            }
+            Action::Shoot if self.game_over.is_none() => {
+                self.firing = true;
+                if self.world.player.weapon.is_none() {
+                    self.fire(s, ctx);
+                }
+            }
+            Action::Reload => if let Some(ref mut weapon) = self.world.player.weapon {
+                weapon.reload();
+            }
+            Action::Mark => self.world.place_marker(aim),
+            _ => (),
+        }
+    }
+    /// Does what an action does when it's let go of
+    fn release(&mut self, s: &State, action: Action, aim: Point2) {
+        // Wheels are pointed at with where the crosshair is in this tick, so replays pick the same
+        let dir = s.world_to_screen(aim) - s.world_to_screen(self.world.player.obj.pos);
+        match action {
+            Action::Shoot => self.firing = false,
+            Action::Command => {
+                self.orders.point(dir);
+                if let Some(order) = self.orders.close() {
+                    self.give_order(order, aim);
+                }
+            }
+            Action::Emote => {
+                self.emotes.point(dir);
+                if let Some(emote) = self.emotes.close() {
+                    self.world.player.emote = Some(Emoting::new(emote));
+                }
+            }
+            _ => (),
+        }
+    }
+    /// Advances the game by one tick of `dt` seconds with what the player did during it
     #[allow(clippy::cognitive_complexity)]
-    fn update(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
-        let mouse_pos = s.mouse - s.offset;
-        // Define player velocity here already because enemies need it
-        let camera_vel = Vector2::new(hor(&ctx), ver(&ctx));
+    pub fn tick(&mut self, s: &mut State, ctx: &mut Context, dt: f32, input: TickInput) -> GameResult<()> {
+        if let Some(ref mut timer) = self.game_over {
+            if timer.update(dt) {
+                self.finish(s, false);
+            }
+        } else {
+            self.world.player.obj.look_at(input.aim);
+            for action in input.pressed.iter() {
+                self.press(s, ctx, action, input.aim);
+            }
+            for action in input.released.iter() {
+                self.release(s, action, input.aim);
+            }
+            let movement = Vector2::new(
+                input.axis(Action::MoveLeft, Action::MoveRight),
+                input.axis(Action::MoveUp, Action::MoveDown),
+            );
+            self.world.player.shield.raise(input.held.contains(Action::Block));
+            let before = self.world.player.obj.pos;
+            self.world.player.update(&self.world.grid, movement, dt);
+            let pos = self.world.player.obj.pos;
+            self.stride += (pos - before).norm();
+            if self.stride >= STRIDE {
+                self.stride = 0.;
+                if let Some(sound) = self.world.grid.footstep_at(pos) {
+                    if let Err(e) = s.mplayer.play(ctx, &sound) {
+                        warn!("Couldn't play {}: {}", sound, e);
+                    }
+                }
+            }
+            // Without a weapon only one bullet comes out per click
+            if self.firing && self.world.player.weapon.is_some() {
+                self.fire(s, ctx);
+            }
+        }
+
+        self.particles.update(dt);
+        if self.game_over.is_none() && self.victory_time <= 0. {
+            self.stats.time += dt;
+        }
+        let hp = self.world.player.health.hp;
+        let enemies = self.world.enemies.len();
+        for event in self.world.update(dt, &mut s.rng) {
+            match event {
+                WorldEvent::Sound(sound, pos) => {
+                    let occluded = !self.world.grid.is_clear(pos, s.mplayer.listener());
+                    if let Err(e) = s.mplayer.play_at(ctx, &sound, pos, occluded) {
+                        warn!("Couldn't play {}: {}", sound, e);
+                    }
+                }
+                // Only what can be heard gets a subtitle
+                WorldEvent::Line { speaker, text, pos } => if s.mplayer.can_hear(pos) {
+                    s.subtitle(&speaker, &text);
+                }
+                WorldEvent::Combat(fighting) => s.mplayer.set_combat(fighting),
+                WorldEvent::Hit(hit, bullet) => match hit {
+                    Hit::Wall => {
+                        self.holes.add(bullet.drawparams());
+                        // Chips fly back the way the bullet came
+                        self.particles.burst(&DEBRIS, bullet.pos, -bullet.vel);
+                    }
+                    Hit::Enemy(_) | Hit::Pierced(_) => {
+                        self.stats.hits += 1;
+                        self.particles.burst(&BLOOD, bullet.pos, bullet.vel);
+                    }
+                    Hit::Player => self.particles.burst(&BLOOD, bullet.pos, bullet.vel),
+                    Hit::None | Hit::Expired => (),
+                }
+                WorldEvent::Recruited => if let Some(ref companion) = self.world.companion {
+                    s.notify(&format!("A {} joined you", companion.kind.name()));
+                    if !self.practice {
+                        companion.save(&mut s.saves);
+                    }
+                }
+                WorldEvent::Fetched(resource) => if let Some(ref companion) = self.world.companion {
+                    s.notify(&format!("Your {} brought back {}", companion.kind.name(), resource));
+                }
+                WorldEvent::CompanionDowned => if let Some(ref companion) = self.world.companion {
+                    s.notify(&format!("Your {} is down, stand next to it to revive it", companion.kind.name()));
+                }
+            }
+        }
+        if let Some(companion) = self.world.companion.as_ref().filter(|c| c.is_downed()) {
+            self.particles.emit(&BLOOD, companion.obj.pos, Vector2::new(0., -1.), dt);
+        }
+        // Enemies are only ever removed from the world by dying
+        self.stats.kills += enemies.saturating_sub(self.world.enemies.len()) as u32;
+        let lost = hp - self.world.player.health.hp;
+        if lost > 0. {
+            self.stats.damage_taken += lost;
+            s.camera.add_shake(lost / self.world.player.health.max_hp * SHAKE_PER_HP);
+        }
 
+        // Once the level is won or lost, that's how it ends
+        let playing = self.game_over.is_none() && self.victory_time <= 0.;
+        if self.victory_time > 0. {
+            let left = VICTORY_TIME - (self.world.clock.time - self.victory_time);
+            if left <= 0. {
+                self.victory_time = 0.;
+                self.finish(s, true);
+            } else {
+                self.status_text.update(0, format!("Level complete\n{:.0}", left.ceil()))?;
+            }
+        } else if playing && self.world.player.health.is_dead() {
+            self.game_over = Some(Timer::started(GAME_OVER_TIME));
+            self.firing = false;
+            self.status_text.update(0, "Game over")?;
+        } else if playing && self.is_won() {
+            self.victory_time = self.world.clock.time;
+            self.firing = false;
+            self.status_text.update(0, "Level complete")?;
+            s.mplayer.play_music(ctx, Playlist::once("victory"), VICTORY_FADE)?;
+            if self.practice {
+                return Ok(())
+            }
+            jukebox::unlock(&mut s.saves, Some("victory"));
+            if let Some(companion) = self.world.companion.as_ref().filter(|c| c.recruited) {
+                companion.save(&mut s.saves);
+            }
+            if let Some(ref mut campaign) = s.campaign {
+                campaign.complete_level(&mut s.saves);
+            }
+        }
+        Ok(())
+    }
+    /// Damages the player, for testing
+    fn hurt_command(&mut self, args: Args) -> Result<(), CommandError> {
+        let dmg = Some(args.float(1)?).filter(|&dmg| dmg > 0.)
+            .ok_or(CommandError::InvalidArg{index: 1, expected: ArgType::Float})?;
+        self.world.player.health.damage(dmg);
         Ok(())
     }
+}
+
+const COMMANDS: &[CommandInfo] = &[
+    CommandInfo::new("savelevel", "<name>", "Saves the world as a level").completing(Completion::Levels),
+    CommandInfo::new("loadlevel", "<name>", "Replaces the world with a saved level").completing(Completion::Levels),
+    CommandInfo::new("restart", "", "Plays the level over from the start, loading it again"),
+    CommandInfo::new("map", "<name>", "Plays a level from the start, keeping the player's weapon").completing(Completion::Levels),
+    CommandInfo::new("spawn", "", "Moves the spawn point to the crosshair"),
+    CommandInfo::new("exit", "", "Moves the level's exit to the crosshair, so reaching it wins the level instead of killing every enemy"),
+    CommandInfo::new("clearexit", "", "Removes the level's exit"),
+    CommandInfo::new("hurt", "<damage>", "Damages the player by a positive amount"),
+    CommandInfo::new("sign", "<text>", "Puts a sign at the crosshair"),
+    CommandInfo::new("clearsigns", "", "Removes all signs"),
+    CommandInfo::new("enemy", "[kind]", "Spawns an enemy at the crosshair").completing(Completion::Enemies),
+    CommandInfo::new("clearenemies", "", "Removes all enemies"),
+    CommandInfo::new("companion", "<kind>", "Puts a stray companion at the crosshair").completing(Completion::Companions),
+    CommandInfo::new("give", "<weapon>", "Gives the player a weapon").completing(Completion::Weapons),
+    CommandInfo::new("music", "[shuffle] [track...]", "Sets the music the level plays, the campaign's or the default if no tracks are given"),
+    CommandInfo::new("ambience", "[loop]", "Sets the ambience looped under the level's music, the campaign's if none is given"),
+    CommandInfo::new("combatmusic", "[stem...]", "Sets the stems layered over the level's music during fights"),
+    CommandInfo::new("record", "[name]", "Records a replay of the level from the start, or stops recording and saves it. Commands aren't recorded").completing(Completion::Replays),
+];
+
+impl GameState for Play {
+    fn name(&self) -> &'static str {
+        "play"
+    }
+    fn on_enter(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        s.camera.snap_to(self.world.player.obj.pos);
+        mouse::set_cursor_hidden(ctx, true);
+        s.mplayer.set_combat(self.world.in_combat());
+        self.play_level_music(s, ctx, MUSIC_FADE)
+    }
+    fn on_exit(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        self.save_recording(ctx);
+        s.mplayer.set_combat(false);
+        if let Err(e) = s.mplayer.play_ambience(ctx, None, MUSIC_FADE) {
+            error!("Couldn't stop the level's ambience: {}", e);
+        }
+        mouse::set_cursor_hidden(ctx, false);
+        mouse::set_cursor_type(ctx, mouse::MouseCursor::Default);
+        Ok(())
+    }
+    fn on_pause(&mut self, _s: &mut State, _ctx: &mut Context) -> GameResult<()> {
+        // The button being let go of won't reach us while paused
+        self.firing = false;
+        Ok(())
+    }
+    fn update(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        if self.pause.is_some() {
+            return Ok(())
+        }
+        let input = TickInput {
+            held: s.input.held(ctx),
+            aim: s.screen_to_world(s.aim),
+            .. mem::replace(&mut self.pending, TickInput::default())
+        };
+        let dt = s.delta();
+        if let Some((_, ref mut replay)) = self.recording {
+            replay.record(dt, input);
+        }
+        self.tick(s, ctx, dt, input)
+    }
     fn logic(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
-        let dist = s.mouse - s.offset; // - self.world.player.obj.pos;
+        self.hud.update_bars(frame_time(ctx));
+        if let Some(ref mut pause) = self.pause {
+            pause.update(frame_time(ctx), s.mouse);
+            return Ok(())
+        }
+        if s.cvars.bool("dev_inspector") {
+            self.inspector.update(&mut self.world, s.screen_to_world(s.aim));
+        }
 
-        self.hud.update_bars(ctx)?;
+        s.camera.follow(self.world.player.obj.pos);
+        // Wheels stay around the player and are pointed at with the crosshair
+        let center = s.world_to_screen(self.world.player.obj.pos);
+        if self.orders.is_open() {
+            self.orders.set_center(center);
+            self.orders.point_at(s.aim);
+        }
+        if self.emotes.is_open() {
+            self.emotes.set_center(center);
+            self.emotes.point_at(s.aim);
+        }
 
-        // Center the camera on the player
-        // let p = self.world.player.obj.pos;
-        // s.focus_on(p);
+        let player = &self.world.player;
+        let loading = player.weapon.as_ref().map_or(1., |w| if w.is_reloading() {
+            w.reload_progress()
+        } else {
+            f32::from(w.loaded) / f32::from(w.def.clip_size)
+        });
+        self.hud.set_values(player.health.hp_fraction(), player.health.armour_fraction(), loading, player.shield.fraction());
+
+        if let Some(ref weapon) = player.weapon {
+            let ammo = if weapon.is_reloading() {
+                format!("{} reloading {:.0}%", weapon.def.name, weapon.reload_progress() * 100.)
+            } else {
+                format!("{} {}/{}", weapon.def.name, weapon.loaded, weapon.def.clip_size)
+            };
+            self.top_text.update(0, ammo)?;
+        }
+        self.timer_text.update(0, format_time(self.stats.time))?;
         Ok(())
     }
 
     fn draw(&mut self, s: &State, ctx: &mut Context) -> GameResult<()> {
-        self.world.grid.draw(ctx, &s.assets)?;
+        self.world.draw(ctx, &s.assets, s.view_rect())?;
+        self.holes.draw(ctx, DrawParam::new())?;
+        self.particles.draw(ctx)?;
+        if s.cvars.bool("dev_inspector") {
+            self.inspector.draw(ctx, &self.world)?;
+        }
 
         Ok(())
     }
@@ -83,9 +760,28 @@ impl GameState for Play {
 
         self.top_text.draw_text(ctx)?;
         self.status_text.draw_center(ctx)?;
+        if s.cvars.bool("hud_timer") {
+            self.timer_text.draw_center(ctx)?;
+        }
+
+        if s.cvars.bool("hud_indicators") {
+            self.draw_indicators(s, ctx)?;
+        }
+        if s.cvars.bool("hud_minimap") {
+            self.hud.draw_minimap(ctx, &self.world, s.width, s.height)?;
+        }
+        if s.cvars.bool("dev_inspector") {
+            self.inspector.draw_panel(ctx, &s.assets, &self.world, s.width)?;
+        }
+
+        if let Some(ref pause) = self.pause {
+            return pause.draw(ctx)
+        }
+        self.orders.draw(ctx, &s.assets)?;
+        self.emotes.draw(ctx, &s.assets)?;
 
         let drawparams = graphics::DrawParam {
-            dest: s.mouse.into(),
+            dest: s.aim.into(),
             offset: Point2::new(0.5, 0.5).into(),
             color: RED,
             .. Default::default()
@@ -93,13 +789,118 @@ impl GameState for Play {
         let img = s.assets.get_img(ctx, "common/crosshair");
         graphics::draw(ctx, &*img, drawparams)
     }
+    fn event_down(&mut self, s: &mut State, ctx: &mut Context, event: Event) {
+        let action = s.input.action(event);
+        if action == Some(Action::Pause) {
+            let paused = self.pause.is_none();
+            if let Err(e) = self.set_paused(s, ctx, paused) {
+                error!("Couldn't pause: {}", e);
+            }
+            return
+        }
+        if self.pause.is_some() {
+            return
+        }
+        if action == Some(Action::Minimap) {
+            let shown = s.cvars.bool("hud_minimap");
+            if let Err(e) = s.cvars.set("hud_minimap", CvarValue::Bool(!shown)) {
+                error!("Couldn't toggle the minimap: {}", e);
+            }
+            return
+        }
+        if action == Some(Action::Restart) && !self.practice {
+            let level = self.level.clone();
+            if let Err(e) = self.restart(s, ctx, &level) {
+                error!("Couldn't restart the level: {}", e);
+            }
+            return
+        }
+        // Clicking something to inspect it doesn't shoot
+        if event == Event::Mouse(MouseButton::Left) && s.cvars.bool("dev_inspector")
+            && self.inspector.grab(&self.world, s.screen_to_world(s.aim)) {
+            return
+        }
+        // Done in the next tick, so it happens at the same point when replayed
+        if let Some(action) = action {
+            self.pending.pressed.insert(action);
+        }
+    }
     fn event_up(&mut self, s: &mut State, ctx: &mut Context, event: Event) {
-        // use self::KeyCode::*;
-        match event {
-            _ => (),
+        if event == Event::Mouse(MouseButton::Left) {
+            self.inspector.release();
+        }
+        if let Some(action) = s.input.action(event) {
+            self.pending.released.insert(action);
+        }
+        // The pause menu is clicked like any other menu, whatever shooting is bound to
+        if let Event::Mouse(MouseButton::Left) = event {
+            let resume = self.pause.as_ref().map_or(false, |p| p.click(s));
+            if resume {
+                if let Err(e) = self.set_paused(s, ctx, false) {
+                    error!("Couldn't resume: {}", e);
+                }
+            }
+        }
+    }
+
+    fn commands(&self) -> &'static [CommandInfo] {
+        COMMANDS
+    }
+    fn command(&mut self, s: &mut State, ctx: &mut Context, args: Args) -> Option<Result<(), CommandError>> {
+        match args.cmd() {
+            "savelevel" => Some(args.string(1).map(|name| {
+                match self.world.save(ctx, level_path(name)) {
+                    Ok(()) => info!("Saved level {}", name),
+                    Err(e) => error!("Couldn't save level {}: {}", name, e),
+                }
+            })),
+            "loadlevel" => Some(self.load_command(ctx, s, args)),
+            "restart" => {
+                let level = self.level.clone();
+                Some(self.restart_command(s, ctx, &level))
+            }
+            "map" => Some(args.name(1).and_then(|name| self.restart_command(s, ctx, name))),
+            "spawn" => {
+                self.world.spawn = s.screen_to_world(s.aim);
+                Some(Ok(()))
+            }
+            "exit" => {
+                self.world.exit = Some(s.screen_to_world(s.aim));
+                Some(Ok(()))
+            }
+            "clearexit" => {
+                self.world.exit = None;
+                Some(Ok(()))
+            }
+            "hurt" => Some(self.hurt_command(args)),
+            "sign" => {
+                let pos = s.screen_to_world(s.aim);
+                self.world.signs.push(Sign::new(pos, &args.rest(1)));
+                Some(Ok(()))
+            }
+            "clearsigns" => {
+                self.world.signs.clear();
+                Some(Ok(()))
+            }
+            "enemy" => Some(self.enemy_command(s, args)),
+            "companion" => Some(self.companion_command(s, args)),
+            "clearenemies" => {
+                self.world.enemies.clear();
+                Some(Ok(()))
+            }
+            "give" => Some(self.give_command(s, args)),
+            "music" => Some(self.music_command(s, ctx, args)),
+            "combatmusic" => Some(self.combat_music_command(s, ctx, args)),
+            "ambience" => Some(self.ambience_command(s, ctx, args)),
+            "record" => Some(self.record_command(s, ctx, args)),
+            _ => None,
         }
     }
 
+    fn uses_aim(&self) -> bool {
+        self.pause.is_none()
+    }
+
     fn get_world(&self) -> Option<&World> {
         Some(&self.world)
     }
@@ -108,12 +909,141 @@ impl GameState for Play {
     }
 }
 
+/// Width of a full bar in the HUD
+const BAR_WIDTH: f32 = 100.;
+/// Seconds it takes a bar to slide to a new value
+const BAR_SLIDE_TIME: f32 = 0.3;
+/// Seconds the damage ghost lingers before catching up
+const GHOST_HOLD: f32 = 0.6;
+const SHIELD_COLOUR: Color = Color{r: 0.9, g: 0.75, b: 0.2, a: 1.};
+
+#[derive(Debug)]
+/// A bar in the HUD that slides towards its value
+///
+/// A ghosted bar leaves a white segment behind for a moment when it drops
+struct Bar {
+    y: f32,
+    color: Color,
+    value: Tween,
+    /// The ghost and how long it lingers
+    ghost: Option<(Tween, Timer)>,
+}
+
+impl Bar {
+    fn new(y: f32, color: Color, ghosted: bool) -> Self {
+        let ghost = if ghosted {
+            Some((Tween::new(1., BAR_SLIDE_TIME, ease::quad_in), Timer::new(GHOST_HOLD)))
+        } else {
+            None
+        };
+        Bar {
+            y,
+            color,
+            value: Tween::new(1., BAR_SLIDE_TIME, ease::quad_out),
+            ghost,
+        }
+    }
+    fn set(&mut self, v: f32) {
+        if let Some((ref mut ghost, ref mut hold)) = self.ghost {
+            if v < self.value.target() {
+                // Keep the ghost where the bar was before the hit
+                if ghost.value() < self.value.value() {
+                    ghost.snap(self.value.value());
+                }
+                hold.restart();
+            }
+        }
+        self.value.set_target(v);
+    }
+    fn update(&mut self, dt: f32) {
+        self.value.update(dt);
+
+        if let Some((ref mut ghost, ref mut hold)) = self.ghost {
+            hold.update(dt);
+            if hold.is_done() {
+                ghost.set_target(self.value.target());
+            }
+            ghost.update(dt);
+        }
+    }
+    /// Draws the bar by stretching the unit rectangle `unit`
+    fn draw(&self, ctx: &mut Context, unit: &Mesh) -> GameResult<()> {
+        let value = self.value.value();
+        let dest = Point2::new(2., self.y);
+
+        if let Some((ref ghost, _)) = self.ghost {
+            let ghost = ghost.value().max(value);
+            unit.draw(ctx, DrawParam::new().dest(dest).scale(Vector2::new(BAR_WIDTH * ghost, 24.)).color(graphics::WHITE))?;
+        }
+        unit.draw(ctx, DrawParam::new().dest(dest).scale(Vector2::new(BAR_WIDTH * value, 24.)).color(self.color))
+    }
+}
+
+/// Longest side of the minimap in pixels
+const MINIMAP_SIZE: f32 = 160.;
+/// Most pixels a tile takes up on the minimap, so small levels aren't blown up
+const MINIMAP_MAX_SCALE: f32 = 4.;
+/// Distance from the corner of the screen to the minimap
+const MINIMAP_MARGIN: f32 = 8.;
+const MINIMAP_BACKGROUND: Color = Color{r: 0., g: 0., b: 0., a: 0.6};
+
+#[derive(Debug, Default)]
+/// A small map of the whole level, coloured by material, with blips for the player and enemies
+struct Minimap {
+    /// A pixel for each tile, and the revision of the grid and how many tiles had been explored when it was made
+    tiles: Option<((u32, usize), Image)>,
+}
+
+impl Minimap {
+    /// Draws the minimap in the bottom right corner of a screen of the given size
+    fn draw(&mut self, ctx: &mut Context, world: &World, width: f32, height: f32) -> GameResult<()> {
+        let grid = &world.grid;
+        let made_from = (grid.revision(), world.visibility.as_ref().map_or(0, Visibility::explored));
+        if self.tiles.as_ref().map(|&(made_from, _)| made_from) != Some(made_from) {
+            let mut rgba = Vec::with_capacity(grid.width() as usize * grid.height() as usize * 4);
+            for y in 0..grid.height() {
+                for x in 0..grid.width() {
+                    let explored = world.visibility.as_ref().map_or(true, |v| v.is_explored(x, y));
+                    let (r, g, b, a) = grid.get(x, y).filter(|_| explored).map_or(graphics::BLACK, Material::colour).to_rgba();
+                    rgba.extend_from_slice(&[r, g, b, a]);
+                }
+            }
+            let mut img = Image::from_rgba8(ctx, grid.width(), grid.height(), &rgba)?;
+            img.set_filter(FilterMode::Nearest);
+            self.tiles = Some((made_from, img));
+        }
+
+        let (w, h) = (f32::from(grid.width()), f32::from(grid.height()));
+        let scale = (MINIMAP_SIZE / w.max(h)).min(MINIMAP_MAX_SCALE);
+        let corner = Point2::new(width - MINIMAP_MARGIN - w * scale, height - MINIMAP_MARGIN - h * scale);
+        // Tiles are 32 pixels wide in the world
+        let to_map = |p: Point2| corner + p.coords * (scale / 32.);
+
+        let background = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect::new(corner.x - 2., corner.y - 2., w * scale + 4., h * scale + 4.), MINIMAP_BACKGROUND)?;
+        graphics::draw(ctx, &background, DrawParam::new())?;
+        if let Some((_, ref img)) = self.tiles {
+            graphics::draw(ctx, img, DrawParam::new().dest(corner).scale(Vector2::new(scale, scale)))?;
+        }
+        let mut blips = MeshBuilder::new();
+        for enemy in world.enemies.iter().filter(|e| world.can_see(e.obj.pos)) {
+            blips.circle(DrawMode::fill(), to_map(enemy.obj.pos), 2., 0.5, RED);
+        }
+        blips.circle(DrawMode::fill(), to_map(world.player.obj.pos), 3., 0.5, graphics::WHITE);
+        let blips = blips.build(ctx)?;
+        graphics::draw(ctx, &blips, DrawParam::new())
+    }
+}
+
 #[derive(Debug)]
 pub struct Hud {
     hud_bar: Mesh,
-    hp_bar: Mesh,
-    armour_bar: Mesh,
-    loading_bar: Mesh,
+    /// A white 1×1 rectangle that is scaled and coloured to draw the bars
+    unit: Mesh,
+    hp_bar: Bar,
+    armour_bar: Bar,
+    loading_bar: Bar,
+    shield_bar: Bar,
+    minimap: Minimap,
 }
 
 impl Hud {
@@ -122,30 +1052,43 @@ impl Hud {
             .rectangle(DrawMode::fill(), Rect{x: 1., y: 1., w: 102., h: 26.}, graphics::BLACK)
             .rectangle(DrawMode::fill(), Rect{x: 1., y: 29., w: 102., h: 26.}, graphics::BLACK)
             .rectangle(DrawMode::fill(), Rect{x: 1., y: 57., w: 102., h: 26.}, graphics::BLACK)
+            .rectangle(DrawMode::fill(), Rect{x: 1., y: 85., w: 102., h: 26.}, graphics::BLACK)
             .build(ctx)?;
-
-        let hp_bar = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect{x: 2., y: 2., w: 0., h: 24.}, GREEN)?;
-        let armour_bar = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect{x: 2., y: 30., w: 0., h: 24.}, BLUE)?;
-        let loading_bar = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect{x: 2., y: 58., w: 0., h: 24.}, RED)?;
+        let unit = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect{x: 0., y: 0., w: 1., h: 1.}, graphics::WHITE)?;
 
         Ok(Hud{
             hud_bar,
-            hp_bar,
-            armour_bar,
-            loading_bar,
+            unit,
+            hp_bar: Bar::new(2., GREEN, true),
+            armour_bar: Bar::new(30., BLUE, true),
+            loading_bar: Bar::new(58., RED, false),
+            shield_bar: Bar::new(86., SHIELD_COLOUR, true),
+            minimap: Minimap::default(),
         })
     }
-    pub fn update_bars(&mut self, ctx: &mut Context) -> GameResult<()> {
-        self.hp_bar = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect{x: 2., y: 2., w: 100., h: 24.}, GREEN)?;
-        self.armour_bar = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect{x: 2., y: 30., w: 100., h: 24.}, BLUE)?;
-        self.loading_bar = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect{x: 2., y: 58., w: 100., h: 24.}, RED)?;
-
-        Ok(())
+    /// Sets the fractions (between 0 and 1) the bars should slide towards
+    pub fn set_values(&mut self, hp: f32, armour: f32, loading: f32, shield: f32) {
+        self.hp_bar.set(hp);
+        self.armour_bar.set(armour);
+        self.loading_bar.set(loading);
+        self.shield_bar.set(shield);
+    }
+    pub fn update_bars(&mut self, dt: f32) {
+        self.hp_bar.update(dt);
+        self.armour_bar.update(dt);
+        self.loading_bar.update(dt);
+        self.shield_bar.update(dt);
     }
     pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
         self.hud_bar.draw(ctx, Default::default())?;
-        self.hp_bar.draw(ctx, Default::default())?;
-        self.armour_bar.draw(ctx, Default::default())?;
-        self.loading_bar.draw(ctx, Default::default())
+        self.hp_bar.draw(ctx, &self.unit)?;
+        self.armour_bar.draw(ctx, &self.unit)?;
+        self.loading_bar.draw(ctx, &self.unit)?;
+        self.shield_bar.draw(ctx, &self.unit)
+    }
+    /// Draws the minimap of the world in the corner of a screen of the given size
+    #[inline]
+    pub fn draw_minimap(&mut self, ctx: &mut Context, world: &World, width: f32, height: f32) -> GameResult<()> {
+        self.minimap.draw(ctx, world, width, height)
     }
-}
\ No newline at end of file
+}
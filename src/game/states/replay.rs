@@ -0,0 +1,111 @@
+use std::vec;
+
+use crate::{
+    util::Point2,
+    io::{tex::PosText, input::{Action, TickInput}},
+    ext::Timer,
+    game::{
+        State, GameState, StateSwitch, Console, CommandInfo, CommandError, Completion,
+        world::World,
+        replay::Replay,
+        states::play::Play,
+        event::{Event, KeyCode},
+    },
+};
+use ggez::{Context, GameResult};
+
+/// Seconds the end of a replay is shown before going back to the menu
+const END_TIME: f32 = 2.;
+
+/// Watches a replay, playing the level back tick by tick with what the player did
+pub struct Playback {
+    play: Play,
+    ticks: vec::IntoIter<(f32, TickInput)>,
+    /// What the player did in the tick played last, whose crosshair is shown
+    last: TickInput,
+    text: PosText,
+    /// Counts down from the replay running out to going back to the menu
+    end: Option<Timer>,
+}
+
+impl Playback {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(ctx: &mut Context, s: &mut State, replay: Replay) -> GameResult<Box<dyn GameState>> {
+        let world = replay.world(&s.enemies)?;
+        s.rng.reseed(replay.seed);
+
+        Ok(Box::new(Playback {
+            play: Play::with_world(ctx, s, world, true)?,
+            ticks: replay.ticks.into_iter(),
+            last: TickInput::default(),
+            text: s.assets.text(Point2::new(s.width - 96., 4.)).and_text("Replay"),
+            end: None,
+        }))
+    }
+}
+
+impl GameState for Playback {
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+    fn on_enter(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        self.play.on_enter(s, ctx)
+    }
+    fn on_exit(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        self.play.on_exit(s, ctx)
+    }
+    fn update(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        match self.ticks.next() {
+            Some((dt, input)) => {
+                self.last = input;
+                s.aim = s.world_to_screen(input.aim);
+                self.play.tick(s, ctx, dt, input)
+            }
+            None => {
+                let end = self.end.get_or_insert_with(|| Timer::started(END_TIME));
+                if end.update(s.delta()) {
+                    s.switch(StateSwitch::Menu);
+                }
+                Ok(())
+            }
+        }
+    }
+    fn logic(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        // The crosshair is where it was, not where the mouse is
+        s.aim = s.world_to_screen(self.last.aim);
+        self.play.logic(s, ctx)
+    }
+    fn draw(&mut self, s: &State, ctx: &mut Context) -> GameResult<()> {
+        self.play.draw(s, ctx)
+    }
+    fn draw_hud(&mut self, s: &State, ctx: &mut Context) -> GameResult<()> {
+        self.play.draw_hud(s, ctx)?;
+        self.text.draw_text(ctx)
+    }
+    fn event_down(&mut self, s: &mut State, _ctx: &mut Context, event: Event) {
+        if event == Event::Key(KeyCode::Escape) || s.input.is(event, Action::Pause) {
+            s.switch(StateSwitch::Menu);
+        }
+    }
+
+    fn get_world(&self) -> Option<&World> {
+        self.play.get_world()
+    }
+}
+
+/// Adds the replay commands to the console
+pub fn register_commands(console: &mut Console) {
+    console.register(CommandInfo::new("playback", "<name>", "Watches a replay").completing(Completion::Replays), |_, ctx, state, _, args| {
+        let name = args.name(1)?;
+        match Replay::load(ctx, name) {
+            Ok(replay) => {
+                state.switch(StateSwitch::Replay(replay));
+                Ok(())
+            }
+            Err(e) => {
+                error!("Couldn't load replay {}: {}", name, e);
+                Err(CommandError::NoSuchFile)
+            }
+        }
+    });
+}
@@ -0,0 +1,113 @@
+use crate::{
+    util::{Point2, frame_time},
+    io::{tex::PosText, btn::Button},
+    game::{
+        State, GameState, StateSwitch,
+        stats::Stats,
+        campaign::next_level,
+        event::{Event::{self, Mouse, Key}, MouseButton, KeyCode},
+    },
+};
+use ggez::{
+    Context, GameResult,
+    graphics::Rect,
+};
+
+#[derive(Debug, Clone, Copy)]
+/// How a level ended
+pub struct Outcome {
+    pub won: bool,
+    pub stats: Stats,
+    /// Whether it was the fastest the level has been won
+    pub best: bool,
+}
+
+#[derive(Clone, Copy)]
+enum Callback {
+    /// Goes on to the next level of the campaign
+    Continue,
+    Retry,
+    Menu,
+}
+
+fn button_rect(w: f32, h: f32, i: f32) -> Rect {
+    Rect{x: 3. * w / 7., y: h / 2. + 40. + i * 68., w: w / 7., h: 64.}
+}
+
+/// Shows how a level went after it's won or lost, with what to do next
+pub struct Results {
+    title_txt: PosText,
+    stats_txt: PosText,
+    buttons: Vec<Button<Callback>>,
+}
+
+impl Results {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(ctx: &mut Context, s: &mut State, outcome: Outcome) -> GameResult<Box<dyn GameState>> {
+        let (w, h) = (s.width, s.height);
+        let title = if outcome.won { "Level complete" } else { "Game over" };
+        let mut summary = outcome.stats.summary();
+        if outcome.best {
+            summary.push_str("\nNew best time!");
+        }
+
+        let mut callbacks = Vec::with_capacity(3);
+        if outcome.won {
+            callbacks.push(("Continue", Callback::Continue));
+        }
+        callbacks.push(("Retry", Callback::Retry));
+        callbacks.push(("Quit to Menu", Callback::Menu));
+        let buttons = callbacks.into_iter().enumerate()
+            .map(|(i, (text, callback))| Button::new(ctx, &s.assets, button_rect(w, h, i as f32), text, callback))
+            .collect::<GameResult<_>>()?;
+
+        Ok(Box::new(Results {
+            title_txt: s.assets.text_sized(Point2::new(w / 2., 64.), 32.).and_text(title),
+            stats_txt: s.assets.text(Point2::new(w / 2., h / 2. - 80.)).and_text(summary),
+            buttons,
+        }))
+    }
+}
+
+fn click(s: &mut State, callback: Callback) {
+    match callback {
+        Callback::Continue => if next_level(s).is_none() {
+            // Levels outside a campaign have nothing after them
+            s.switch(StateSwitch::Menu);
+        }
+        Callback::Retry => s.switch(StateSwitch::Play),
+        Callback::Menu => s.switch(StateSwitch::Menu),
+    }
+}
+
+impl GameState for Results {
+    fn name(&self) -> &'static str {
+        "results"
+    }
+    fn logic(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        let dt = frame_time(ctx);
+        for button in &mut self.buttons {
+            button.update(dt, s.mouse);
+        }
+        Ok(())
+    }
+    fn draw_hud(&mut self, _s: &State, ctx: &mut Context) -> GameResult<()> {
+        self.title_txt.draw_center(ctx)?;
+        self.stats_txt.draw_center(ctx)?;
+        for button in &self.buttons {
+            button.draw(ctx)?;
+        }
+        Ok(())
+    }
+    fn event_up(&mut self, s: &mut State, _ctx: &mut Context, event: Event) {
+        match event {
+            Mouse(MouseButton::Left) => if let Some(button) = self.buttons.iter().find(|b| b.in_bounds(s.mouse)) {
+                click(s, button.callback);
+            }
+            // The first button is what most people want to do next
+            Key(KeyCode::Return) | Key(KeyCode::Space) => click(s, self.buttons[0].callback),
+            Key(KeyCode::Escape) => click(s, Callback::Menu),
+            _ => (),
+        }
+    }
+}
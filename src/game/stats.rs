@@ -0,0 +1,152 @@
+//! Statistics of how levels were played, like kills and accuracy
+//!
+//! The lifetime totals and the fastest win of each level are kept in the current profile's saves,
+//! one line each: the level's name (`*` for the totals), then the kills, shots fired, shots that hit,
+//! damage taken and seconds played, all separated by spaces.
+
+use std::collections::BTreeMap;
+
+use crate::io::save::Saves;
+use super::{Console, CommandInfo, CommandError, Completion};
+
+/// Name of the save the stats are kept in
+const SAVE_NAME: &str = "stats";
+/// Name the lifetime totals are saved under
+const TOTAL: &str = "*";
+
+/// Formats seconds like `1:05.3`
+pub fn format_time(time: f32) -> String {
+    let minutes = (time / 60.).floor();
+    format!("{}:{:04.1}", minutes, time - minutes * 60.)
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+/// What the player did while playing
+pub struct Stats {
+    pub kills: u32,
+    pub shots: u32,
+    /// Shots that hit an enemy, counting each enemy a piercing shot went through
+    pub hits: u32,
+    pub damage_taken: f32,
+    /// Seconds played
+    pub time: f32,
+}
+
+impl Stats {
+    /// The fraction of shots that hit, 0 if none were fired
+    pub fn accuracy(&self) -> f32 {
+        if self.shots == 0 {
+            0.
+        } else {
+            (self.hits as f32 / self.shots as f32).min(1.)
+        }
+    }
+    fn add(&mut self, other: &Stats) {
+        self.kills += other.kills;
+        self.shots += other.shots;
+        self.hits += other.hits;
+        self.damage_taken += other.damage_taken;
+        self.time += other.time;
+    }
+    /// The stats as lines to show the player
+    pub fn summary(&self) -> String {
+        format!(
+            "Time {}\nKills {}\nAccuracy {:.0}% ({}/{})\nDamage taken {:.0}",
+            format_time(self.time), self.kills, self.accuracy() * 100., self.hits, self.shots, self.damage_taken,
+        )
+    }
+    fn parse(fields: &[&str]) -> Option<Self> {
+        match *fields {
+            [kills, shots, hits, damage_taken, time] => Some(Stats {
+                kills: kills.parse().ok()?,
+                shots: shots.parse().ok()?,
+                hits: hits.parse().ok()?,
+                damage_taken: damage_taken.parse().ok()?,
+                time: time.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+    fn into_line(self, name: &str) -> String {
+        format!("{} {} {} {} {} {}", name, self.kills, self.shots, self.hits, self.damage_taken, self.time)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+/// The stats kept between games
+pub struct Records {
+    /// Everything played, won or lost
+    pub total: Stats,
+    /// The fastest win of each level
+    pub best: BTreeMap<String, Stats>,
+}
+
+impl Records {
+    /// Reads the records from the saves
+    pub fn load(saves: &mut Saves) -> Self {
+        let bytes = match saves.read(SAVE_NAME) {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => return Records::default(),
+            Err(e) => {
+                error!("Couldn't read stats: {}", e);
+                return Records::default()
+            }
+        };
+        let mut records = Records::default();
+        for line in String::from_utf8_lossy(&bytes).lines() {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            let stats = match fields.split_first().and_then(|(&name, rest)| Stats::parse(rest).map(|s| (name, s))) {
+                Some(stats) => stats,
+                None => {
+                    warn!("Bad line in stats: {}", line);
+                    continue
+                }
+            };
+            match stats {
+                (TOTAL, stats) => records.total = stats,
+                (level, stats) => {
+                    records.best.insert(level.to_owned(), stats);
+                }
+            }
+        }
+        records
+    }
+    fn save(&self, saves: &mut Saves) {
+        let lines: Vec<_> = Some(self.total.into_line(TOTAL)).into_iter()
+            .chain(self.best.iter().map(|(level, stats)| stats.into_line(level)))
+            .collect();
+        if let Err(e) = saves.write(SAVE_NAME, lines.join("\n").as_bytes()) {
+            error!("Couldn't save stats: {}", e);
+        }
+    }
+}
+
+/// Adds a played level to the lifetime totals, keeping it as the level's best if it was won faster than before
+///
+/// Returns whether it was the fastest win
+pub fn record(saves: &mut Saves, level: &str, stats: &Stats, won: bool) -> bool {
+    let mut records = Records::load(saves);
+    records.total.add(stats);
+    let fastest = won && records.best.get(level).map_or(true, |best| stats.time < best.time);
+    if fastest {
+        records.best.insert(level.to_owned(), *stats);
+    }
+    records.save(saves);
+    fastest
+}
+
+/// Adds the stats commands to the console
+pub fn register_commands(console: &mut Console) {
+    console.register(CommandInfo::new("stats", "[level]", "Shows the lifetime stats or the fastest win of a level").completing(Completion::Levels), |_, _, state, _, args| {
+        let records = Records::load(&mut state.saves);
+        let (title, stats) = match args.get(1) {
+            Some(level) => (format!("Fastest win of {}", level), *records.best.get(level).ok_or(CommandError::NoSuchLevel)?),
+            None => ("Lifetime".to_owned(), records.total),
+        };
+        info!("{}", title);
+        for line in stats.summary().lines() {
+            info!("  {}", line);
+        }
+        Ok(())
+    });
+}
@@ -0,0 +1,229 @@
+//! Weapon definitions loaded from the TOML files in `/weapons`
+//!
+//! Each file defines one weapon, named after the file:
+//!
+//! ```toml
+//! name = "Pistol"
+//! fire_rate = 4.0     # shots per second
+//! damage = 25.0
+//! spread = 2.0        # degrees
+//! clip_size = 12
+//! reload_time = 1.2   # seconds
+//! fire_sound = "shot" # optional
+//! sprite = "weapons/pistol"
+//! ```
+//!
+//! Bullets fly straight by default, but can be given a trajectory with any of these optional keys:
+//!
+//! ```toml
+//! arc = 300.0     # pixels per second thrown upwards, flying over things
+//! bounces = 2     # times bouncing off walls
+//! pierce = 1      # enemies gone through
+//! homing = 90.0   # degrees per second turned towards enemies
+//! ```
+
+use std::collections::BTreeMap;
+#[cfg(not(feature = "headless"))]
+use std::io::Read;
+#[cfg(not(feature = "headless"))]
+use std::path::Path;
+
+use crate::{
+    ext::{Cooldown, Timer},
+    game::world::Trajectory,
+};
+use ggez::{GameResult, GameError};
+#[cfg(not(feature = "headless"))]
+use ggez::{Context, filesystem};
+use rand::Rng;
+use toml::Value;
+
+#[derive(Debug, Clone)]
+/// What a kind of weapon is like
+pub struct WeaponDef {
+    pub name: String,
+    /// Shots per second
+    pub fire_rate: f32,
+    pub damage: f32,
+    /// Radians bullets can stray to either side
+    pub spread: f32,
+    pub clip_size: u16,
+    /// Seconds it takes to reload
+    pub reload_time: f32,
+    pub fire_sound: Option<String>,
+    /// Sprite drawn in the hands of whoever holds it
+    pub sprite: String,
+    /// How its bullets fly
+    pub trajectory: Trajectory,
+}
+
+fn bad_weapon<T>(id: &str, msg: &str) -> GameResult<T> {
+    Err(GameError::ResourceLoadError(format!("Bad weapon {}: {}", id, msg)))
+}
+
+/// Reads the optional trajectory keys of a weapon or enemy definition
+pub fn trajectory_from_toml(value: &Value) -> Result<Trajectory, String> {
+    let optional = |key: &str| match value.get(key) {
+        Some(&Value::Float(n)) => Ok(n as f32),
+        Some(&Value::Integer(n)) => Ok(n as f32),
+        Some(_) => Err(format!("{} should be a number", key)),
+        None => Ok(0.),
+    };
+    let count = |key: &str| {
+        let n = optional(key)?;
+        if n < 0. || n > f32::from(u8::max_value()) {
+            return Err(format!("{} is out of range", key))
+        }
+        Ok(n as u8)
+    };
+    Ok(Trajectory {
+        arc: optional("arc")?.max(0.),
+        bounces: count("bounces")?,
+        pierce: count("pierce")?,
+        homing: optional("homing")?.max(0.).to_radians(),
+    })
+}
+
+impl WeaponDef {
+    fn from_toml(id: &str, src: &str) -> GameResult<Self> {
+        let value: Value = match src.parse() {
+            Ok(v) => v,
+            Err(e) => return bad_weapon(id, &e.to_string()),
+        };
+        let number = |key: &str| -> GameResult<f32> {
+            match value.get(key) {
+                Some(&Value::Float(n)) => Ok(n as f32),
+                Some(&Value::Integer(n)) => Ok(n as f32),
+                _ => bad_weapon(id, &format!("{} should be a number", key)),
+            }
+        };
+        let string = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_owned);
+
+        let fire_rate = number("fire_rate")?;
+        if fire_rate <= 0. {
+            return bad_weapon(id, "fire_rate should be positive")
+        }
+        let clip_size = number("clip_size")?;
+        if clip_size < 1. || clip_size > f32::from(u16::max_value()) {
+            return bad_weapon(id, "clip_size is out of range")
+        }
+        Ok(WeaponDef {
+            name: string("name").unwrap_or_else(|| id.to_owned()),
+            fire_rate,
+            damage: number("damage")?,
+            spread: number("spread")?.to_radians(),
+            clip_size: clip_size as u16,
+            reload_time: number("reload_time")?,
+            fire_sound: string("fire_sound"),
+            sprite: string("sprite").unwrap_or_else(|| format!("weapons/{}", id)),
+            trajectory: match trajectory_from_toml(&value) {
+                Ok(t) => t,
+                Err(e) => return bad_weapon(id, &e),
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// All the weapon definitions by name
+pub struct Weapons {
+    defs: BTreeMap<String, WeaponDef>,
+}
+
+impl Weapons {
+    /// Loads every weapon in `/weapons`, skipping the ones that can't be loaded
+    #[cfg(not(feature = "headless"))]
+    pub fn load(ctx: &mut Context) -> Self {
+        let mut weapons = Weapons::default();
+        let paths: Vec<_> = match filesystem::read_dir(ctx, "/weapons") {
+            Ok(paths) => paths.filter(|p| p.extension().map_or(false, |e| e == "toml")).collect(),
+            Err(_) => return weapons,
+        };
+        for path in paths {
+            let id = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(id) => id.to_owned(),
+                None => continue,
+            };
+            match Self::load_def(ctx, &id, &path) {
+                Ok(def) => {
+                    weapons.defs.insert(id, def);
+                }
+                Err(e) => error!("Couldn't load weapon {}: {}", id, e),
+            }
+        }
+        weapons
+    }
+    #[cfg(not(feature = "headless"))]
+    fn load_def(ctx: &mut Context, id: &str, path: &Path) -> GameResult<WeaponDef> {
+        let mut src = String::new();
+        filesystem::open(ctx, path)?.read_to_string(&mut src)?;
+        WeaponDef::from_toml(id, &src)
+    }
+    #[inline]
+    pub fn get(&self, id: &str) -> Option<&WeaponDef> {
+        self.defs.get(id)
+    }
+    pub fn ids(&self) -> impl Iterator<Item=&str> {
+        self.defs.keys().map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A weapon being carried
+pub struct Weapon {
+    pub def: WeaponDef,
+    /// Bullets left in the clip
+    pub loaded: u16,
+    cooldown: Cooldown,
+    reload: Timer,
+}
+
+impl Weapon {
+    pub fn new(def: WeaponDef) -> Self {
+        Weapon {
+            loaded: def.clip_size,
+            cooldown: Cooldown::new(1. / def.fire_rate),
+            reload: Timer::new(def.reload_time),
+            def,
+        }
+    }
+    pub fn update(&mut self, dt: f32) {
+        self.cooldown.update(dt);
+        if self.reload.update(dt) {
+            self.loaded = self.def.clip_size;
+        }
+    }
+    #[inline]
+    pub fn is_reloading(&self) -> bool {
+        !self.reload.is_done()
+    }
+    /// How far along reloading is
+    #[inline]
+    pub fn reload_progress(&self) -> f32 {
+        self.reload.progress()
+    }
+    /// Starts reloading unless the clip is full
+    pub fn reload(&mut self) {
+        if !self.is_reloading() && self.loaded < self.def.clip_size {
+            self.reload.restart();
+        }
+    }
+    /// Fires a bullet if the weapon is ready, returning how far it strays from the aim in radians
+    ///
+    /// Starts reloading when the clip is empty
+    pub fn fire<R: Rng>(&mut self, rng: &mut R) -> Option<f32> {
+        if self.is_reloading() {
+            return None
+        }
+        if self.loaded == 0 {
+            self.reload();
+            return None
+        }
+        if !self.cooldown.trigger() {
+            return None
+        }
+        self.loaded -= 1;
+        let spread = self.def.spread;
+        Some(if spread > 0. { rng.gen_range(-spread, spread) } else { 0. })
+    }
+}
@@ -0,0 +1,209 @@
+use crate::{
+    util::{Point2, Vector2, angle_to_vec, angle_from_vec},
+    ext::Timer,
+};
+#[cfg(not(feature = "headless"))]
+use crate::io::tex::Assets;
+#[cfg(not(feature = "headless"))]
+use ggez::{Context, GameResult, graphics};
+
+use super::{Object, Grid, Enemy, SpatialHash, ENEMY_RADIUS, PLAYER_RADIUS};
+
+/// Pixels per second a bullet flies
+const BULLET_SPEED: f32 = 800.;
+/// Seconds before a bullet that hasn't hit anything disappears
+const BULLET_LIFETIME: f32 = 1.5;
+const BULLET_DAMAGE: f32 = 25.;
+/// Longest distance a bullet moves between collision checks
+///
+/// Shorter than an enemy's radius, so bullets can't skip through anything
+const SUBSTEP: f32 = 8.;
+/// Pixels per second squared arcing bullets fall with
+const GRAVITY: f32 = 600.;
+/// Height above which arcing bullets fly over walls and enemies
+const CLEARANCE: f32 = 24.;
+/// Furthest away homing bullets notice what they're after
+const HOMING_RANGE: f32 = 320.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// How a kind of bullet flies, the default being straight until it hits something
+pub struct Trajectory {
+    /// Pixels per second the bullet is thrown upwards with, arcing over things in its way
+    pub arc: f32,
+    /// Times it bounces off walls before stopping
+    pub bounces: u8,
+    /// Enemies it goes through before stopping
+    pub pierce: u8,
+    /// Radians per second it turns towards the closest enemy
+    pub homing: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// What a bullet did in an update
+pub enum Hit {
+    None,
+    Wall,
+    /// Hit the enemy at this index
+    Enemy(usize),
+    /// Hit the enemy at this index and kept going
+    Pierced(usize),
+    /// An enemy's bullet hit the player
+    Player,
+    Expired,
+}
+
+#[derive(Debug, Clone)]
+pub struct Bullet {
+    pub obj: Object,
+    pub damage: f32,
+    /// Fired by an enemy, so it hits the player instead of enemies
+    pub hostile: bool,
+    lifetime: Timer,
+    trajectory: Trajectory,
+    /// How high above the ground it is
+    height: f32,
+    /// Pixels per second it's moving upwards
+    rise: f32,
+    bounces_left: u8,
+    pierces_left: u8,
+    /// Distance left to fly before hitting enemies again after piercing one
+    ghost: f32,
+}
+
+impl Bullet {
+    /// Makes a bullet at `pos` flying in the direction `rot`
+    pub fn new(pos: Point2, rot: f32) -> Self {
+        Self::with_trajectory(pos, rot, Trajectory::default())
+    }
+    pub fn with_trajectory(pos: Point2, rot: f32, trajectory: Trajectory) -> Self {
+        // Bullets are points, so they can fly right past corners
+        let mut obj = Object::new(pos, 0.);
+        obj.rot = rot;
+        obj.vel = angle_to_vec(rot) * BULLET_SPEED;
+        Bullet {
+            obj,
+            damage: BULLET_DAMAGE,
+            hostile: false,
+            lifetime: Timer::started(BULLET_LIFETIME),
+            trajectory,
+            height: 0.,
+            rise: trajectory.arc,
+            bounces_left: trajectory.bounces,
+            pierces_left: trajectory.pierce,
+            ghost: 0.,
+        }
+    }
+    /// Whether it's high enough to fly over walls and enemies
+    #[inline]
+    fn is_airborne(&self) -> bool {
+        self.height > CLEARANCE
+    }
+    /// Turns towards the closest target in range
+    fn home(&mut self, targets: impl Iterator<Item=Point2>, dt: f32) {
+        let pos = self.obj.pos;
+        let closest = targets
+            .filter(|&p| (p - pos).norm() < HOMING_RANGE)
+            .min_by(|&a, &b| (a - pos).norm_squared().partial_cmp(&(b - pos).norm_squared()).unwrap());
+        if let Some(target) = closest {
+            self.obj.rotate_toward(target, self.trajectory.homing * dt);
+            self.obj.vel = angle_to_vec(self.obj.rot) * self.obj.vel.norm();
+        }
+    }
+    /// Bounces off the solid tile it moved into with `step`
+    fn bounce(&mut self, grid: &Grid, from: Point2, step: Vector2) {
+        let solid_at = |p: Point2| {
+            let (x, y) = Grid::snap(p);
+            grid.is_solid(x, y)
+        };
+        let hit_x = solid_at(Point2::new(from.x + step.x, from.y));
+        let hit_y = solid_at(Point2::new(from.x, from.y + step.y));
+        // Hitting a corner head on sends it straight back
+        if hit_x || !hit_y {
+            self.obj.vel.x = -self.obj.vel.x;
+        }
+        if hit_y || !hit_x {
+            self.obj.vel.y = -self.obj.vel.y;
+        }
+        self.obj.pos = from;
+        self.obj.rot = angle_from_vec(self.obj.vel);
+    }
+    /// Flies for `dt` seconds, stopping at the first wall or enemy in the way unless it bounces or pierces
+    ///
+    /// Hostile bullets go for the player at `player` instead of enemies, which are found with `cells`
+    pub fn update(&mut self, grid: &Grid, enemies: &[Enemy], cells: &SpatialHash, player: Point2, dt: f32) -> Hit {
+        if self.lifetime.update(dt) {
+            return Hit::Expired
+        }
+        if self.trajectory.homing > 0. {
+            if self.hostile {
+                self.home(std::iter::once(player), dt);
+            } else {
+                let near = cells.query_radius(self.obj.pos, HOMING_RANGE);
+                self.home(near.into_iter().map(|i| enemies[i].obj.pos), dt);
+            }
+        }
+        if self.trajectory.arc > 0. {
+            self.rise -= GRAVITY * dt;
+            self.height += self.rise * dt;
+            if self.height <= 0. {
+                return Hit::Expired
+            }
+        }
+        let step = self.obj.vel * dt;
+        let steps = (step.norm() / SUBSTEP).ceil().max(1.);
+        let substep = step / steps;
+
+        for _ in 0..steps as u32 {
+            let from = self.obj.pos;
+            self.obj.pos += substep;
+            self.ghost = (self.ghost - substep.norm()).max(0.);
+            if self.is_airborne() {
+                continue
+            }
+            let (x, y) = Grid::snap(self.obj.pos);
+            if grid.is_solid(x, y) {
+                if self.bounces_left == 0 {
+                    return Hit::Wall
+                }
+                self.bounces_left -= 1;
+                // The rest of this update's movement is lost, which is hardly noticeable
+                self.bounce(grid, from, substep);
+                return Hit::None
+            }
+            if self.hostile {
+                if (player - self.obj.pos).norm() < PLAYER_RADIUS {
+                    return Hit::Player
+                }
+                continue
+            }
+            if self.ghost > 0. {
+                continue
+            }
+            let pos = self.obj.pos;
+            let hit = cells.query_radius(pos, ENEMY_RADIUS)
+                .into_iter()
+                .map(|i| (i, (enemies[i].obj.pos - pos).norm()))
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            if let Some((i, _)) = hit {
+                if self.pierces_left == 0 {
+                    return Hit::Enemy(i)
+                }
+                self.pierces_left -= 1;
+                // Don't hit the same enemy again on the way through it
+                self.ghost = 2. * ENEMY_RADIUS;
+                return Hit::Pierced(i)
+            }
+        }
+        Hit::None
+    }
+    #[cfg(not(feature = "headless"))]
+    pub fn draw(&self, ctx: &mut Context, assets: &Assets) -> GameResult<()> {
+        let img = assets.get_img(ctx, if self.hostile { "common/enemy_bullet" } else { "common/bullet" });
+        // Arcing bullets are drawn higher up and bigger the higher they are
+        let scale = 1. + self.height / 64.;
+        let params = self.obj.drawparams()
+            .dest(self.obj.pos - Vector2::new(0., self.height))
+            .scale(Vector2::new(scale, scale));
+        graphics::draw(ctx, &*img, params)
+    }
+}
@@ -0,0 +1,328 @@
+use crate::{
+    util::{Point2, Vector2},
+    io::{anim::{Animation, Sheet}, save::Saves},
+    ext::{Timer, Cooldown},
+};
+#[cfg(not(feature = "headless"))]
+use crate::io::tex::Assets;
+#[cfg(not(feature = "headless"))]
+use ggez::{Context, GameResult, graphics::Color};
+
+use super::{Object, Grid, Tile, Health, Enemy, SpatialHash, tile_center};
+
+pub const COMPANION_RADIUS: f32 = 10.;
+/// Radians per second a companion turns
+const TURN_SPEED: f32 = 8.;
+/// How close the player has to walk to a stray to recruit it
+const RECRUIT_RANGE: f32 = 48.;
+/// How far behind the player a following companion stays
+const FOLLOW_DISTANCE: f32 = 56.;
+/// How close the player has to stand to a downed companion to revive it
+const REVIVE_RANGE: f32 = 40.;
+/// Seconds it takes to revive a downed companion
+const REVIVE_TIME: f32 = 3.;
+/// Share of its health a companion gets back when revived
+const REVIVE_HEALTH: f32 = 0.5;
+/// Furthest away an attacking companion goes after enemies
+const HUNT_RANGE: f32 = 320.;
+/// How close a companion has to be to bite
+const BITE_RANGE: f32 = 24.;
+/// Seconds between bites
+const BITE_TIME: f32 = 0.6;
+/// How close a companion has to be to a place to have reached it
+const ARRIVE_RANGE: f32 = 8.;
+/// Seconds between finding new paths
+const REPATH_TIME: f32 = 0.5;
+/// Name of the save the companion is kept in
+const SAVE_NAME: &str = "companion";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// What kind of animal a companion is
+pub enum CompanionKind {
+    Dog,
+    Sheep,
+}
+
+const KINDS: &[(&str, CompanionKind)] = &[
+    ("dog", CompanionKind::Dog),
+    ("sheep", CompanionKind::Sheep),
+];
+
+impl CompanionKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        KINDS.iter().find(|&&(n, _)| n == name).map(|&(_, k)| k)
+    }
+    pub fn name(self) -> &'static str {
+        KINDS.iter().find(|&&(_, k)| k == self).map(|&(n, _)| n).unwrap()
+    }
+    /// Names of every kind
+    pub fn names() -> impl Iterator<Item=&'static str> {
+        KINDS.iter().map(|&(n, _)| n)
+    }
+    pub fn walk(self) -> Sheet {
+        match self {
+            CompanionKind::Dog => Sheet { sprite: "common/dog_walk", frames: 4, frame_time: 0.08 },
+            CompanionKind::Sheep => Sheet { sprite: "common/sheep_walk", frames: 4, frame_time: 0.14 },
+        }
+    }
+    /// Pixels per second it walks
+    fn speed(self) -> f32 {
+        match self {
+            CompanionKind::Dog => 180.,
+            CompanionKind::Sheep => 130.,
+        }
+    }
+    fn max_hp(self) -> f32 {
+        match self {
+            CompanionKind::Dog => 80.,
+            CompanionKind::Sheep => 150.,
+        }
+    }
+    /// Damage done with each bite
+    fn damage(self) -> f32 {
+        match self {
+            CompanionKind::Dog => 20.,
+            CompanionKind::Sheep => 8.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// What a companion has been told to do
+pub enum Order {
+    Follow,
+    /// Stay where it was told to
+    Stay,
+    /// Fetch whatever can be harvested from the newest marker
+    Fetch,
+    /// Go after the enemies near the player
+    Attack,
+}
+
+/// Every order, in the order they're laid out on the command wheel
+pub const ORDERS: [Order; 4] = [Order::Follow, Order::Stay, Order::Fetch, Order::Attack];
+
+impl Order {
+    pub fn name(self) -> &'static str {
+        match self {
+            Order::Follow => "Follow",
+            Order::Stay => "Stay",
+            Order::Fetch => "Fetch",
+            Order::Attack => "Attack",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// An animal that follows the player around once recruited
+pub struct Companion {
+    pub obj: Object,
+    pub kind: CompanionKind,
+    pub health: Health,
+    /// Whether it has joined the player, strays waiting to be walked up to
+    pub recruited: bool,
+    order: Order,
+    /// Where it was told to stay or fetch from
+    spot: Point2,
+    /// What it's bringing back when fetching
+    carrying: Option<String>,
+    /// Tiles left to walk through, the next one last
+    path: Vec<Tile>,
+    repath: Timer,
+    bite: Cooldown,
+    revive: Timer,
+    anim: Animation,
+}
+
+impl Companion {
+    /// Makes a stray that joins the player when they walk up to it
+    pub fn new(kind: CompanionKind, pos: Point2) -> Self {
+        Companion {
+            obj: Object::new(pos, COMPANION_RADIUS),
+            kind,
+            health: Health::new(kind.max_hp(), 0.),
+            recruited: false,
+            order: Order::Follow,
+            spot: pos,
+            carrying: None,
+            path: Vec::new(),
+            repath: Timer::new(REPATH_TIME),
+            bite: Cooldown::new(BITE_TIME),
+            revive: Timer::started(REVIVE_TIME),
+            anim: Animation::looping(kind.walk()),
+        }
+    }
+    /// Reads the player's companion from the saves, placing it at `pos`
+    pub fn load(saves: &mut Saves, pos: Point2) -> Option<Self> {
+        let bytes = match saves.read(SAVE_NAME) {
+            Ok(bytes) => bytes?,
+            Err(e) => {
+                error!("Couldn't read companion: {}", e);
+                return None
+            }
+        };
+        let text = String::from_utf8_lossy(&bytes);
+        let mut lines = text.lines();
+        let kind = lines.next().and_then(CompanionKind::from_name)?;
+        let hp = lines.next().and_then(|l| l.parse().ok()).unwrap_or_else(|| kind.max_hp());
+        Some(Companion::recruited(kind, hp, pos))
+    }
+    /// Makes a companion that has already joined the player, with `hp` health left
+    pub fn recruited(kind: CompanionKind, hp: f32, pos: Point2) -> Self {
+        let mut companion = Companion::new(kind, pos);
+        companion.recruited = true;
+        companion.health.hp = companion.health.max_hp.min(hp);
+        companion
+    }
+    /// Keeps the companion in the saves so it comes along to the next level
+    pub fn save(&self, saves: &mut Saves) {
+        let text = format!("{}\n{}", self.kind.name(), self.health.hp);
+        if let Err(e) = saves.write(SAVE_NAME, text.as_bytes()) {
+            error!("Couldn't save companion: {}", e);
+        }
+    }
+    /// Whether it's lying down waiting to be revived
+    #[inline]
+    pub fn is_downed(&self) -> bool {
+        self.health.is_dead()
+    }
+    #[inline]
+    pub fn order(&self) -> Order {
+        self.order
+    }
+    /// Tells it what to do, `spot` being where to stay or fetch from
+    pub fn command(&mut self, order: Order, spot: Point2) {
+        self.order = order;
+        self.spot = spot;
+        self.carrying = None;
+        self.path.clear();
+    }
+    /// Moves it to `pos` right away, like when going to another level
+    pub fn place(&mut self, pos: Point2) {
+        self.obj.pos = pos;
+        self.spot = pos;
+        self.path.clear();
+    }
+    /// Does what it was told for `dt` seconds, the player being at `player` and the enemies being found with `cells`
+    ///
+    /// Returns what it fetched once it's brought it back to the player
+    pub fn update(&mut self, grid: &Grid, player: Point2, enemies: &mut [Enemy], cells: &SpatialHash, dt: f32) -> Option<String> {
+        self.obj.vel = Vector2::new(0., 0.);
+        self.bite.update(dt);
+        self.repath.update(dt);
+        let to_player = (player - self.obj.pos).norm();
+
+        if !self.recruited {
+            if to_player <= RECRUIT_RANGE {
+                self.recruited = true;
+            }
+            return None
+        }
+        if self.is_downed() {
+            // The player has to stay close the whole time for it to get back up
+            if to_player > REVIVE_RANGE {
+                self.revive.restart();
+            } else if self.revive.update(dt) {
+                self.health.hp = self.health.max_hp * REVIVE_HEALTH;
+                self.revive.restart();
+            }
+            return None
+        }
+
+        match self.order {
+            Order::Follow => {
+                if to_player > FOLLOW_DISTANCE {
+                    self.walk_to(grid, player, dt);
+                }
+            }
+            Order::Stay => {
+                let spot = self.spot;
+                self.walk_to(grid, spot, dt);
+            }
+            Order::Fetch => return self.fetch(grid, player, dt),
+            Order::Attack => {
+                let pos = self.obj.pos;
+                let distance = |i: &usize| (enemies[*i].obj.pos - pos).norm_squared();
+                let closest = cells.query_radius(player, HUNT_RANGE).into_iter()
+                    .min_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap());
+                match closest {
+                    Some(i) => {
+                        let enemy = &mut enemies[i];
+                        if (enemy.obj.pos - pos).norm() <= BITE_RANGE {
+                            self.obj.rotate_toward(enemy.obj.pos, TURN_SPEED * dt);
+                            if self.bite.trigger() {
+                                enemy.health.damage(self.kind.damage());
+                            }
+                        } else {
+                            self.walk_to(grid, enemy.obj.pos, dt);
+                        }
+                    }
+                    // Heel when there's nothing to fight
+                    None => if to_player > FOLLOW_DISTANCE {
+                        self.walk_to(grid, player, dt);
+                    }
+                }
+            }
+        }
+        None
+    }
+    /// Goes to the spot, picks up what can be harvested there and brings it to the player
+    fn fetch(&mut self, grid: &Grid, player: Point2, dt: f32) -> Option<String> {
+        if self.carrying.is_none() {
+            let spot = self.spot;
+            if !self.walk_to(grid, spot, dt) {
+                return None
+            }
+            let (x, y) = Grid::snap(spot);
+            self.carrying = grid.resource(x, y);
+            if self.carrying.is_none() {
+                self.order = Order::Follow;
+            }
+            self.path.clear();
+            return None
+        }
+        if (player - self.obj.pos).norm() <= FOLLOW_DISTANCE {
+            self.order = Order::Follow;
+            return self.carrying.take()
+        }
+        self.walk_to(grid, player, dt);
+        None
+    }
+    /// Walks towards `goal` around solid tiles, returning whether it's there
+    fn walk_to(&mut self, grid: &Grid, goal: Point2, dt: f32) -> bool {
+        if (goal - self.obj.pos).norm() <= ARRIVE_RANGE {
+            return true
+        }
+        if self.repath.is_done() {
+            self.path = grid.find_path(Grid::snap(self.obj.pos), Grid::snap(goal)).unwrap_or_default();
+            self.repath.restart();
+        }
+        let speed = self.kind.speed() * grid.speed_at(self.obj.pos);
+        while let Some(&tile) = self.path.last() {
+            if (tile_center(tile) - self.obj.pos).norm() < speed * dt {
+                self.path.pop();
+            } else {
+                break
+            }
+        }
+        let next = self.path.last().copied().map_or(goal, tile_center);
+        let dir = next - self.obj.pos;
+        if dir.norm_squared() > 0. {
+            self.obj.vel = dir.normalize() * speed;
+        }
+        self.obj.slide(grid, dt);
+        self.obj.rotate_toward(next, TURN_SPEED * dt);
+        self.anim.update(dt);
+        false
+    }
+    #[cfg(not(feature = "headless"))]
+    pub fn draw(&self, ctx: &mut Context, assets: &Assets) -> GameResult<()> {
+        let params = self.obj.drawparams();
+        if self.is_downed() {
+            // Greyed out, brightening as it's revived
+            let v = 0.4 + 0.6 * self.revive.progress();
+            return self.anim.draw(ctx, assets, params.color(Color::new(v, v, v, 1.)))
+        }
+        self.anim.draw(ctx, assets, params)
+    }
+}
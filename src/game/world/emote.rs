@@ -0,0 +1,72 @@
+use crate::{
+    io::anim::{Animation, Sheet},
+    ext::Timer,
+};
+#[cfg(not(feature = "headless"))]
+use crate::{util::{Point2, Vector2}, io::tex::Assets};
+#[cfg(not(feature = "headless"))]
+use ggez::{Context, GameResult, graphics::DrawParam};
+
+/// Pixels above the player emotes are shown
+const EMOTE_HEIGHT: f32 = 36.;
+/// Seconds an emote is shown
+const EMOTE_TIME: f32 = 2.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Something the player can show to others
+pub enum Emote {
+    Wave,
+    Point,
+    Dance,
+}
+
+/// Every emote, in the order they're laid out on the emote wheel
+pub const EMOTES: [Emote; 3] = [Emote::Wave, Emote::Point, Emote::Dance];
+
+impl Emote {
+    pub fn name(self) -> &'static str {
+        match self {
+            Emote::Wave => "Wave",
+            Emote::Point => "Point",
+            Emote::Dance => "Dance",
+        }
+    }
+    fn sheet(self) -> Sheet {
+        match self {
+            Emote::Wave => Sheet { sprite: "emotes/wave", frames: 4, frame_time: 0.12 },
+            Emote::Point => Sheet { sprite: "emotes/point", frames: 2, frame_time: 0.3 },
+            Emote::Dance => Sheet { sprite: "emotes/dance", frames: 6, frame_time: 0.1 },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// An emote being shown above the player
+pub struct Emoting {
+    pub emote: Emote,
+    anim: Animation,
+    timer: Timer,
+}
+
+impl Emoting {
+    pub fn new(emote: Emote) -> Self {
+        Emoting {
+            emote,
+            anim: Animation::looping(emote.sheet()),
+            timer: Timer::started(EMOTE_TIME),
+        }
+    }
+    /// Plays the emote for `dt` seconds, returning whether it's over
+    pub fn update(&mut self, dt: f32) -> bool {
+        self.anim.update(dt);
+        self.timer.update(dt) || self.timer.is_done()
+    }
+    /// Draws the emote above `pos`
+    #[cfg(not(feature = "headless"))]
+    pub fn draw(&self, ctx: &mut Context, assets: &Assets, pos: Point2) -> GameResult<()> {
+        let params = DrawParam::new()
+            .dest(pos - Vector2::new(0., EMOTE_HEIGHT))
+            .offset(Point2::new(0.5, 0.5));
+        self.anim.draw(ctx, assets, params)
+    }
+}
@@ -0,0 +1,249 @@
+use crate::{
+    util::{Point2, Vector2, angle_from_vec},
+    io::anim::{Animation, Sheet},
+    ext::{Timer, Cooldown},
+    game::{enemies::EnemyDef, barks::{BarkEvent, BarkLine}},
+};
+#[cfg(not(feature = "headless"))]
+use crate::io::tex::Assets;
+#[cfg(not(feature = "headless"))]
+use ggez::{Context, GameResult};
+use rand::Rng;
+
+use super::{Object, Grid, Tile, Health, Bullet, tile_center};
+
+/// Pixels per second an enemy walks
+pub const ENEMY_SPEED: f32 = 100.;
+pub const ENEMY_RADIUS: f32 = 12.;
+/// Radians per second an enemy turns
+const ENEMY_TURN_SPEED: f32 = 6.;
+pub const ENEMY_HEALTH: f32 = 100.;
+/// How close the player has to be for an enemy to notice them
+const SIGHT_RANGE: f32 = 320.;
+/// How far away the player can get before a chasing enemy gives up
+const GIVE_UP_RANGE: f32 = 480.;
+/// How close an enemy has to be to attack
+const ATTACK_RANGE: f32 = 28.;
+/// Seconds between finding new paths while chasing
+const REPATH_TIME: f32 = 0.5;
+/// Seconds between attacks
+const ATTACK_TIME: f32 = 0.8;
+pub const ATTACK_DAMAGE: f32 = 10.;
+/// The walk cycle, its first frame being the enemy standing still
+pub const ENEMY_WALK: Sheet = Sheet { sprite: "common/enemy_walk", frames: 4, frame_time: 0.15 };
+pub const ENEMY_ATTACK: Sheet = Sheet { sprite: "common/enemy_attack", frames: 3, frame_time: 0.08 };
+/// Drawing back before a shot, held on its last frame until the shot goes off
+pub const ENEMY_WINDUP: Sheet = Sheet { sprite: "common/enemy_windup", frames: 4, frame_time: 0.1 };
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnemyState {
+    Idle,
+    Chase,
+    Attack,
+    /// Standing still shooting at the player
+    Shoot,
+}
+
+#[derive(Debug, Clone)]
+/// Something an enemy did to the player
+pub enum EnemyAttack {
+    /// Hit the player up close for this much damage
+    Melee(f32),
+    /// Started winding up a shot, making a sound if it has one
+    Windup(Option<String>),
+    /// Fired a bullet, making a sound if it has one
+    Shot(Bullet, Option<String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Enemy {
+    pub obj: Object,
+    /// The name of its definition, empty for the built-in enemy
+    pub kind: String,
+    def: EnemyDef,
+    pub health: Health,
+    pub state: EnemyState,
+    /// Tiles left to walk through, the next one last
+    path: Vec<Tile>,
+    repath: Timer,
+    attack: Cooldown,
+    shot: Cooldown,
+    /// Where the shot being wound up will go
+    aim: Option<Point2>,
+    windup: Timer,
+    anim: Animation,
+    /// Keeps it from saying something too often
+    bark: Cooldown,
+    /// Its health when it was last checked for having been hurt
+    seen_hp: f32,
+}
+
+impl Enemy {
+    /// Makes the built-in enemy, which only attacks up close
+    pub fn new(pos: Point2) -> Self {
+        Self::with_def(pos, String::new(), EnemyDef::default())
+    }
+    pub fn with_def(pos: Point2, kind: String, def: EnemyDef) -> Self {
+        let (cooldown, windup) = def.ranged.as_ref().map_or((0., 0.), |r| (r.cooldown, r.windup));
+        Enemy {
+            obj: Object::new(pos, ENEMY_RADIUS),
+            kind,
+            health: Health::new(def.health, 0.),
+            state: EnemyState::Idle,
+            path: Vec::new(),
+            repath: Timer::new(REPATH_TIME),
+            attack: Cooldown::new(ATTACK_TIME),
+            shot: Cooldown::new(cooldown),
+            aim: None,
+            windup: Timer::new(windup),
+            anim: Animation::looping(ENEMY_WALK),
+            bark: Cooldown::new(def.barks.cooldown),
+            seen_hp: def.health,
+            def,
+        }
+    }
+    /// Moves it and where it's aiming to where `f` puts them, forgetting where it was going
+    pub fn move_points(&mut self, f: impl Fn(Point2) -> Point2) {
+        self.obj.pos = f(self.obj.pos);
+        self.aim = self.aim.map(&f);
+        self.path.clear();
+    }
+    #[inline]
+    pub fn def(&self) -> &EnemyDef {
+        &self.def
+    }
+    /// The name of its kind, for things like subtitles
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.def.name
+    }
+    /// What's happened to it that it could say something about, `was` being its state
+    /// before the last update
+    pub fn bark_event(&mut self, was: EnemyState) -> Option<BarkEvent> {
+        let hurt = self.health.hp < self.seen_hp;
+        self.seen_hp = self.health.hp;
+        match (was, self.state) {
+            (EnemyState::Idle, now) if now != EnemyState::Idle => Some(BarkEvent::Spot),
+            (was, EnemyState::Idle) if was != EnemyState::Idle => Some(BarkEvent::Lose),
+            _ if hurt => Some(BarkEvent::Hurt),
+            _ => None,
+        }
+    }
+    /// Picks a line to say about `event`, unless it's said something too recently
+    ///
+    /// Dying always gets a line in
+    pub fn bark<R: Rng>(&mut self, event: BarkEvent, rng: &mut R) -> Option<BarkLine> {
+        if !self.def.barks.has(event) || (event != BarkEvent::Death && !self.bark.trigger()) {
+            return None
+        }
+        self.def.barks.pick(event, rng).cloned()
+    }
+    /// Whether it can shoot at the player at `target` from where it is
+    fn can_shoot(&self, grid: &Grid, target: Point2, dist: f32) -> bool {
+        self.def.ranged.as_ref().map_or(false, |r| dist <= r.range) && grid.is_clear(self.obj.pos, target)
+    }
+    /// Decides what to do about the player at `target` and does it for `dt` seconds
+    ///
+    /// Returns what it did to the player, if anything
+    pub fn update(&mut self, grid: &Grid, target: Point2, dt: f32) -> Option<EnemyAttack> {
+        use self::EnemyState::*;
+        self.attack.update(dt);
+        self.shot.update(dt);
+        self.bark.update(dt);
+        // The wind-up is held on its last frame until the shot goes off
+        if self.anim.update(dt) && self.aim.is_none() {
+            // Back to walking once an attack has played
+            self.anim.play(ENEMY_WALK, true);
+        }
+        let dist = (target - self.obj.pos).norm();
+        self.state = match self.state {
+            // A shot being wound up goes off even if the player gets away
+            _ if self.aim.is_some() => Shoot,
+            _ if dist <= ATTACK_RANGE => Attack,
+            _ if self.can_shoot(grid, target, dist) => Shoot,
+            Idle if dist <= SIGHT_RANGE => Chase,
+            Chase | Attack | Shoot if dist <= GIVE_UP_RANGE => Chase,
+            _ => Idle,
+        };
+
+        match self.state {
+            Idle => {
+                self.obj.vel = Vector2::new(0., 0.);
+                self.path.clear();
+                if self.anim.sheet() == ENEMY_WALK {
+                    self.anim.restart();
+                }
+            }
+            Chase => self.chase(grid, target, dt),
+            Attack => {
+                self.obj.vel = Vector2::new(0., 0.);
+                self.obj.rotate_toward(target, ENEMY_TURN_SPEED * dt);
+                if self.attack.trigger() {
+                    self.anim.play(ENEMY_ATTACK, false);
+                    return Some(EnemyAttack::Melee(self.def.damage))
+                }
+            }
+            Shoot => {
+                self.obj.vel = Vector2::new(0., 0.);
+                return self.shoot(target, dt)
+            }
+        }
+        None
+    }
+    /// Winds up a shot at where the player is now, so they can dodge it by moving, then fires it
+    fn shoot(&mut self, target: Point2, dt: f32) -> Option<EnemyAttack> {
+        let ranged = self.def.ranged.as_ref()?;
+        match self.aim {
+            None => {
+                self.obj.rotate_toward(target, ENEMY_TURN_SPEED * dt);
+                if self.shot.trigger() {
+                    self.aim = Some(target);
+                    self.windup.restart();
+                    self.anim.play(ENEMY_WINDUP, false);
+                    return Some(EnemyAttack::Windup(ranged.windup_sound.clone()))
+                }
+            }
+            Some(aim) => {
+                self.obj.rotate_toward(aim, ENEMY_TURN_SPEED * dt);
+                if self.windup.update(dt) {
+                    self.aim = None;
+                    self.anim.play(ENEMY_ATTACK, false);
+                    let mut bullet = Bullet::with_trajectory(self.obj.pos, angle_from_vec(aim - self.obj.pos), ranged.trajectory);
+                    bullet.damage = ranged.damage;
+                    bullet.hostile = true;
+                    return Some(EnemyAttack::Shot(bullet, ranged.fire_sound.clone()))
+                }
+            }
+        }
+        None
+    }
+    fn chase(&mut self, grid: &Grid, target: Point2, dt: f32) {
+        self.repath.update(dt);
+        if self.repath.is_done() {
+            self.path = grid.find_path(Grid::snap(self.obj.pos), Grid::snap(target)).unwrap_or_default();
+            self.repath.restart();
+        }
+        // Skip the steps already reached
+        while let Some(&tile) = self.path.last() {
+            if (tile_center(tile) - self.obj.pos).norm() < self.def.speed * dt {
+                self.path.pop();
+            } else {
+                break
+            }
+        }
+        // Head straight for the player once in the same tile
+        let goal = self.path.last().copied().map_or(target, tile_center);
+        let dir = goal - self.obj.pos;
+        self.obj.vel = if dir.norm_squared() > 0. {
+            dir.normalize() * self.def.speed * grid.speed_at(self.obj.pos)
+        } else {
+            Vector2::new(0., 0.)
+        };
+        self.obj.slide(grid, dt);
+        self.obj.rotate_toward(goal, ENEMY_TURN_SPEED * dt);
+    }
+    #[cfg(not(feature = "headless"))]
+    pub fn draw(&self, ctx: &mut Context, assets: &Assets) -> GameResult<()> {
+        self.anim.draw(ctx, assets, self.obj.drawparams())
+    }
+}
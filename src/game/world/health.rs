@@ -0,0 +1,50 @@
+/// Share of incoming damage armour takes instead of health
+const ARMOUR_ABSORPTION: f32 = 2. / 3.;
+
+#[derive(Debug, Clone, Copy)]
+/// Health and armour of something that can be hurt
+pub struct Health {
+    pub hp: f32,
+    pub max_hp: f32,
+    pub armour: f32,
+    pub max_armour: f32,
+}
+
+impl Health {
+    /// Makes a full health and armour
+    pub fn new(max_hp: f32, max_armour: f32) -> Self {
+        Health {
+            hp: max_hp,
+            max_hp,
+            armour: max_armour,
+            max_armour,
+        }
+    }
+    /// Takes `dmg` damage, armour absorbing part of it for as long as it lasts
+    ///
+    /// Negative damage does nothing rather than healing past the maximum.
+    pub fn damage(&mut self, dmg: f32) {
+        let dmg = dmg.max(0.);
+        let absorbed = (dmg * ARMOUR_ABSORPTION).min(self.armour);
+        self.armour -= absorbed;
+        self.hp -= dmg - absorbed;
+    }
+    #[inline]
+    pub fn is_dead(&self) -> bool {
+        self.hp <= 0.
+    }
+    /// Health left from 0 to 1
+    #[inline]
+    pub fn hp_fraction(&self) -> f32 {
+        (self.hp / self.max_hp).max(0.)
+    }
+    /// Armour left from 0 to 1
+    #[inline]
+    pub fn armour_fraction(&self) -> f32 {
+        if self.max_armour > 0. {
+            self.armour / self.max_armour
+        } else {
+            0.
+        }
+    }
+}
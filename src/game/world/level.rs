@@ -0,0 +1,294 @@
+//! The binary level format
+//!
+//! All numbers are little endian:
+//!
+//! - `CFLV` and a version byte
+//! - width and height as `u16`s
+//! - since version 3, the number of materials used as a `u16`, then each material's name
+//!   as a `u16` length followed by UTF-8 bytes
+//! - a material byte for each tile, row by row, indexing the names since version 3
+//!   and the built-in materials before that
+//! - the player spawn point as two `f32`s
+//! - the clock's ticks as a `u64` and time as an `f32`
+//! - the number of signs as a `u16`, then for each sign its position as two `f32`s
+//!   and its text as a `u16` length followed by UTF-8 bytes
+//! - since version 2, the number of enemies as a `u16` and their positions as two `f32`s each,
+//!   each followed since version 4 by the name of its kind as a `u16` length and UTF-8 bytes
+//! - since version 5, the number of music tracks as a `u16`, then each track's name
+//!   as a `u16` length followed by UTF-8 bytes, then a byte that's 1 if they're shuffled
+//! - since version 6, the number of combat stems as a `u16`, then each stem's name
+//!   as a `u16` length followed by UTF-8 bytes
+//! - since version 7, the name of the ambience loop as a `u16` length followed by UTF-8 bytes,
+//!   empty for none
+//! - since version 8, a byte that's 1 if the level has an exit, then the exit's position as two `f32`s
+
+#[cfg(not(feature = "headless"))]
+use std::io::{Read, Write};
+#[cfg(not(feature = "headless"))]
+use std::path::Path;
+
+use crate::{
+    util::Point2,
+    io::snd::{Playlist, PlaylistMode},
+    game::enemies::Enemies,
+};
+use ggez::{GameResult, GameError};
+#[cfg(not(feature = "headless"))]
+use ggez::{Context, filesystem};
+
+use super::{World, Grid, Material, Player, Sign, Clock, Enemy};
+
+const MAGIC: &[u8; 4] = b"CFLV";
+const VERSION: u8 = 8;
+
+/// Path of the level file with the given name
+pub fn level_path(name: &str) -> String {
+    format!("/levels/{}.lvl", name)
+}
+
+fn bad_level<T>(msg: &str) -> GameResult<T> {
+    Err(GameError::ResourceLoadError(format!("Bad level: {}", msg)))
+}
+
+/// Reads values from the bytes of a level file
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> GameResult<&'a [u8]> {
+        if self.bytes.len() < n {
+            return bad_level("unexpected end of file")
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+    fn u8(&mut self) -> GameResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> GameResult<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+    fn u64(&mut self) -> GameResult<u64> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+    fn f32(&mut self) -> GameResult<f32> {
+        let b = self.take(4)?;
+        Ok(f32::from_bits(u32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+    }
+    fn point(&mut self) -> GameResult<Point2> {
+        Ok(Point2::new(self.f32()?, self.f32()?))
+    }
+    fn string(&mut self) -> GameResult<String> {
+        let len = self.u16()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).or_else(|_| bad_level("text isn't UTF-8"))
+    }
+}
+
+fn write_point(buf: &mut Vec<u8>, p: Point2) {
+    buf.extend_from_slice(&p.x.to_bits().to_le_bytes());
+    buf.extend_from_slice(&p.y.to_bits().to_le_bytes());
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    let bytes = s.as_bytes();
+    let len = bytes.len().min(u16::max_value() as usize);
+    buf.extend_from_slice(&(len as u16).to_le_bytes());
+    buf.extend_from_slice(&bytes[..len]);
+}
+
+impl World {
+    /// Encodes the world in the level format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(32 + self.grid.mats.len());
+        buf.extend_from_slice(MAGIC);
+        buf.push(VERSION);
+
+        buf.extend_from_slice(&self.grid.width().to_le_bytes());
+        buf.extend_from_slice(&self.grid.height().to_le_bytes());
+
+        // Only the materials used are named, so the level doesn't depend on what else is registered
+        let mut used: Vec<Material> = Vec::new();
+        let tiles: Vec<u8> = self.grid.mats.iter().map(|&m| {
+            match used.iter().position(|&u| u == m) {
+                Some(i) => i as u8,
+                None => {
+                    used.push(m);
+                    (used.len() - 1) as u8
+                }
+            }
+        }).collect();
+        buf.extend_from_slice(&(used.len() as u16).to_le_bytes());
+        for mat in used {
+            write_string(&mut buf, mat.name());
+        }
+        buf.extend(tiles);
+
+        write_point(&mut buf, self.spawn);
+        buf.extend_from_slice(&self.clock.ticks.to_le_bytes());
+        buf.extend_from_slice(&self.clock.time.to_bits().to_le_bytes());
+
+        buf.extend_from_slice(&(self.signs.len() as u16).to_le_bytes());
+        for sign in &self.signs {
+            write_point(&mut buf, sign.pos);
+            write_string(&mut buf, sign.text());
+        }
+
+        buf.extend_from_slice(&(self.enemies.len() as u16).to_le_bytes());
+        for enemy in &self.enemies {
+            write_point(&mut buf, enemy.obj.pos);
+            write_string(&mut buf, &enemy.kind);
+        }
+
+        buf.extend_from_slice(&(self.music.tracks.len() as u16).to_le_bytes());
+        for track in &self.music.tracks {
+            write_string(&mut buf, track);
+        }
+        buf.push((self.music.mode == PlaylistMode::Shuffle) as u8);
+        buf.extend_from_slice(&(self.music.combat.len() as u16).to_le_bytes());
+        for stem in &self.music.combat {
+            write_string(&mut buf, stem);
+        }
+        write_string(&mut buf, self.ambience.as_deref().unwrap_or(""));
+        match self.exit {
+            Some(exit) => {
+                buf.push(1);
+                write_point(&mut buf, exit);
+            }
+            None => buf.push(0),
+        }
+        buf
+    }
+    /// Decodes a world in the level format, giving the enemies the definitions of their kinds in `defs`
+    pub fn from_bytes(defs: &Enemies, bytes: &[u8]) -> GameResult<Self> {
+        let mut r = Reader { bytes };
+        if r.take(4)? != MAGIC {
+            return bad_level("not a level file")
+        }
+        let version = r.u8()?;
+        if version == 0 || version > VERSION {
+            return bad_level(&format!("unsupported version {}", version))
+        }
+
+        let width = r.u16()?;
+        let height = r.u16()?;
+        if width == 0 || height == 0 {
+            return bad_level("empty grid")
+        }
+        let palette = if version >= 3 {
+            let count = r.u16()?;
+            let mut palette = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let name = r.string()?;
+                // Materials missing from this game get the defaults rather than failing the whole level
+                let mat = Material::by_name(&name).or_else(|| {
+                    warn!("Level uses unknown material {}", name);
+                    Material::register(&name)
+                });
+                palette.push(mat.map_or_else(|| bad_level("too many materials"), Ok)?);
+            }
+            Some(palette)
+        } else {
+            None
+        };
+        let mats = r.take(width as usize * height as usize)?
+            .iter()
+            .map(|&n| match palette {
+                Some(ref palette) => palette.get(n as usize).copied(),
+                None => Material::from_builtin_id(n),
+            }.map_or_else(|| bad_level("unknown material"), Ok))
+            .collect::<GameResult<_>>()?;
+
+        let spawn = r.point()?;
+        let clock = Clock {
+            ticks: r.u64()?,
+            time: r.f32()?,
+        };
+
+        let sign_count = r.u16()?;
+        let mut signs = Vec::with_capacity(sign_count as usize);
+        for _ in 0..sign_count {
+            let pos = r.point()?;
+            signs.push(Sign::new(pos, &r.string()?));
+        }
+
+        let mut enemies = Vec::new();
+        if version >= 2 {
+            let enemy_count = r.u16()?;
+            enemies.reserve(enemy_count as usize);
+            for _ in 0..enemy_count {
+                let pos = r.point()?;
+                let kind = if version >= 4 { r.string()? } else { String::new() };
+                // Like materials, kinds missing from this game fall back to the built-in enemy
+                let enemy = match defs.get(&kind) {
+                    Some(def) => Enemy::with_def(pos, kind, def.clone()),
+                    None => {
+                        if !kind.is_empty() {
+                            warn!("Level uses unknown enemy {}", kind);
+                        }
+                        Enemy::new(pos)
+                    }
+                };
+                enemies.push(enemy);
+            }
+        }
+
+        let mut music = Playlist::default();
+        if version >= 5 {
+            let track_count = r.u16()?;
+            for _ in 0..track_count {
+                music.tracks.push(r.string()?);
+            }
+            if r.u8()? != 0 {
+                music.mode = PlaylistMode::Shuffle;
+            }
+        }
+        if version >= 6 {
+            let stem_count = r.u16()?;
+            for _ in 0..stem_count {
+                music.combat.push(r.string()?);
+            }
+        }
+        let ambience = if version >= 7 { Some(r.string()?).filter(|a| !a.is_empty()) } else { None };
+        let exit = if version >= 8 && r.u8()? != 0 { Some(r.point()?) } else { None };
+
+        let mut world = World::new(width, height);
+        world.grid = Grid::with_mats(width, mats);
+        world.spawn = spawn;
+        world.player = Player::new(spawn);
+        world.clock = clock;
+        world.signs = signs;
+        world.enemies = enemies;
+        world.music = music;
+        world.ambience = ambience;
+        world.exit = exit;
+        Ok(world)
+    }
+    /// Saves the world to a level file in the user directory
+    #[cfg(not(feature = "headless"))]
+    pub fn save<P: AsRef<Path>>(&self, ctx: &mut Context, path: P) -> GameResult<()> {
+        let mut file = filesystem::create(ctx, path)?;
+        file.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+    /// Loads a world from a level file
+    #[cfg(not(feature = "headless"))]
+    pub fn load<P: AsRef<Path>>(ctx: &mut Context, defs: &Enemies, path: P) -> GameResult<Self> {
+        let mut bytes = Vec::new();
+        filesystem::open(ctx, path)?.read_to_end(&mut bytes)?;
+        Self::from_bytes(defs, &bytes)
+    }
+    /// Loads the default level, or makes an empty world if there is none
+    #[cfg(not(feature = "headless"))]
+    pub fn load_default(ctx: &mut Context, defs: &Enemies) -> GameResult<Self> {
+        if filesystem::exists(ctx, level_path("default")) {
+            Self::load(ctx, defs, level_path("default"))
+        } else {
+            Ok(World::new(16, 16))
+        }
+    }
+}
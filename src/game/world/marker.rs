@@ -0,0 +1,48 @@
+use crate::{
+    util::Point2,
+    ext::Timer,
+};
+use ggez::graphics::Color;
+#[cfg(not(feature = "headless"))]
+use ggez::{Context, GameResult, graphics::{self, Mesh, DrawMode}};
+
+/// Seconds a marker stays in the world
+const MARKER_LIFETIME: f32 = 6.;
+const MARKER_COLOUR: Color = Color{r:1.,g:0.8,b:0.,a:1.};
+
+#[derive(Debug, Clone, Copy)]
+/// A temporary ping placed in the world to point something out
+pub struct Marker {
+    pub pos: Point2,
+    timer: Timer,
+}
+
+impl Marker {
+    pub fn new(pos: Point2) -> Self {
+        Marker {
+            pos,
+            timer: Timer::started(MARKER_LIFETIME),
+        }
+    }
+    #[inline]
+    pub fn colour(&self) -> Color {
+        MARKER_COLOUR
+    }
+    #[inline]
+    pub fn is_expired(&self) -> bool {
+        self.timer.is_done()
+    }
+    #[inline]
+    pub fn update(&mut self, dt: f32) {
+        self.timer.update(dt);
+    }
+    /// Draws a ring that shrinks in over and over
+    #[cfg(not(feature = "headless"))]
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        let pulse = (self.timer.left() * 2.).fract();
+        let ring = Mesh::new_circle(ctx, DrawMode::stroke(3.), self.pos, 8. + 16. * pulse, 0.5, MARKER_COLOUR)?;
+        let dot = Mesh::new_circle(ctx, DrawMode::fill(), self.pos, 5., 0.5, MARKER_COLOUR)?;
+        graphics::draw(ctx, &ring, (Point2::new(0., 0.),))?;
+        graphics::draw(ctx, &dot, (Point2::new(0., 0.),))
+    }
+}
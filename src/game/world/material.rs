@@ -1,44 +1,296 @@
-use crate::{
-    io::tex::Assets,
-    util::{Point2, Vector2},
-};
-use ggez::{graphics::{self, Image}, Context, GameResult};
+//! The material registry, filled from the files in `/materials`
+//!
+//! Every `<name>.png` or `<name>.toml` there is a material, so new tiles can be added without recompiling.
+//! The TOML file sets its properties, and anything left out is the default:
+//!
+//! ```toml
+//! solid = false
+//! speed = 1.0             # multiplies how fast things walk over it
+//! resource = "apples"     # optional, what harvesting it gives
+//! footstep = "step_grass" # optional sound played when walking over it
+//! frames = 1              # frames in the sprite, laid out left to right
+//! frame_time = 0.25       # seconds each frame is shown
+//! colour = "#6b8e23"      # optional, what it looks like on the minimap
+//! ```
+
+#[cfg(not(feature = "headless"))]
+use crate::io::tex::Assets;
+use ggez::{graphics::Color, GameResult, GameError};
+#[cfg(not(feature = "headless"))]
+use ggez::{graphics::Image, Context, filesystem};
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 use std::sync::RwLock;
-use std::fs::File;
+#[cfg(not(feature = "headless"))]
 use std::io::Read;
+#[cfg(not(feature = "headless"))]
 use std::cell::Ref;
+use toml::Value;
+
+/// The materials that come with the game, in the order of their ids in old levels
+const BUILTIN: &[&str] = &["apples", "grains", "lumber", "ore", "sheeps"];
+/// What materials without a colour of their own look like on the minimap
+const FLOOR_COLOUR: Color = Color{r: 0.6, g: 0.6, b: 0.6, a: 1.};
+const WALL_COLOUR: Color = Color{r: 0.25, g: 0.25, b: 0.25, a: 1.};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+/// A material in the registry, the default being the first built-in one
+pub struct Material(u8);
 
 #[derive(Debug)]
-pub struct Mat {
+struct Mat {
+    name: &'static str,
     spr: Box<str>,
+    props: MaterialProperties,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    mats: Vec<Mat>,
+    ids: HashMap<&'static str, Material>,
+}
+
+impl Registry {
+    fn with_builtins() -> Self {
+        let mut registry = Registry::default();
+        for name in BUILTIN {
+            registry.register(name);
+        }
+        registry
+    }
+    fn register(&mut self, name: &str) -> Option<Material> {
+        if let Some(&mat) = self.ids.get(name) {
+            return Some(mat)
+        }
+        if self.mats.len() > u8::max_value() as usize {
+            return None
+        }
+        let mat = Material(self.mats.len() as u8);
+        // Materials are never removed, so their names can live as long as the game
+        let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+        self.mats.push(Mat {
+            name,
+            spr: format!("materials/{}", name).into_boxed_str(),
+            props: MaterialProperties::default(),
+        });
+        self.ids.insert(name, mat);
+        Some(mat)
+    }
 }
 
 lazy_static! {
-    static ref MATS: RwLock<HashMap<String, Mat>> = {
-        RwLock::new(HashMap::with_capacity(10))
-    };
+    static ref MATS: RwLock<Registry> = RwLock::new(Registry::with_builtins());
 }
 
-fn ensure(mat: &str) {
-    if !MATS.read().unwrap().contains_key(mat) {
-        let mat_data = Mat { spr: format!("materials/{}", mat).into_boxed_str()};
+#[derive(Debug, Clone)]
+/// How a material behaves in the game
+pub struct MaterialProperties {
+    /// Whether things can't move through it
+    pub solid: bool,
+    /// Multiplies how fast things walk over it
+    pub speed: f32,
+    /// What harvesting it gives
+    pub resource: Option<String>,
+    /// Sound played when walking over it
+    pub footstep: Option<String>,
+    /// Frames in the sprite, more than one making the tile animated
+    pub frames: u16,
+    /// Seconds each frame is shown
+    pub frame_time: f32,
+    /// What it looks like on the minimap, grey if not set
+    pub colour: Option<Color>,
+}
 
-        MATS.write().unwrap().insert(mat.to_owned(), mat_data);
+impl Default for MaterialProperties {
+    fn default() -> Self {
+        MaterialProperties {
+            solid: false,
+            speed: 1.,
+            resource: None,
+            footstep: None,
+            frames: 1,
+            frame_time: 0.25,
+            colour: None,
+        }
     }
 }
 
-#[derive(Debug, Default)]
-struct MaterialProperties {
-    solid: bool,
+fn bad_material<T>(mat: &str, msg: &str) -> GameResult<T> {
+    Err(GameError::ResourceLoadError(format!("Bad material {}: {}", mat, msg)))
 }
 
-#[inline]
-pub fn get_img<'a>(ctx: &mut Context, assets: &'a Assets, mat: &str) -> Ref<'a, Image> {
-    ensure(mat);
+/// Parses a colour written like `#6b8e23`
+fn parse_colour(s: &str) -> Option<Color> {
+    if s.len() != 7 || !s.starts_with('#') {
+        return None
+    }
+    let rgb = u32::from_str_radix(&s[1..], 16).ok()?;
+    Some(Color::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+}
+
+impl MaterialProperties {
+    fn from_toml(mat: &str, src: &str) -> GameResult<Self> {
+        let value: Value = match src.parse() {
+            Ok(v) => v,
+            Err(e) => return bad_material(mat, &e.to_string()),
+        };
+        let string = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_owned);
+        let mut props = MaterialProperties::default();
+
+        if let Some(solid) = value.get("solid") {
+            props.solid = solid.as_bool().map_or_else(|| bad_material(mat, "solid should be true or false"), Ok)?;
+        }
+        match value.get("speed") {
+            Some(&Value::Float(n)) => props.speed = n as f32,
+            Some(&Value::Integer(n)) => props.speed = n as f32,
+            Some(_) => return bad_material(mat, "speed should be a number"),
+            None => (),
+        }
+        if props.speed < 0. {
+            return bad_material(mat, "speed can't be negative")
+        }
+        props.resource = string("resource");
+        props.footstep = string("footstep");
+        match value.get("frames") {
+            Some(&Value::Integer(n)) if n >= 1 && n <= i64::from(u16::max_value()) => props.frames = n as u16,
+            Some(_) => return bad_material(mat, "frames should be a positive integer"),
+            None => (),
+        }
+        match value.get("frame_time") {
+            Some(&Value::Float(n)) if n > 0. => props.frame_time = n as f32,
+            Some(&Value::Integer(n)) if n > 0 => props.frame_time = n as f32,
+            Some(_) => return bad_material(mat, "frame_time should be a positive number"),
+            None => (),
+        }
+        if let Some(colour) = value.get("colour") {
+            props.colour = Some(colour.as_str().and_then(parse_colour).map_or_else(|| bad_material(mat, "colour should be like \"#6b8e23\""), Ok)?);
+        }
+        Ok(props)
+    }
+}
+
+/// Registers every material in `/materials` and loads their properties
+///
+/// Materials without a TOML file get the default properties
+#[cfg(not(feature = "headless"))]
+pub fn load_materials(ctx: &mut Context) {
+    let mut names: Vec<String> = match filesystem::read_dir(ctx, "/materials") {
+        Ok(paths) => paths
+            .filter(|p| p.extension().map_or(false, |e| e == "toml" || e == "png"))
+            .filter_map(|p| p.file_stem()?.to_str().map(str::to_owned))
+            .filter(|name| name != "missing")
+            .collect(),
+        Err(e) => {
+            error!("Couldn't read /materials: {}", e);
+            return
+        }
+    };
+    // Sorted so the materials get the same ids every time
+    names.sort();
+    names.dedup();
 
-    assets.get_img(ctx, &MATS.read().unwrap()[mat].spr)
+    for name in names {
+        let mat = match Material::register(&name) {
+            Some(mat) => mat,
+            None => {
+                error!("Too many materials, skipping {}", name);
+                continue
+            }
+        };
+        let path = format!("/materials/{}.toml", name);
+        if !filesystem::exists(ctx, &path) {
+            continue
+        }
+        let mut src = String::new();
+        let props = filesystem::open(ctx, &path)
+            .and_then(|mut f| f.read_to_string(&mut src).map_err(GameError::from))
+            .and_then(|_| MaterialProperties::from_toml(&name, &src));
+        match props {
+            Ok(props) => mat.set_properties(props),
+            Err(e) => error!("Couldn't load material {}: {}", name, e),
+        }
+    }
 }
 
-const PALETTE: &[&str] = &["apples", "grains", "lumber", "ore", "sheeps"];
+impl Material {
+    /// Adds a material with the default properties to the registry, unless it's already there
+    ///
+    /// Returns `None` if the registry is full
+    pub fn register(name: &str) -> Option<Self> {
+        MATS.write().unwrap().register(name)
+    }
+    pub fn by_name(name: &str) -> Option<Self> {
+        MATS.read().unwrap().ids.get(name).copied()
+    }
+    /// The built-in material with the id it has in levels from before materials were named in them
+    pub fn from_builtin_id(id: u8) -> Option<Self> {
+        if (id as usize) < BUILTIN.len() {
+            Some(Material(id))
+        } else {
+            None
+        }
+    }
+    /// Every registered material, in the order they're shown in palettes
+    pub fn palette() -> Vec<Self> {
+        (0..MATS.read().unwrap().mats.len()).map(|i| Material(i as u8)).collect()
+    }
+    /// The name of the material, which its sprite and properties are named after
+    #[inline]
+    pub fn name(self) -> &'static str {
+        MATS.read().unwrap().mats[self.0 as usize].name
+    }
+    /// Path of the material's sprite
+    #[inline]
+    pub fn sprite(self) -> String {
+        MATS.read().unwrap().mats[self.0 as usize].spr.to_string()
+    }
+    /// Changes the properties of the material, like when they've been loaded
+    pub fn set_properties(self, props: MaterialProperties) {
+        MATS.write().unwrap().mats[self.0 as usize].props = props;
+    }
+    /// Looks at the properties of the material
+    #[inline]
+    pub fn with_properties<T, F: FnOnce(&MaterialProperties) -> T>(self, f: F) -> T {
+        f(&MATS.read().unwrap().mats[self.0 as usize].props)
+    }
+    /// Whether things can't move through this material
+    #[inline]
+    pub fn is_solid(self) -> bool {
+        self.with_properties(|p| p.solid)
+    }
+    /// Multiplies how fast things walk over this material
+    #[inline]
+    pub fn speed(self) -> f32 {
+        self.with_properties(|p| p.speed)
+    }
+    /// What harvesting this material gives
+    pub fn resource(self) -> Option<String> {
+        self.with_properties(|p| p.resource.clone())
+    }
+    /// Sound played when walking over this material
+    pub fn footstep(self) -> Option<String> {
+        self.with_properties(|p| p.footstep.clone())
+    }
+    /// The frame of an animated material to show `time` seconds into the game, and how many frames it has
+    pub fn frame_at(self, time: f32) -> (u16, u16) {
+        self.with_properties(|p| {
+            let frame = (time / p.frame_time) as u64 % u64::from(p.frames);
+            (frame as u16, p.frames)
+        })
+    }
+    #[inline]
+    pub fn is_animated(self) -> bool {
+        self.with_properties(|p| p.frames > 1)
+    }
+    /// What this material looks like on the minimap
+    pub fn colour(self) -> Color {
+        self.with_properties(|p| p.colour.unwrap_or(if p.solid { WALL_COLOUR } else { FLOOR_COLOUR }))
+    }
+}
+
+#[inline]
+#[cfg(not(feature = "headless"))]
+pub fn get_img<'a>(ctx: &mut Context, assets: &'a Assets, mat: Material) -> Ref<'a, Image> {
+    let spr = MATS.read().unwrap().mats[mat.0 as usize].spr.clone();
+    assets.get_img(ctx, &spr)
+}
@@ -0,0 +1,88 @@
+use crate::util::{Point2, Vector2, angle_to_vec, angle_from_vec, angle_diff};
+use crate::ext::FloatExt;
+#[cfg(not(feature = "headless"))]
+use crate::io::tex::Assets;
+#[cfg(not(feature = "headless"))]
+use ggez::{Context, GameResult, graphics::{self, DrawParam}};
+
+use super::Grid;
+
+#[derive(Debug, Clone, Copy)]
+/// Something with a position, rotation and velocity in the world, colliding as a circle
+pub struct Object {
+    pub pos: Point2,
+    /// Direction it's facing in radians, 0 being along the x-axis
+    pub rot: f32,
+    pub vel: Vector2,
+    /// Radius of the circle it takes up, 0 for things that are just a point
+    pub radius: f32,
+}
+
+impl Object {
+    #[inline]
+    pub fn new(pos: Point2, radius: f32) -> Self {
+        Object {
+            pos,
+            rot: 0.,
+            vel: Vector2::new(0., 0.),
+            radius,
+        }
+    }
+    /// Unit vector in the direction it's facing
+    #[inline]
+    pub fn dir(&self) -> Vector2 {
+        angle_to_vec(self.rot)
+    }
+    /// Turns to face `p`
+    pub fn look_at(&mut self, p: Point2) {
+        let dist = p - self.pos;
+        if dist.norm_squared() > 0. {
+            self.rot = angle_from_vec(dist);
+        }
+    }
+    /// Turns towards `p` by no more than `max_turn` radians
+    pub fn rotate_toward(&mut self, p: Point2, max_turn: f32) {
+        let dist = p - self.pos;
+        if dist.norm_squared() <= 0. {
+            return
+        }
+        let diff = angle_diff(self.rot, angle_from_vec(dist));
+        self.rot += diff.limit(-max_turn, max_turn);
+    }
+    /// Moves towards `p` by no more than `max_dist`, returning whether it got there
+    pub fn move_toward(&mut self, p: Point2, max_dist: f32) -> bool {
+        let dist = p - self.pos;
+        let len = dist.norm();
+        if len <= max_dist {
+            self.pos = p;
+            true
+        } else {
+            self.pos += dist * (max_dist / len);
+            false
+        }
+    }
+    /// Moves by its velocity over `dt` seconds
+    #[inline]
+    pub fn step(&mut self, dt: f32) {
+        self.pos += self.vel * dt;
+    }
+    /// Moves by its velocity over `dt` seconds, sliding along solid tiles instead of going into them
+    #[inline]
+    pub fn slide(&mut self, grid: &Grid, dt: f32) {
+        self.pos = grid.slide(self.pos, self.vel * dt, self.radius);
+    }
+    /// Parameters for drawing a sprite centered on the object and rotated like it
+    #[cfg(not(feature = "headless"))]
+    pub fn drawparams(&self) -> DrawParam {
+        DrawParam::new()
+            .dest(self.pos)
+            .rotation(self.rot)
+            .offset(Point2::new(0.5, 0.5))
+    }
+    /// Draws a sprite centered on the object and rotated like it
+    #[cfg(not(feature = "headless"))]
+    pub fn draw(&self, ctx: &mut Context, assets: &Assets, sprite: &str) -> GameResult<()> {
+        let img = assets.get_img(ctx, sprite);
+        graphics::draw(ctx, &*img, self.drawparams())
+    }
+}
@@ -0,0 +1,97 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::util::Point2;
+use super::Grid;
+
+/// A tile position in the grid
+pub type Tile = (u16, u16);
+
+/// The center of a tile in world coordinates
+#[inline]
+pub fn tile_center((x, y): Tile) -> Point2 {
+    Point2::new(f32::from(x) * 32. + 16., f32::from(y) * 32. + 16.)
+}
+
+/// Cost of a straight step, diagonal steps costing about √2 times as much
+const STRAIGHT: u32 = 10;
+const DIAGONAL: u32 = 14;
+/// Most tiles looked at before giving up on finding a path
+const MAX_VISITED: usize = 4096;
+
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (1, 0), (-1, 0), (0, 1), (0, -1),
+    (1, 1), (1, -1), (-1, 1), (-1, -1),
+];
+
+/// Cost of the shortest path between two tiles if nothing was in the way
+fn estimate(a: Tile, b: Tile) -> u32 {
+    let dx = (i32::from(a.0) - i32::from(b.0)).unsigned_abs();
+    let dy = (i32::from(a.1) - i32::from(b.1)).unsigned_abs();
+    STRAIGHT * (dx + dy) - (2 * STRAIGHT - DIAGONAL) * dx.min(dy)
+}
+
+impl Grid {
+    /// Whether the tile at signed coordinates is solid, negative ones being outside the grid
+    fn is_solid_at(&self, x: i32, y: i32) -> bool {
+        x < 0 || y < 0 || self.is_solid(x as u16, y as u16)
+    }
+    /// Finds the shortest path from `start` to `goal` around solid tiles using A*
+    ///
+    /// The path leaves out `start` and goes from `goal` back to the first step,
+    /// so the next step can be popped off the end
+    pub fn find_path(&self, start: Tile, goal: Tile) -> Option<Vec<Tile>> {
+        if self.is_solid(goal.0, goal.1) {
+            return None
+        }
+        let mut open = BinaryHeap::new();
+        let mut came_from = HashMap::new();
+        let mut costs = HashMap::new();
+        costs.insert(start, 0);
+        open.push(Reverse((estimate(start, goal), start)));
+
+        let mut visited = 0;
+        while let Some(Reverse((_, cur))) = open.pop() {
+            if cur == goal {
+                let mut path = vec![goal];
+                let mut tile = goal;
+                while let Some(&prev) = came_from.get(&tile) {
+                    if prev == start {
+                        break
+                    }
+                    path.push(prev);
+                    tile = prev;
+                }
+                if start == goal {
+                    path.clear();
+                }
+                return Some(path)
+            }
+            visited += 1;
+            if visited > MAX_VISITED {
+                return None
+            }
+
+            let cost = costs[&cur];
+            let (x, y) = (i32::from(cur.0), i32::from(cur.1));
+            for &(dx, dy) in &NEIGHBOURS {
+                if self.is_solid_at(x + dx, y + dy) {
+                    continue
+                }
+                let diagonal = dx != 0 && dy != 0;
+                // Don't cut across the corners of solid tiles
+                if diagonal && (self.is_solid_at(x + dx, y) || self.is_solid_at(x, y + dy)) {
+                    continue
+                }
+                let next = ((x + dx) as u16, (y + dy) as u16);
+                let next_cost = cost + if diagonal { DIAGONAL } else { STRAIGHT };
+                if costs.get(&next).map_or(true, |&c| next_cost < c) {
+                    costs.insert(next, next_cost);
+                    came_from.insert(next, cur);
+                    open.push(Reverse((next_cost + estimate(next, goal), next)));
+                }
+            }
+        }
+        None
+    }
+}
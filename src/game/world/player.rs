@@ -0,0 +1,98 @@
+use crate::{
+    util::{Point2, Vector2},
+    io::anim::{Animation, Sheet},
+    game::weapons::Weapon,
+};
+#[cfg(not(feature = "headless"))]
+use crate::io::tex::Assets;
+#[cfg(not(feature = "headless"))]
+use ggez::{Context, GameResult};
+
+use super::{Object, Grid, Health, Shield, Emoting, BLOCK_SPEED};
+
+/// Pixels per second the player walks
+const PLAYER_SPEED: f32 = 160.;
+pub const PLAYER_RADIUS: f32 = 12.;
+const PLAYER_HP: f32 = 100.;
+const PLAYER_ARMOUR: f32 = 100.;
+/// The walk cycle, its first frame being the player standing still
+pub const PLAYER_WALK: Sheet = Sheet { sprite: "common/player_walk", frames: 4, frame_time: 0.12 };
+
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub obj: Object,
+    pub health: Health,
+    pub weapon: Option<Weapon>,
+    pub shield: Shield,
+    /// The emote being shown above the player, if any
+    pub emote: Option<Emoting>,
+    anim: Animation,
+}
+
+impl Player {
+    #[inline]
+    pub fn new(pos: Point2) -> Self {
+        Player {
+            obj: Object::new(pos, PLAYER_RADIUS),
+            health: Health::new(PLAYER_HP, PLAYER_ARMOUR),
+            weapon: None,
+            shield: Shield::new(),
+            emote: None,
+            anim: Animation::looping(PLAYER_WALK),
+        }
+    }
+    /// Walks in the direction of `input` for `dt` seconds without walking into solid tiles
+    pub fn update(&mut self, grid: &Grid, input: Vector2, dt: f32) {
+        self.shield.update(dt);
+        let mut speed = grid.speed_at(self.obj.pos);
+        if self.shield.is_raised() {
+            speed *= BLOCK_SPEED;
+        }
+        self.obj.vel = if input.norm_squared() > 0. && !self.shield.is_staggered() {
+            input.normalize() * PLAYER_SPEED * speed
+        } else {
+            Vector2::new(0., 0.)
+        };
+        self.obj.slide(grid, dt);
+        if self.obj.vel.norm_squared() > 0. {
+            // Take shorter steps when walking slower
+            self.anim.update(speed * dt);
+        } else {
+            self.anim.restart();
+        }
+
+        if let Some(ref mut weapon) = self.weapon {
+            weapon.update(dt);
+        }
+        if self.emote.as_mut().map_or(false, |e| e.update(dt)) {
+            self.emote = None;
+        }
+    }
+    /// Takes a hit from something at `from`, which the shield might block some of
+    pub fn take_hit(&mut self, dmg: f32, from: Point2) {
+        let dmg = self.shield.block(dmg, self.obj.pos, self.obj.rot, from);
+        self.health.damage(dmg);
+    }
+    /// Whether the player can use their weapon
+    #[inline]
+    pub fn can_shoot(&self) -> bool {
+        !self.shield.is_raised() && !self.shield.is_staggered()
+    }
+    #[cfg(not(feature = "headless"))]
+    pub fn draw(&self, ctx: &mut Context, assets: &Assets) -> GameResult<()> {
+        self.anim.draw(ctx, assets, self.obj.drawparams())?;
+        if let Some(ref weapon) = self.weapon {
+            // Held out in front, pointing where the player is aiming
+            let held = Object { pos: self.obj.pos + self.obj.dir() * (PLAYER_RADIUS / 2.), .. self.obj };
+            held.draw(ctx, assets, &weapon.def.sprite)?;
+        }
+        if self.shield.is_raised() {
+            let shield = Object { pos: self.obj.pos + self.obj.dir() * PLAYER_RADIUS, .. self.obj };
+            shield.draw(ctx, assets, "common/shield")?;
+        }
+        if let Some(ref emote) = self.emote {
+            emote.draw(ctx, assets, self.obj.pos)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use crate::util::{Point2, Vector2};
+use super::{Grid, Material};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Where a ray hit a solid tile
+pub struct RayHit {
+    /// The tile that was hit, which is outside the grid if the ray left it
+    pub tile: (i32, i32),
+    /// The material of the tile, `None` outside the grid
+    pub mat: Option<Material>,
+    /// Where on the edge of the tile the ray hit
+    pub point: Point2,
+    /// Unit vector pointing out of the side of the tile that was hit
+    pub normal: Vector2,
+    /// How far the ray went before hitting
+    pub dist: f32,
+}
+
+/// How far along a ray the first line between tiles on one axis is crossed
+/// and how far it is between the lines after that
+fn first_crossing(origin: f32, dir: f32, tile: i32) -> (f32, f32) {
+    if dir > 0. {
+        ((tile as f32 * 32. + 32. - origin) / dir, 32. / dir)
+    } else if dir < 0. {
+        ((tile as f32 * 32. - origin) / dir, -32. / dir)
+    } else {
+        (f32::INFINITY, f32::INFINITY)
+    }
+}
+
+impl Grid {
+    /// Whether the tile at signed coordinates is solid, tiles outside the grid all being solid
+    fn is_solid_i32(&self, x: i32, y: i32) -> bool {
+        x < 0 || y < 0 || x > i32::from(u16::MAX) || y > i32::from(u16::MAX) || self.is_solid(x as u16, y as u16)
+    }
+    /// Finds the first solid tile a ray from `origin` going in `dir` hits within `max_dist` pixels
+    ///
+    /// Walks through the tiles the ray passes one at a time, so it never misses the corner of one.
+    /// A ray starting inside a solid tile hits it right away, facing back the way the ray went.
+    pub fn raycast(&self, origin: Point2, dir: Vector2, max_dist: f32) -> Option<RayHit> {
+        let tile = |x: i32, y: i32, point: Point2, normal: Vector2, dist: f32| {
+            let mat = if x < 0 || y < 0 { None } else { self.get(x as u16, y as u16) };
+            RayHit { tile: (x, y), mat, point, normal, dist }
+        };
+        let (mut x, mut y) = ((origin.x / 32.).floor() as i32, (origin.y / 32.).floor() as i32);
+        let dir = dir.try_normalize(f32::EPSILON);
+        if self.is_solid_i32(x, y) {
+            return Some(tile(x, y, origin, -dir.unwrap_or_else(Vector2::zeros), 0.))
+        }
+        let dir = dir?;
+
+        let step_x = if dir.x < 0. { -1 } else { 1 };
+        let step_y = if dir.y < 0. { -1 } else { 1 };
+        let (mut next_x, delta_x) = first_crossing(origin.x, dir.x, x);
+        let (mut next_y, delta_y) = first_crossing(origin.y, dir.y, y);
+        loop {
+            let (dist, normal) = if next_x < next_y {
+                x += step_x;
+                next_x += delta_x;
+                (next_x - delta_x, Vector2::new(-step_x as f32, 0.))
+            } else {
+                y += step_y;
+                next_y += delta_y;
+                (next_y - delta_y, Vector2::new(0., -step_y as f32))
+            };
+            if dist > max_dist {
+                return None
+            }
+            // Tiles outside the grid are solid, so this ends even if `max_dist` is infinite
+            if self.is_solid_i32(x, y) {
+                return Some(tile(x, y, origin + dir * dist, normal, dist))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::world::MaterialProperties;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    /// An 8×8 grid with solid tiles at `walls`
+    fn grid(walls: &[(u16, u16)]) -> Grid {
+        let wall = Material::register("test_wall").unwrap();
+        wall.set_properties(MaterialProperties{solid: true, .. Default::default()});
+        let mut grid = Grid::new(8, 8);
+        for &(x, y) in walls {
+            grid.insert(x, y, wall);
+        }
+        grid
+    }
+
+    #[test]
+    fn axis_aligned_hit() {
+        let grid = grid(&[(5, 2)]);
+        let hit = grid.raycast(Point2::new(48., 80.), Vector2::new(1., 0.), 1000.).unwrap();
+        assert_eq!(hit.tile, (5, 2));
+        assert_eq!(hit.mat, Material::by_name("test_wall"));
+        assert!(close(hit.point.x, 160.) && close(hit.point.y, 80.));
+        assert_eq!(hit.normal, Vector2::new(-1., 0.));
+        assert!(close(hit.dist, 112.));
+    }
+    #[test]
+    fn diagonal_hits_corner_tile() {
+        // Going through the corners of tiles exactly, it still gets to the solid one on the diagonal
+        let grid = grid(&[(3, 3)]);
+        let hit = grid.raycast(Point2::new(16., 16.), Vector2::new(1., 1.), 1000.).unwrap();
+        assert_eq!(hit.tile, (3, 3));
+        assert!(close(hit.point.x, 96.) && close(hit.point.y, 96.));
+        assert!(close(hit.dist, 80. * 2f32.sqrt()));
+    }
+    #[test]
+    fn stops_at_max_dist() {
+        let grid = grid(&[(5, 2)]);
+        assert_eq!(grid.raycast(Point2::new(48., 80.), Vector2::new(1., 0.), 100.), None);
+        assert!(grid.raycast(Point2::new(48., 80.), Vector2::new(1., 0.), 120.).is_some());
+    }
+    #[test]
+    fn starts_inside_solid() {
+        let grid = grid(&[(5, 2)]);
+        let origin = Point2::new(170., 80.);
+        let hit = grid.raycast(origin, Vector2::new(0., 1.), 1000.).unwrap();
+        assert_eq!(hit.tile, (5, 2));
+        assert_eq!(hit.point, origin);
+        assert_eq!(hit.normal, Vector2::new(0., -1.));
+        assert_eq!(hit.dist, 0.);
+    }
+    #[test]
+    fn leaves_grid() {
+        let grid = grid(&[]);
+        let hit = grid.raycast(Point2::new(48., 80.), Vector2::new(-1., 0.), 1000.).unwrap();
+        assert_eq!(hit.tile, (-1, 2));
+        assert_eq!(hit.mat, None);
+        assert!(close(hit.point.x, 0.) && close(hit.point.y, 80.));
+        assert_eq!(hit.normal, Vector2::new(1., 0.));
+        assert!(close(hit.dist, 48.));
+
+        let hit = grid.raycast(Point2::new(48., 80.), Vector2::new(0., 1.), 1000.).unwrap();
+        assert_eq!(hit.tile, (1, 8));
+        assert_eq!(hit.mat, None);
+    }
+}
@@ -0,0 +1,105 @@
+use std::f32::consts::PI;
+
+use crate::{
+    util::{Point2, angle_from_vec, angle_diff},
+    ext::Timer,
+};
+
+/// Durability of an unharmed shield
+const SHIELD_DURABILITY: f32 = 100.;
+/// How far to either side of straight ahead the shield covers, in radians
+const SHIELD_HALF_ARC: f32 = PI / 3.;
+/// Share of the damage from the front the shield stops
+const BLOCK_FRACTION: f32 = 0.8;
+/// Durability lost each second the shield is held up
+const HOLD_DRAIN: f32 = 8.;
+/// Durability regained each second once the shield has been lowered for a while
+const RECOVERY_RATE: f32 = 25.;
+/// Seconds after lowering the shield before it starts recovering
+const RECOVERY_DELAY: f32 = 0.8;
+/// Seconds the holder is staggered when the shield breaks
+const STAGGER_TIME: f32 = 1.2;
+/// Multiplies how fast the holder walks while blocking
+pub const BLOCK_SPEED: f32 = 0.5;
+
+#[derive(Debug, Clone)]
+/// A shield that can be held up to block hits from the front
+///
+/// Holding it up and blocking wear it down, and when it breaks the holder is staggered
+pub struct Shield {
+    pub durability: f32,
+    raised: bool,
+    recovery: Timer,
+    stagger: Timer,
+}
+
+impl Default for Shield {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shield {
+    pub fn new() -> Self {
+        Shield {
+            durability: SHIELD_DURABILITY,
+            raised: false,
+            recovery: Timer::new(RECOVERY_DELAY),
+            stagger: Timer::new(STAGGER_TIME),
+        }
+    }
+    #[inline]
+    pub fn is_raised(&self) -> bool {
+        self.raised
+    }
+    /// Whether the holder is reeling from the shield breaking and can't do anything
+    #[inline]
+    pub fn is_staggered(&self) -> bool {
+        !self.stagger.is_done()
+    }
+    /// Durability left from 0 to 1
+    #[inline]
+    pub fn fraction(&self) -> f32 {
+        self.durability / SHIELD_DURABILITY
+    }
+    /// Holds the shield up or lowers it, it can't be raised while staggered
+    pub fn raise(&mut self, raised: bool) {
+        self.raised = raised && !self.is_staggered() && self.durability > 0.;
+    }
+    fn shatter(&mut self) {
+        self.durability = 0.;
+        self.raised = false;
+        self.stagger.restart();
+        self.recovery.restart();
+    }
+    pub fn update(&mut self, dt: f32) {
+        self.stagger.update(dt);
+        if self.raised {
+            self.durability -= HOLD_DRAIN * dt;
+            self.recovery.restart();
+            if self.durability <= 0. {
+                self.shatter();
+            }
+        } else {
+            self.recovery.update(dt);
+            if self.recovery.is_done() {
+                self.durability = (self.durability + RECOVERY_RATE * dt).min(SHIELD_DURABILITY);
+            }
+        }
+    }
+    /// Blocks a hit from `from` if it comes from the front of the holder at `pos` facing `rot`
+    ///
+    /// Returns the damage that gets through
+    pub fn block(&mut self, dmg: f32, pos: Point2, rot: f32, from: Point2) -> f32 {
+        if !self.raised || angle_diff(rot, angle_from_vec(from - pos)).abs() > SHIELD_HALF_ARC {
+            return dmg
+        }
+        // What the shield can't take goes through
+        let blocked = (dmg * BLOCK_FRACTION).min(self.durability);
+        self.durability -= blocked;
+        if self.durability <= 0. {
+            self.shatter();
+        }
+        dmg - blocked
+    }
+}
@@ -0,0 +1,53 @@
+#[cfg(not(feature = "headless"))]
+use std::cell::RefCell;
+
+use crate::util::Point2;
+#[cfg(not(feature = "headless"))]
+use crate::{util::Vector2, io::tex::Assets};
+#[cfg(not(feature = "headless"))]
+use ggez::{Context, GameResult, graphics::{self, Text, Color, DrawParam}};
+
+/// Within this distance from the center of the view signs are fully visible
+const FADE_START: f32 = 200.;
+/// Beyond this distance from the center of the view signs are invisible
+const FADE_END: f32 = 400.;
+
+#[derive(Debug, Clone)]
+/// Text placed in the world
+pub struct Sign {
+    pub pos: Point2,
+    text: String,
+    /// The text to draw, made the first time it's drawn so signs can be made without the font
+    #[cfg(not(feature = "headless"))]
+    label: RefCell<Option<Text>>,
+}
+
+impl Sign {
+    pub fn new(pos: Point2, text: &str) -> Self {
+        Sign {
+            pos,
+            text: text.to_owned(),
+            #[cfg(not(feature = "headless"))]
+            label: RefCell::new(None),
+        }
+    }
+    #[inline]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    /// Draws the sign centered on its position, fading out the further it is from `view_center`
+    #[cfg(not(feature = "headless"))]
+    pub fn draw(&self, ctx: &mut Context, assets: &Assets, view_center: Point2) -> GameResult<()> {
+        let dist = (self.pos - view_center).norm();
+        let alpha = 1. - ((dist - FADE_START) / (FADE_END - FADE_START)).clamp(0., 1.);
+        if alpha <= 0. {
+            return Ok(())
+        }
+
+        let mut label = self.label.borrow_mut();
+        let label = label.get_or_insert_with(|| assets.raw_text_with(&self.text, 16.));
+        let (w, h) = label.dimensions(ctx);
+        let dest = self.pos - Vector2::new(w as f32 / 2., h as f32 / 2.);
+        graphics::draw(ctx, &*label, DrawParam::new().dest(dest).color(Color{a: alpha, ..graphics::WHITE}))
+    }
+}
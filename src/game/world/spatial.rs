@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use crate::util::Point2;
+use ggez::graphics::Rect;
+
+/// Side length in pixels of the cells points are sorted into
+const CELL_SIZE: f32 = 64.;
+
+fn cell(p: Point2) -> (i32, i32) {
+    ((p.x / CELL_SIZE).floor() as i32, (p.y / CELL_SIZE).floor() as i32)
+}
+
+#[derive(Debug, Default, Clone)]
+/// Points sorted into square cells by where they are, so finding the ones in an area
+/// only has to look at the cells it covers instead of every point
+///
+/// Points are known by their index in whatever they were built from, so the hash has to be
+/// built again when that changes.
+pub struct SpatialHash {
+    cells: HashMap<(i32, i32), Vec<(usize, Point2)>>,
+}
+
+impl SpatialHash {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sorts `points` into cells again, forgetting the ones from before
+    pub fn rebuild(&mut self, points: impl Iterator<Item=Point2>) {
+        // Keeps the cells' memory around, since it's done every tick
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+        for (i, p) in points.enumerate() {
+            self.cells.entry(cell(p)).or_default().push((i, p));
+        }
+    }
+    /// Indices of the points in the cells from `min` to `max` for which `f` is true, lowest first
+    fn query(&self, min: Point2, max: Point2, f: impl Fn(Point2) -> bool) -> Vec<usize> {
+        let ((x0, y0), (x1, y1)) = (cell(min), cell(max));
+        let mut found = Vec::new();
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                if let Some(points) = self.cells.get(&(x, y)) {
+                    found.extend(points.iter().filter(|&&(_, p)| f(p)).map(|&(i, _)| i));
+                }
+            }
+        }
+        // Lowest first, so it's the same as going through the points in order
+        found.sort_unstable();
+        found
+    }
+    /// Indices of the points closer than `radius` to `pos`, lowest first
+    pub fn query_radius(&self, pos: Point2, radius: f32) -> Vec<usize> {
+        let min = Point2::new(pos.x - radius, pos.y - radius);
+        let max = Point2::new(pos.x + radius, pos.y + radius);
+        self.query(min, max, |p| (p - pos).norm() < radius)
+    }
+    /// Indices of the points inside `rect`, lowest first
+    pub fn query_rect(&self, rect: Rect) -> Vec<usize> {
+        let min = Point2::new(rect.x, rect.y);
+        let max = Point2::new(rect.x + rect.w, rect.y + rect.h);
+        self.query(min, max, |p| rect.contains(p))
+    }
+}
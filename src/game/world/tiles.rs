@@ -0,0 +1,90 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use crate::{
+    util::{Point2, rect_intersection},
+    io::{tex::Assets, anim::frame_src},
+};
+use ggez::{
+    Context, GameResult,
+    graphics::{self, Rect, DrawParam, spritebatch::SpriteBatch},
+};
+
+use super::{Grid, Material, get_img};
+
+/// Side length in tiles of the squares the grid is batched in,
+/// so chunks that can't be seen aren't drawn
+const CHUNK_SIZE: u16 = 16;
+
+#[derive(Debug)]
+struct Chunk {
+    /// Where the chunk is in the world
+    rect: Rect,
+    /// One batch for each material in the chunk
+    batches: Vec<SpriteBatch>,
+    /// Tiles of animated materials, which are drawn one at a time since their frame changes
+    animated: Vec<(Material, Point2)>,
+}
+
+#[derive(Debug, Default)]
+/// Sprite batches of the tiles in a grid, rebuilt only when the grid changes
+pub struct TileBatches {
+    chunks: RefCell<Vec<Chunk>>,
+    /// Revision of the grid the chunks were built from
+    revision: Cell<Option<u32>>,
+}
+
+impl TileBatches {
+    /// Draws the chunks of the grid that can be seen in `view`, animated tiles as they look `time` seconds in
+    pub fn draw(&self, ctx: &mut Context, assets: &Assets, grid: &Grid, view: Rect, time: f32) -> GameResult<()> {
+        if self.revision.get() != Some(grid.revision()) {
+            self.rebuild(ctx, assets, grid);
+            self.revision.set(Some(grid.revision()));
+        }
+        for chunk in self.chunks.borrow().iter().filter(|c| rect_intersection(c.rect, view).is_some()) {
+            for batch in &chunk.batches {
+                graphics::draw(ctx, batch, DrawParam::new())?;
+            }
+            for &(mat, pos) in &chunk.animated {
+                let (frame, frames) = mat.frame_at(time);
+                let img = get_img(ctx, assets, mat);
+                graphics::draw(ctx, &*img, DrawParam::new().dest(pos).src(frame_src(frames, frame)))?;
+            }
+        }
+        Ok(())
+    }
+    fn rebuild(&self, ctx: &mut Context, assets: &Assets, grid: &Grid) {
+        let mut chunks = self.chunks.borrow_mut();
+        chunks.clear();
+
+        let (width, height) = (grid.width(), grid.height());
+        for cy in (0..height).step_by(CHUNK_SIZE as usize) {
+            for cx in (0..width).step_by(CHUNK_SIZE as usize) {
+                let mut batches: HashMap<Material, SpriteBatch> = HashMap::new();
+                let mut animated = Vec::new();
+                for y in cy..cy.saturating_add(CHUNK_SIZE).min(height) {
+                    for x in cx..cx.saturating_add(CHUNK_SIZE).min(width) {
+                        let mat = match grid.get(x, y) {
+                            Some(mat) => mat,
+                            None => continue,
+                        };
+                        let pos = Point2::new(f32::from(x) * 32., f32::from(y) * 32.);
+                        if mat.is_animated() {
+                            animated.push((mat, pos));
+                            continue
+                        }
+                        let batch = batches.entry(mat)
+                            .or_insert_with(|| SpriteBatch::new(get_img(ctx, assets, mat).clone()));
+                        batch.add(DrawParam::new().dest(pos));
+                    }
+                }
+                let rect = Rect::new(f32::from(cx) * 32., f32::from(cy) * 32., f32::from(CHUNK_SIZE) * 32., f32::from(CHUNK_SIZE) * 32.);
+                chunks.push(Chunk {
+                    rect,
+                    batches: batches.into_values().collect(),
+                    animated,
+                });
+            }
+        }
+    }
+}
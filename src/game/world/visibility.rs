@@ -0,0 +1,145 @@
+#[cfg(not(feature = "headless"))]
+use std::cell::RefCell;
+
+use crate::util::Point2;
+use super::{Grid, Tile, tile_center};
+#[cfg(not(feature = "headless"))]
+use crate::util::Vector2;
+#[cfg(not(feature = "headless"))]
+use ggez::{
+    Context, GameResult,
+    graphics::{self, DrawParam, Image, FilterMode},
+};
+
+/// How far in tiles the player can see
+const SIGHT_RANGE: u16 = 12;
+/// How dark tiles that have been seen before but can't be seen now are, from 0 to 255
+const DIMMED: u8 = 144;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Seen {
+    Unexplored,
+    /// Seen before, but not in sight now
+    Explored,
+    Visible,
+}
+
+#[derive(Debug, Default)]
+/// Which tiles the player has seen and which it can see right now
+pub struct Visibility {
+    width: u16,
+    height: u16,
+    tiles: Vec<Seen>,
+    /// The tile the player was on and the revision of the grid when the tiles were last looked at
+    seen_from: Option<(Tile, u32)>,
+    /// Tiles that have been explored, which only ever goes up
+    explored: usize,
+    /// A pixel of darkness for each tile, made again when the tiles change
+    #[cfg(not(feature = "headless"))]
+    shade: RefCell<Option<Image>>,
+}
+
+/// Whether nothing solid is in the way of seeing `tile` from `from`, the tile itself being allowed to be solid
+/// so walls can be seen
+fn in_sight(grid: &Grid, from: Point2, (x, y): Tile) -> bool {
+    let line = tile_center((x, y)) - from;
+    grid.raycast(from, line, line.norm()).map_or(true, |hit| hit.tile == (i32::from(x), i32::from(y)))
+}
+
+impl Visibility {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Looks around from `eye`, if it's moved to another tile or the grid has changed since the last time
+    pub fn update(&mut self, grid: &Grid, eye: Point2) {
+        let (width, height) = (grid.width(), grid.height());
+        if (width, height) != (self.width, self.height) {
+            // Whatever was explored doesn't line up with the tiles anymore
+            *self = Visibility {
+                width,
+                height,
+                tiles: vec![Seen::Unexplored; width as usize * height as usize],
+                .. Visibility::default()
+            };
+        }
+        let (ex, ey) = Grid::snap(eye);
+        if self.seen_from == Some(((ex, ey), grid.revision())) {
+            return
+        }
+        self.seen_from = Some(((ex, ey), grid.revision()));
+
+        for seen in &mut self.tiles {
+            if *seen == Seen::Visible {
+                *seen = Seen::Explored;
+            }
+        }
+        let range = f32::from(SIGHT_RANGE) * 32.;
+        for y in ey.saturating_sub(SIGHT_RANGE)..ey.saturating_add(SIGHT_RANGE + 1).min(height) {
+            for x in ex.saturating_sub(SIGHT_RANGE)..ex.saturating_add(SIGHT_RANGE + 1).min(width) {
+                if (tile_center((x, y)) - eye).norm() > range || !in_sight(grid, eye, (x, y)) {
+                    continue
+                }
+                let seen = &mut self.tiles[x as usize + y as usize * width as usize];
+                if *seen == Seen::Unexplored {
+                    self.explored += 1;
+                }
+                *seen = Seen::Visible;
+            }
+        }
+        #[cfg(not(feature = "headless"))]
+        {
+            *self.shade.borrow_mut() = None;
+        }
+    }
+    fn get(&self, x: u16, y: u16) -> Seen {
+        if x < self.width && y < self.height {
+            self.tiles[x as usize + y as usize * self.width as usize]
+        } else {
+            Seen::Unexplored
+        }
+    }
+    /// Whether the tile has ever been seen
+    #[inline]
+    pub fn is_explored(&self, x: u16, y: u16) -> bool {
+        self.get(x, y) != Seen::Unexplored
+    }
+    /// Whether the tile a point is on can be seen right now
+    pub fn can_see(&self, p: Point2) -> bool {
+        let (x, y) = Grid::snap(p);
+        self.get(x, y) == Seen::Visible
+    }
+    /// How many tiles have been explored, which goes up whenever more are
+    #[inline]
+    pub fn explored(&self) -> usize {
+        self.explored
+    }
+    /// Darkens the tiles that can't be seen over what's already been drawn
+    #[cfg(not(feature = "headless"))]
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        if self.tiles.is_empty() {
+            return Ok(())
+        }
+        let mut shade = self.shade.borrow_mut();
+        if shade.is_none() {
+            let mut rgba = Vec::with_capacity(self.tiles.len() * 4);
+            for &seen in &self.tiles {
+                let alpha = match seen {
+                    Seen::Unexplored => 255,
+                    Seen::Explored => DIMMED,
+                    Seen::Visible => 0,
+                };
+                rgba.extend_from_slice(&[0, 0, 0, alpha]);
+            }
+            let mut img = Image::from_rgba8(ctx, self.width, self.height, &rgba)?;
+            // Blending between tiles gives the edge of the sight soft edges
+            img.set_filter(FilterMode::Linear);
+            *shade = Some(img);
+        }
+        match *shade {
+            // Tiles are 32 pixels wide and the image has one pixel for each
+            Some(ref img) => graphics::draw(ctx, img, DrawParam::new().scale(Vector2::new(32., 32.))),
+            None => Ok(()),
+        }
+    }
+}
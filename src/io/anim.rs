@@ -0,0 +1,102 @@
+//! Frame animations played from spritesheets
+//!
+//! A spritesheet is one image with all the frames of an animation laid out from left to right
+
+use ggez::{
+    Context, GameResult,
+    graphics::{self, Rect, DrawParam},
+};
+
+use super::tex::Assets;
+
+/// The part of a spritesheet with `frames` frames that frame number `frame` is in,
+/// as a fraction of the image like `DrawParam::src` wants
+#[inline]
+pub fn frame_src(frames: u16, frame: u16) -> Rect {
+    let w = 1. / f32::from(frames.max(1));
+    Rect::new(f32::from(frame) * w, 0., w, 1.)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// A spritesheet and how fast to play it
+pub struct Sheet {
+    pub sprite: &'static str,
+    pub frames: u16,
+    /// Seconds each frame is shown
+    pub frame_time: f32,
+}
+
+#[derive(Debug, Clone)]
+/// A spritesheet being played, either looping or once
+///
+/// Advanced by game time in `update`, so it runs in step with the simulation
+pub struct Animation {
+    sheet: Sheet,
+    looping: bool,
+    /// Seconds since the animation started
+    elapsed: f32,
+}
+
+impl Animation {
+    /// Makes an animation that starts over when it gets to the end
+    pub fn looping(sheet: Sheet) -> Self {
+        Animation {
+            sheet,
+            looping: true,
+            elapsed: 0.,
+        }
+    }
+    /// Makes an animation that stops at its last frame
+    pub fn once(sheet: Sheet) -> Self {
+        Animation {
+            looping: false,
+            .. Self::looping(sheet)
+        }
+    }
+    #[inline]
+    pub fn sheet(&self) -> Sheet {
+        self.sheet
+    }
+    /// Switches to another animation, unless it's already playing it
+    pub fn play(&mut self, sheet: Sheet, looping: bool) {
+        if self.sheet != sheet || self.looping != looping {
+            *self = Animation { sheet, looping, elapsed: 0. };
+        }
+    }
+    /// Goes back to the first frame
+    #[inline]
+    pub fn restart(&mut self) {
+        self.elapsed = 0.;
+    }
+    #[inline]
+    fn duration(&self) -> f32 {
+        f32::from(self.sheet.frames) * self.sheet.frame_time
+    }
+    /// Whether an animation that doesn't loop has got to its end
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        !self.looping && self.elapsed >= self.duration()
+    }
+    /// Advances the animation by `dt` seconds
+    ///
+    /// Returns `true` in the update an animation that doesn't loop finishes,
+    /// so whoever plays it can react to that, like a `Timer`
+    pub fn update(&mut self, dt: f32) -> bool {
+        let was_done = self.is_done();
+        self.elapsed += dt;
+        if self.looping {
+            self.elapsed %= self.duration().max(std::f32::EPSILON);
+        }
+        !was_done && self.is_done()
+    }
+    /// The frame being shown
+    pub fn frame(&self) -> u16 {
+        let frame = (self.elapsed / self.sheet.frame_time) as u16;
+        frame.min(self.sheet.frames.saturating_sub(1))
+    }
+    /// Draws the current frame
+    pub fn draw(&self, ctx: &mut Context, assets: &Assets, params: DrawParam) -> GameResult<()> {
+        let img = assets.get_img(ctx, self.sheet.sprite);
+        graphics::draw(ctx, &*img, params.src(frame_src(self.sheet.frames, self.frame())))
+    }
+}
@@ -1,16 +1,21 @@
-use crate::util::Point2;
+use crate::util::{Point2, Vector2};
+use crate::ext::{Tween, ease};
 use super::tex::{Assets, PosText};
 
 use ggez::{GameResult, Context};
 use ggez::graphics::{Drawable, Color, Rect, Mesh, DrawMode, DrawParam};
 use ggez::nalgebra::coordinates::XY;
 
+/// How much bigger a button gets when hovered
+const HOVER_GROWTH: f32 = 0.08;
+
 pub struct Button<T> {
     width: f32,
     height: f32,
     pub callback: T,
     text: PosText,
-    mesh: Mesh
+    mesh: Mesh,
+    hover: Tween,
 }
 
 impl<T> Button<T> {
@@ -24,12 +29,24 @@ impl<T> Button<T> {
             callback,
             width: rect.w,
             height: rect.h,
+            hover: Tween::new(0., 0.12, ease::quad_out),
         })
     }
+    /// Animates the hover effect, `dt` being the seconds passed since last update
+    pub fn update(&mut self, dt: f32, mouse: Point2) {
+        self.hover.set_target(if self.in_bounds(mouse) { 1. } else { 0. });
+        self.hover.update(dt);
+    }
     pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
-        self.mesh.draw(ctx, DrawParam::new())?;
+        // Scale around the center of the button
+        let scale = 1. + HOVER_GROWTH * self.hover.value();
+        let dest = self.text.pos * (1. - scale);
+        self.mesh.draw(ctx, DrawParam::new().dest(dest).scale(Vector2::new(scale, scale)))?;
         self.text.draw_center(ctx)
     }
+    pub fn set_text(&mut self, text: &str) {
+        self.text.text.fragments_mut()[0] = text.into();
+    }
     pub fn in_bounds(&self, p: Point2) -> bool {
         let XY{x, y} = *self.text.pos;
         let (w, h) = (self.width / 2., self.height / 2.);
@@ -0,0 +1,102 @@
+use std::io::{Read, Write};
+use std::path::Path;
+
+use ggez::{Context, GameResult, conf, filesystem, graphics::{self, Rect}};
+use toml::Value;
+
+use super::save::profile_path;
+
+/// What the config is saved as in each profile
+const CONFIG_FILE: &str = "config.toml";
+
+/// Window sizes that can be picked in the options
+pub const RESOLUTIONS: &[(f32, f32)] = &[(1152., 648.), (1280., 720.), (1600., 900.), (1920., 1080.)];
+
+#[derive(Debug, Clone, PartialEq)]
+/// Settings changed in the options menu
+pub struct Config {
+    pub width: f32,
+    pub height: f32,
+    pub fullscreen: bool,
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            width: 1152.,
+            height: 648.,
+            fullscreen: false,
+            master_volume: 1.,
+            music_volume: 1.,
+            sfx_volume: 1.,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config of `profile`, using the default for anything missing or invalid
+    pub fn load(ctx: &mut Context, profile: &str) -> Self {
+        let mut config = Config::default();
+        let path = profile_path(profile, CONFIG_FILE);
+        if !filesystem::exists(ctx, &path) {
+            return config
+        }
+        let mut src = String::new();
+        if let Err(e) = filesystem::open(ctx, &path).and_then(|mut f| f.read_to_string(&mut src).map_err(Into::into)) {
+            warn!("Couldn't read {}: {}", path, e);
+            return config
+        }
+        let value: Value = match src.parse() {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Bad config {}: {}", path, e);
+                return config
+            }
+        };
+
+        let number = |key: &str, default: f32| match value.get(key) {
+            Some(&Value::Float(n)) => n as f32,
+            Some(&Value::Integer(n)) => n as f32,
+            _ => default,
+        };
+        config.width = number("width", config.width).max(320.);
+        config.height = number("height", config.height).max(240.);
+        config.fullscreen = value.get("fullscreen").and_then(Value::as_bool).unwrap_or(config.fullscreen);
+        config.master_volume = number("master_volume", config.master_volume).clamp(0., 1.);
+        config.music_volume = number("music_volume", config.music_volume).clamp(0., 1.);
+        config.sfx_volume = number("sfx_volume", config.sfx_volume).clamp(0., 1.);
+        config
+    }
+    pub fn save(&self, ctx: &mut Context, profile: &str) -> GameResult<()> {
+        let path = profile_path(profile, CONFIG_FILE);
+        if let Some(dir) = Path::new(&path).parent() {
+            filesystem::create_dir(ctx, dir)?;
+        }
+        let mut file = filesystem::create(ctx, path)?;
+        writeln!(file, "width = {}", self.width)?;
+        writeln!(file, "height = {}", self.height)?;
+        writeln!(file, "fullscreen = {}", self.fullscreen)?;
+        writeln!(file, "master_volume = {:.2}", self.master_volume)?;
+        writeln!(file, "music_volume = {:.2}", self.music_volume)?;
+        writeln!(file, "sfx_volume = {:.2}", self.sfx_volume)?;
+        Ok(())
+    }
+    pub fn window_mode(&self) -> conf::WindowMode {
+        let fullscreen_type = if self.fullscreen {
+            conf::FullscreenType::Desktop
+        } else {
+            conf::FullscreenType::Windowed
+        };
+        conf::WindowMode::default()
+            .dimensions(self.width, self.height)
+            .fullscreen_type(fullscreen_type)
+    }
+    /// Changes the window to match the config
+    pub fn apply(&self, ctx: &mut Context) -> GameResult<()> {
+        graphics::set_mode(ctx, self.window_mode())?;
+        graphics::set_screen_coordinates(ctx, Rect::new(0., 0., self.width, self.height))
+    }
+}
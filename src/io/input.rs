@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+
+use ggez::{
+    Context,
+    event::{KeyCode, MouseButton},
+    input::{keyboard, mouse},
+};
+
+use crate::util::Point2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A physical key or mouse button
+pub enum Input {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+/// Names of the keys that can be bound
+const KEYS: &[(&str, KeyCode)] = &[
+    ("a", KeyCode::A), ("b", KeyCode::B), ("c", KeyCode::C), ("d", KeyCode::D),
+    ("e", KeyCode::E), ("f", KeyCode::F), ("g", KeyCode::G), ("h", KeyCode::H),
+    ("i", KeyCode::I), ("j", KeyCode::J), ("k", KeyCode::K), ("l", KeyCode::L),
+    ("m", KeyCode::M), ("n", KeyCode::N), ("o", KeyCode::O), ("p", KeyCode::P),
+    ("q", KeyCode::Q), ("r", KeyCode::R), ("s", KeyCode::S), ("t", KeyCode::T),
+    ("u", KeyCode::U), ("v", KeyCode::V), ("w", KeyCode::W), ("x", KeyCode::X),
+    ("y", KeyCode::Y), ("z", KeyCode::Z),
+    ("0", KeyCode::Key0), ("1", KeyCode::Key1), ("2", KeyCode::Key2), ("3", KeyCode::Key3),
+    ("4", KeyCode::Key4), ("5", KeyCode::Key5), ("6", KeyCode::Key6), ("7", KeyCode::Key7),
+    ("8", KeyCode::Key8), ("9", KeyCode::Key9),
+    ("f1", KeyCode::F1), ("f2", KeyCode::F2), ("f3", KeyCode::F3), ("f4", KeyCode::F4),
+    ("f5", KeyCode::F5), ("f6", KeyCode::F6), ("f7", KeyCode::F7), ("f8", KeyCode::F8),
+    ("f9", KeyCode::F9), ("f10", KeyCode::F10), ("f11", KeyCode::F11), ("f12", KeyCode::F12),
+    ("up", KeyCode::Up), ("down", KeyCode::Down), ("left", KeyCode::Left), ("right", KeyCode::Right),
+    ("escape", KeyCode::Escape), ("space", KeyCode::Space), ("tab", KeyCode::Tab),
+    ("enter", KeyCode::Return), ("backspace", KeyCode::Back),
+    ("insert", KeyCode::Insert), ("delete", KeyCode::Delete), ("home", KeyCode::Home), ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp), ("pagedown", KeyCode::PageDown),
+    ("lshift", KeyCode::LShift), ("rshift", KeyCode::RShift),
+    ("lctrl", KeyCode::LControl), ("rctrl", KeyCode::RControl),
+    ("lalt", KeyCode::LAlt), ("ralt", KeyCode::RAlt),
+    ("grave", KeyCode::Grave), ("minus", KeyCode::Minus), ("equals", KeyCode::Equals),
+    ("comma", KeyCode::Comma), ("period", KeyCode::Period), ("slash", KeyCode::Slash),
+    ("backslash", KeyCode::Backslash), ("semicolon", KeyCode::Semicolon), ("apostrophe", KeyCode::Apostrophe),
+    ("lbracket", KeyCode::LBracket), ("rbracket", KeyCode::RBracket),
+];
+
+/// Names of the mouse buttons that can be bound
+const BUTTONS: &[(&str, MouseButton)] = &[
+    ("mouse1", MouseButton::Left),
+    ("mouse2", MouseButton::Right),
+    ("mouse3", MouseButton::Middle),
+];
+
+impl Input {
+    /// Parses the name of a key or mouse button, like `w` or `mouse1`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        KEYS.iter().find(|&&(n, _)| n == name).map(|&(_, k)| Input::Key(k))
+            .or_else(|| BUTTONS.iter().find(|&&(n, _)| n == name).map(|&(_, b)| Input::Mouse(b)))
+    }
+    /// Names of every key and mouse button that can be bound
+    pub fn names() -> impl Iterator<Item=&'static str> {
+        KEYS.iter().map(|&(n, _)| n).chain(BUTTONS.iter().map(|&(n, _)| n))
+    }
+    /// Whether the key or button is held down right now
+    pub fn is_pressed(self, ctx: &Context) -> bool {
+        match self {
+            Input::Key(k) => keyboard::is_key_pressed(ctx, k),
+            Input::Mouse(b) => mouse::button_pressed(ctx, b),
+        }
+    }
+}
+
+impl Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Input::Key(k) => KEYS.iter().find(|&&(_, key)| key == k).map(|&(n, _)| n),
+            Input::Mouse(b) => BUTTONS.iter().find(|&&(_, btn)| btn == b).map(|&(n, _)| n),
+        };
+        match name {
+            Some(name) => name.fmt(f),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// Something the player can do, which keys and mouse buttons are bound to
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Shoot,
+    Block,
+    Reload,
+    Mark,
+    /// Held to open the command wheel for the companion
+    Command,
+    /// Held to open the emote wheel
+    Emote,
+    Pause,
+    Console,
+    /// Plays the level over from the start
+    Restart,
+    /// Shows or hides the minimap
+    Minimap,
+}
+
+const ACTIONS: &[(&str, Action)] = &[
+    ("move_up", Action::MoveUp),
+    ("move_down", Action::MoveDown),
+    ("move_left", Action::MoveLeft),
+    ("move_right", Action::MoveRight),
+    ("shoot", Action::Shoot),
+    ("block", Action::Block),
+    ("reload", Action::Reload),
+    ("mark", Action::Mark),
+    ("command", Action::Command),
+    ("emote", Action::Emote),
+    ("pause", Action::Pause),
+    ("console", Action::Console),
+    ("restart", Action::Restart),
+    ("minimap", Action::Minimap),
+];
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Self> {
+        ACTIONS.iter().find(|&&(n, _)| n == name).map(|&(_, a)| a)
+    }
+    pub fn name(self) -> &'static str {
+        ACTIONS.iter().find(|&&(_, a)| a == self).map(|&(n, _)| n).unwrap()
+    }
+    /// Names of every action
+    pub fn names() -> impl Iterator<Item=&'static str> {
+        ACTIONS.iter().map(|&(n, _)| n)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// A set of actions, kept as bits so it's cheap to copy and write down
+pub struct ActionSet(u16);
+
+impl ActionSet {
+    #[inline]
+    pub fn from_bits(bits: u16) -> Self {
+        ActionSet(bits)
+    }
+    #[inline]
+    pub fn bits(self) -> u16 {
+        self.0
+    }
+    #[inline]
+    pub fn insert(&mut self, action: Action) {
+        self.0 |= 1 << action as u16;
+    }
+    #[inline]
+    pub fn contains(self, action: Action) -> bool {
+        self.0 & 1 << action as u16 != 0
+    }
+    pub fn iter(self) -> impl Iterator<Item=Action> {
+        ACTIONS.iter().map(|&(_, a)| a).filter(move |&a| self.contains(a))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// What the player did during one tick of the game, which is all it takes to do it again
+pub struct TickInput {
+    /// Actions held down
+    pub held: ActionSet,
+    /// Actions pressed since the tick before
+    pub pressed: ActionSet,
+    /// Actions let go of since the tick before
+    pub released: ActionSet,
+    /// Where the crosshair points in the world
+    pub aim: Point2,
+}
+
+impl Default for TickInput {
+    fn default() -> Self {
+        TickInput {
+            held: ActionSet::default(),
+            pressed: ActionSet::default(),
+            released: ActionSet::default(),
+            aim: Point2::new(0., 0.),
+        }
+    }
+}
+
+impl TickInput {
+    /// -1 if the `neg` action is held, 1 if `pos` is held and 0 if both or neither are
+    pub fn axis(&self, neg: Action, pos: Action) -> f32 {
+        f32::from(self.held.contains(pos) as i8 - self.held.contains(neg) as i8)
+    }
+}
+
+/// The bindings used when nothing has been changed
+const DEFAULT_BINDS: &[(&str, Action)] = &[
+    ("w", Action::MoveUp),
+    ("up", Action::MoveUp),
+    ("s", Action::MoveDown),
+    ("down", Action::MoveDown),
+    ("a", Action::MoveLeft),
+    ("left", Action::MoveLeft),
+    ("d", Action::MoveRight),
+    ("right", Action::MoveRight),
+    ("mouse1", Action::Shoot),
+    ("mouse2", Action::Block),
+    ("r", Action::Reload),
+    ("mouse3", Action::Mark),
+    ("q", Action::Command),
+    ("e", Action::Emote),
+    ("escape", Action::Pause),
+    ("grave", Action::Console),
+    ("f5", Action::Restart),
+    ("m", Action::Minimap),
+];
+
+/// The key that opens the console when nothing is bound to it, so it can't be locked out
+pub const CONSOLE_FALLBACK: Input = Input::Key(KeyCode::Grave);
+
+#[derive(Debug, Clone, PartialEq)]
+/// Which action each key and mouse button does
+///
+/// An action can have several inputs bound to it, but each input only does one action
+pub struct InputMap {
+    binds: HashMap<Input, Action>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let binds = DEFAULT_BINDS.iter()
+            .map(|&(name, action)| (Input::from_name(name).unwrap(), action))
+            .collect();
+        InputMap { binds }
+    }
+}
+
+impl InputMap {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// The action an input is bound to
+    #[inline]
+    pub fn action(&self, input: Input) -> Option<Action> {
+        self.binds.get(&input).copied()
+    }
+    /// Whether the input is bound to the action
+    ///
+    /// If nothing is bound to the console, its default key opens it no matter what that's bound to.
+    #[inline]
+    pub fn is(&self, input: Input, action: Action) -> bool {
+        self.action(input) == Some(action)
+            || action == Action::Console && input == CONSOLE_FALLBACK && !self.is_bound(Action::Console)
+    }
+    /// Whether any input is bound to the action
+    #[inline]
+    pub fn is_bound(&self, action: Action) -> bool {
+        self.binds.values().any(|&a| a == action)
+    }
+    pub fn bind(&mut self, input: Input, action: Action) {
+        self.binds.insert(input, action);
+    }
+    pub fn unbind(&mut self, input: Input) -> Option<Action> {
+        self.binds.remove(&input)
+    }
+    /// Removes every binding to an action
+    pub fn unbind_action(&mut self, action: Action) {
+        self.binds.retain(|_, &mut a| a != action);
+    }
+    pub fn unbind_all(&mut self) {
+        self.binds.clear();
+    }
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+    /// All bindings, sorted by action
+    pub fn binds(&self) -> Vec<(Input, Action)> {
+        let mut binds: Vec<_> = self.binds.iter().map(|(&i, &a)| (i, a)).collect();
+        binds.sort_by_key(|&(i, a)| (a, i.to_string()));
+        binds
+    }
+    /// Whether any input bound to the action is held down
+    pub fn is_down(&self, ctx: &Context, action: Action) -> bool {
+        self.binds.iter().any(|(&i, &a)| a == action && i.is_pressed(ctx))
+    }
+    /// Every action that has an input bound to it held down
+    pub fn held(&self, ctx: &Context) -> ActionSet {
+        let mut held = ActionSet::default();
+        for &(_, action) in ACTIONS {
+            if self.is_down(ctx, action) {
+                held.insert(action);
+            }
+        }
+        held
+    }
+    /// Writes the bindings as a script of `bind` commands
+    ///
+    /// Nothing is written if they're the defaults, so changing a default reaches existing configs
+    pub fn write_config<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        if *self == Self::default() {
+            return Ok(())
+        }
+        writeln!(w, "unbindall")?;
+        for (input, action) in self.binds() {
+            writeln!(w, "bind {} {}", input, action.name())?;
+        }
+        Ok(())
+    }
+}
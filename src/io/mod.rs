@@ -1,3 +1,10 @@
 pub mod tex;
 pub mod snd;
 pub mod btn;
+pub mod anim;
+pub mod wheel;
+pub mod notify;
+pub mod subtitle;
+pub mod save;
+pub mod config;
+pub mod input;
@@ -0,0 +1,63 @@
+use crate::util::Point2;
+use crate::ext::{Tween, ease};
+
+use ggez::{GameResult, Context};
+use ggez::graphics::{self, Text, DrawParam};
+
+/// Seconds a notification stays on screen
+const LIFETIME: f32 = 3.;
+/// Seconds it takes to slide in or out
+const SLIDE_TIME: f32 = 0.25;
+const MARGIN: f32 = 8.;
+
+#[derive(Debug)]
+struct Notification {
+    text: Text,
+    slide: Tween,
+    time_left: f32,
+}
+
+#[derive(Debug, Default)]
+/// Short messages that slide in from the right edge of the screen
+pub struct Notifications {
+    list: Vec<Notification>,
+}
+
+impl Notifications {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(&mut self, text: Text) {
+        let mut slide = Tween::new(0., SLIDE_TIME, ease::back_out);
+        slide.set_target(1.);
+
+        self.list.push(Notification {
+            text,
+            slide,
+            time_left: LIFETIME,
+        });
+    }
+    /// Advances the animations by `dt` seconds
+    pub fn update(&mut self, dt: f32) {
+        for n in &mut self.list {
+            n.time_left -= dt;
+            if n.time_left <= SLIDE_TIME {
+                n.slide.set_target(0.);
+            }
+            n.slide.update(dt);
+        }
+        self.list.retain(|n| n.time_left > 0.);
+    }
+    pub fn draw(&self, ctx: &mut Context, screen_width: f32) -> GameResult<()> {
+        let mut y = MARGIN;
+        for n in &self.list {
+            let (w, h) = n.text.dimensions(ctx);
+            let x = screen_width - (w as f32 + MARGIN) * n.slide.value();
+
+            graphics::draw(ctx, &n.text, DrawParam::new().dest(Point2::new(x, y)))?;
+            y += h as f32 + MARGIN / 2.;
+        }
+        Ok(())
+    }
+}
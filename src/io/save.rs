@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use ggez::{Context, filesystem};
+
+#[derive(Debug, Clone)]
+/// The contents of a save along with when it was last changed
+pub struct SaveData {
+    pub bytes: Vec<u8>,
+    pub modified: SystemTime,
+}
+
+/// A remote place saves can be synchronised with (e.g. a platform's cloud storage)
+pub trait SyncBackend {
+    fn name(&self) -> &str;
+    /// Gets the remote copy of a save, if there is one
+    fn pull(&mut self, name: &str) -> io::Result<Option<SaveData>>;
+    /// Replaces the remote copy of a save
+    fn push(&mut self, name: &str, data: &SaveData) -> io::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How the local copy of a save relates to the remote one
+pub enum SyncState {
+    /// There's no sync backend
+    LocalOnly,
+    InSync,
+    LocalNewer,
+    RemoteNewer,
+    /// Both copies changed since they were last synchronised
+    Conflict,
+}
+
+/// Name of the profile used when none is picked
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Where the saves of a profile are kept
+///
+/// The default profile keeps the saves from before there were profiles
+fn save_dir(ctx: &Context, profile: &str) -> PathBuf {
+    let data = filesystem::user_data_dir(ctx);
+    if profile == DEFAULT_PROFILE {
+        data.join("saves")
+    } else {
+        data.join("profiles").join(profile).join("saves")
+    }
+}
+
+/// Names of all profiles that have been used
+pub fn list_profiles(ctx: &Context) -> io::Result<Vec<String>> {
+    let dir = filesystem::user_data_dir(ctx).join("profiles");
+    let mut names = vec![DEFAULT_PROFILE.to_owned()];
+    if dir.exists() {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                if let Ok(name) = entry.file_name().into_string() {
+                    names.push(name);
+                }
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// Where `file` of a profile is kept in the game's filesystem
+///
+/// Like the saves, the default profile keeps its files where they were before there were profiles
+pub fn profile_path(profile: &str, file: &str) -> String {
+    if profile == DEFAULT_PROFILE {
+        format!("/{}", file)
+    } else {
+        format!("/profiles/{}/{}", profile, file)
+    }
+}
+
+/// Save storage in the user's data directory, optionally kept in sync with a `SyncBackend`
+///
+/// Each local profile has its own saves so people sharing a computer don't overwrite each other's progress
+pub struct Saves {
+    profile: String,
+    dir: PathBuf,
+    backend: Option<Box<dyn SyncBackend>>,
+    /// When each save was last known to be the same locally and remotely
+    synced: HashMap<String, SystemTime>,
+}
+
+impl Saves {
+    pub fn new(ctx: &Context, profile: &str) -> Self {
+        Saves {
+            profile: profile.to_owned(),
+            dir: save_dir(ctx, profile),
+            backend: None,
+            synced: HashMap::new(),
+        }
+    }
+    #[inline]
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+    /// Switches to the saves of another profile, keeping the sync backend
+    pub fn set_profile(&mut self, ctx: &Context, profile: &str) -> io::Result<()> {
+        let dir = save_dir(ctx, profile);
+        fs::create_dir_all(&dir)?;
+        self.dir = dir;
+        self.profile = profile.to_owned();
+        self.synced.clear();
+        Ok(())
+    }
+    pub fn set_backend(&mut self, backend: Box<dyn SyncBackend>) {
+        self.backend = Some(backend);
+        self.synced.clear();
+    }
+    #[inline]
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+    /// Name of a save on the sync backend, which is shared between profiles
+    fn remote_name(&self, name: &str) -> String {
+        if self.profile == DEFAULT_PROFILE {
+            name.to_owned()
+        } else {
+            format!("{}/{}", self.profile, name)
+        }
+    }
+    fn read_local(&self, name: &str) -> io::Result<Option<SaveData>> {
+        let path = self.path(name);
+        if !path.exists() {
+            return Ok(None)
+        }
+        Ok(Some(SaveData {
+            modified: fs::metadata(&path)?.modified()?,
+            bytes: fs::read(path)?,
+        }))
+    }
+    fn write_local(&self, name: &str, bytes: &[u8]) -> io::Result<SystemTime> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(name);
+        fs::write(&path, bytes)?;
+        fs::metadata(path)?.modified()
+    }
+    /// Names of all local saves
+    pub fn list(&self) -> io::Result<Vec<String>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new())
+        }
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            if let Ok(name) = entry?.file_name().into_string() {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+    fn state_of(&self, name: &str, local: Option<&SaveData>, remote: Option<&SaveData>) -> SyncState {
+        use self::SyncState::*;
+        let base = self.synced.get(name);
+        match (local, remote) {
+            (_, _) if self.backend.is_none() => LocalOnly,
+            (None, None) => InSync,
+            (Some(_), None) => LocalNewer,
+            (None, Some(_)) => RemoteNewer,
+            (Some(l), Some(r)) if l.modified == r.modified => InSync,
+            (Some(l), Some(r)) => match base {
+                Some(&b) if l.modified > b && r.modified > b => Conflict,
+                None if l.bytes != r.bytes => Conflict,
+                _ if l.modified > r.modified => LocalNewer,
+                _ => RemoteNewer,
+            }
+        }
+    }
+    /// Compares the local save with the remote one
+    pub fn check(&mut self, name: &str) -> io::Result<SyncState> {
+        let local = self.read_local(name)?;
+        let remote_name = self.remote_name(name);
+        let remote = match self.backend {
+            Some(ref mut b) => b.pull(&remote_name)?,
+            None => None,
+        };
+        Ok(self.state_of(name, local.as_ref(), remote.as_ref()))
+    }
+    /// Reads a save, taking the remote copy if it is newer
+    ///
+    /// Fails with `io::ErrorKind::Other` if the copies conflict, which has to be settled with `resolve` first
+    pub fn read(&mut self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        match self.check(name)? {
+            SyncState::Conflict => Err(io::Error::other(format!("save {} has conflicting copies", name))),
+            SyncState::RemoteNewer => {
+                self.resolve(name, false)?;
+                Ok(self.read_local(name)?.map(|d| d.bytes))
+            }
+            _ => Ok(self.read_local(name)?.map(|d| d.bytes)),
+        }
+    }
+    /// Writes a save locally and pushes it to the backend unless the remote copy changed in the meantime
+    pub fn write(&mut self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        let state = self.check(name)?;
+        let modified = self.write_local(name, bytes)?;
+
+        match state {
+            SyncState::Conflict | SyncState::RemoteNewer => {
+                warn!("Remote copy of save {} changed, not overwriting it", name);
+                Ok(())
+            }
+            _ => self.push(name, SaveData{bytes: bytes.to_vec(), modified}),
+        }
+    }
+    fn push(&mut self, name: &str, data: SaveData) -> io::Result<()> {
+        let remote_name = self.remote_name(name);
+        if let Some(ref mut b) = self.backend {
+            b.push(&remote_name, &data)?;
+            self.synced.insert(name.to_owned(), data.modified);
+        }
+        Ok(())
+    }
+    /// Settles a difference between the local and remote copy by keeping one of them
+    pub fn resolve(&mut self, name: &str, keep_local: bool) -> io::Result<()> {
+        if keep_local {
+            if let Some(local) = self.read_local(name)? {
+                self.push(name, local)?;
+            }
+        } else {
+            let remote_name = self.remote_name(name);
+            let remote = match self.backend {
+                Some(ref mut b) => b.pull(&remote_name)?,
+                None => None,
+            };
+            if let Some(remote) = remote {
+                self.write_local(name, &remote.bytes)?;
+                // The local file gets a new modification time, so match the remote one to it
+                let local = self.read_local(name)?.expect("just written");
+                self.push(name, local)?;
+            }
+        }
+        Ok(())
+    }
+    /// Name of the sync backend if there is one
+    pub fn backend_name(&self) -> Option<&str> {
+        self.backend.as_ref().map(|b| b.name())
+    }
+}
@@ -1,22 +1,340 @@
-use std::collections::HashMap;
+//! Music and sound effects
+//!
+//! The sounds are listed in `/sounds/sounds.toml`, a table for each sound named after it,
+//! which are all loaded at startup:
+//!
+//! ```toml
+//! [music]
+//! path = "music.ogg"   # in /sounds
+//! category = "music"   # or "effect"
+//! looping = true       # optional, false by default
+//! volume = 0.8         # optional, multiplied with the volume of the category
+//! ```
+//!
+//! Sounds not in it are loaded the first time they're played from `/sounds/<name>.ogg` for music
+//! and `/sounds/<name>.wav` for effects.
 
-use ggez::{Context, GameResult};
-use ggez::audio::{Source, SoundData, SoundSource};
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
 
+use ggez::{Context, GameResult, GameError, filesystem};
+use ggez::audio::{Source, SpatialSource, SoundData, SoundSource};
+
+use rand::{thread_rng, Rng};
+use toml::Value;
+
+use crate::util::Point2;
+use super::config::Config;
+
+/// Most effects playing at once across all sounds, new ones not being played past it
 const EFFECTS_LIMIT: usize = 25;
+/// How many times the same effect can play over itself by default
+pub const DEFAULT_POLYPHONY: usize = 4;
+/// Volume of effects at full volume, since they're much louder than the music
+const EFFECTS_BASE_VOLUME: f32 = 0.1;
+/// Pixels from the listener positional sounds are at full volume within,
+/// getting quieter with the square of the distance beyond that
+const HEARING_SCALE: f32 = 160.;
+/// Positional sounds further away from the listener than this aren't played at all
+const MAX_HEARING: f32 = 1200.;
+/// How many times further away sounds behind walls seem, muffling them
+const OCCLUSION_DISTANCE: f32 = 2.5;
+/// Half the distance between the listener's ears, in units of `HEARING_SCALE`
+const EAR_OFFSET: f32 = 0.5;
+
+/// Where the list of sounds to load at startup is
+const MANIFEST_PATH: &str = "/sounds/sounds.toml";
+
+/// Seconds music takes to fade from one track to another
+pub const MUSIC_FADE: f32 = 2.;
+/// Seconds combat stems take to fade in once a fight starts
+const COMBAT_FADE_IN: f32 = 1.;
+/// Seconds combat stems take to fade out after a fight, longer so a lull doesn't cut them off
+const COMBAT_FADE_OUT: f32 = 4.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// What order the tracks of a playlist are played in
+pub enum PlaylistMode {
+    /// One after another in the order they're listed
+    Sequence,
+    /// A random track after each, never the same one twice in a row and never running out
+    Shuffle,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Music tracks played one after another
+pub struct Playlist {
+    pub tracks: Vec<String>,
+    pub mode: PlaylistMode,
+    /// Whether it starts over after the last track instead of going quiet
+    pub looping: bool,
+    /// Stems played along with each track in sync, only heard during combat
+    pub combat: Vec<String>,
+}
 
-fn new_source(ctx: &mut Context, data: &SoundData) -> GameResult<Source> {
+impl Default for Playlist {
+    fn default() -> Self {
+        Playlist {
+            tracks: Vec::new(),
+            mode: PlaylistMode::Sequence,
+            looping: true,
+            combat: Vec::new(),
+        }
+    }
+}
+
+impl Playlist {
+    /// A track played over and over
+    pub fn single<S: Into<String>>(track: S) -> Self {
+        Playlist {
+            tracks: vec![track.into()],
+            .. Playlist::default()
+        }
+    }
+    /// A track played once, staying quiet after it
+    pub fn once<S: Into<String>>(track: S) -> Self {
+        Playlist {
+            looping: false,
+            .. Playlist::single(track)
+        }
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+    /// Index of the track to play after track `current`, if any
+    fn next(&self, current: usize) -> Option<usize> {
+        let n = self.tracks.len();
+        match self.mode {
+            PlaylistMode::Shuffle if n > 1 => {
+                // Picks from every track but the current one
+                let i = thread_rng().gen_range(0, n - 1);
+                Some(if i >= current { i + 1 } else { i })
+            }
+            _ if current + 1 < n => Some(current + 1),
+            _ if self.looping && n > 0 => Some(0),
+            _ => None,
+        }
+    }
+    /// Index of the track to start with
+    fn first(&self) -> usize {
+        match self.mode {
+            PlaylistMode::Shuffle if !self.tracks.is_empty() => thread_rng().gen_range(0, self.tracks.len()),
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// How loud a music track is as a share of the music volume, moving towards a target
+struct Fade {
+    level: f32,
+    target: f32,
+    /// How much the level changes per second
+    rate: f32,
+}
+
+impl Fade {
+    fn new(level: f32, target: f32, duration: f32) -> Self {
+        let mut fade = Fade { level, target, rate: 0. };
+        fade.retarget(target, duration);
+        fade
+    }
+    /// Starts fading to `target` over `duration` seconds, instantly if it's 0
+    fn retarget(&mut self, target: f32, duration: f32) {
+        self.target = target;
+        if duration > 0. {
+            self.rate = 1. / duration;
+        } else {
+            self.level = target;
+        }
+    }
+    fn update(&mut self, dt: f32) {
+        let step = self.rate * dt;
+        self.level = if self.level < self.target {
+            (self.level + step).min(self.target)
+        } else {
+            (self.level - step).max(self.target)
+        };
+    }
+    /// Whether it's faded out completely
+    #[inline]
+    fn is_silent(&self) -> bool {
+        self.target <= 0. && self.level <= 0.
+    }
+}
+
+fn new_source(ctx: &mut Context, data: &SoundData, volume: f32, repeat: bool) -> GameResult<Source> {
     Source::from_data(ctx, data.clone()).map(|mut src| {
-        src.set_volume(0.1);
+        src.set_volume(volume);
+        src.set_repeat(repeat);
         src
     })
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Which volume setting a sound follows
+pub enum SoundCategory {
+    Music,
+    Effect,
+}
+
+#[derive(Debug, Clone)]
+/// A sound listed in the manifest
+pub struct SoundEntry {
+    /// Path of the file in `/sounds`
+    pub path: String,
+    pub category: SoundCategory,
+    pub looping: bool,
+    /// Multiplied with the volume of the category
+    pub volume: f32,
+}
+
+fn bad_manifest<T>(msg: &str) -> GameResult<T> {
+    Err(GameError::ResourceLoadError(format!("Bad sound manifest: {}", msg)))
+}
+
+impl SoundEntry {
+    fn from_toml(name: &str, value: &Value) -> GameResult<Self> {
+        let path = match value.get("path").and_then(Value::as_str) {
+            Some(path) => path.to_owned(),
+            None => return bad_manifest(&format!("{}.path should be a string", name)),
+        };
+        let category = match value.get("category").and_then(Value::as_str) {
+            Some("music") => SoundCategory::Music,
+            Some("effect") => SoundCategory::Effect,
+            _ => return bad_manifest(&format!("{}.category should be \"music\" or \"effect\"", name)),
+        };
+        let looping = match value.get("looping") {
+            Some(&Value::Boolean(b)) => b,
+            Some(_) => return bad_manifest(&format!("{}.looping should be a boolean", name)),
+            None => false,
+        };
+        let volume = match value.get("volume") {
+            Some(&Value::Float(n)) => n as f32,
+            Some(&Value::Integer(n)) => n as f32,
+            Some(_) => return bad_manifest(&format!("{}.volume should be a number", name)),
+            None => 1.,
+        };
+        Ok(SoundEntry {
+            path,
+            category,
+            looping,
+            volume: volume.max(0.),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// The sounds listed in `/sounds/sounds.toml`
+pub struct SoundManifest {
+    entries: HashMap<String, SoundEntry>,
+}
+
+impl SoundManifest {
+    /// Reads the manifest, which is empty if there isn't one
+    pub fn load(ctx: &mut Context) -> GameResult<Self> {
+        if !filesystem::exists(ctx, MANIFEST_PATH) {
+            return Ok(Self::default())
+        }
+        let mut src = String::new();
+        filesystem::open(ctx, MANIFEST_PATH)?.read_to_string(&mut src)?;
+        let value: Value = match src.parse() {
+            Ok(v) => v,
+            Err(e) => return bad_manifest(&e.to_string()),
+        };
+        let table = match value {
+            Value::Table(table) => table,
+            _ => return bad_manifest("should be a table of sounds"),
+        };
+        let entries = table.iter()
+            .map(|(name, entry)| SoundEntry::from_toml(name, entry).map(|e| (name.clone(), e)))
+            .collect::<GameResult<_>>()?;
+        Ok(SoundManifest {
+            entries,
+        })
+    }
+    #[inline]
+    pub fn get(&self, s: &str) -> Option<&SoundEntry> {
+        self.entries.get(s)
+    }
+    pub fn iter(&self) -> impl Iterator<Item=(&str, &SoundEntry)> {
+        self.entries.iter().map(|(name, entry)| (name.as_str(), entry))
+    }
+    /// Path of the file of a sound, `ext` being the extension of unlisted ones
+    fn path(&self, s: &str, ext: &str) -> String {
+        match self.get(s) {
+            Some(entry) => format!("/sounds/{}", entry.path),
+            None => format!("/sounds/{}.{}", s, ext),
+        }
+    }
+    /// How loud a sound is compared to the others of its category
+    fn gain(&self, s: &str) -> f32 {
+        self.get(s).map_or(1., |e| e.volume)
+    }
+    fn looping(&self, s: &str) -> bool {
+        self.get(s).map_or(false, |e| e.looping)
+    }
+}
+
+/// One effect being played
+enum Voice {
+    Flat(Source),
+    /// Played at a point in the world
+    Spatial(SpatialSource),
+}
+
+impl Voice {
+    fn play(&mut self) -> GameResult<()> {
+        match self {
+            Voice::Flat(src) => src.play(),
+            Voice::Spatial(src) => src.play(),
+        }
+    }
+    fn playing(&self) -> bool {
+        match self {
+            Voice::Flat(src) => src.playing(),
+            Voice::Spatial(src) => src.playing(),
+        }
+    }
+    fn set_volume(&mut self, volume: f32) {
+        match self {
+            Voice::Flat(src) => src.set_volume(volume),
+            Voice::Spatial(src) => src.set_volume(volume),
+        }
+    }
+    fn stop(&mut self) {
+        match self {
+            Voice::Flat(src) => src.stop(),
+            Voice::Spatial(src) => src.stop(),
+        }
+    }
+}
+
 pub struct MediaPlayer {
     data: HashMap<String, SoundData>,
+    /// How each listed sound is loaded and played
+    manifest: SoundManifest,
     // containers for sources
     music_sources: HashMap<String, Source>,
-    effects: Vec<Source>,
+    /// How loud each music track being faded in or out is
+    music_fades: HashMap<String, Fade>,
+    /// The playlist being played and which of its tracks is playing
+    playlist: Option<(Playlist, usize)>,
+    /// The combat stems playing along with the current track, kept going while silent to stay in sync
+    stems: Vec<(String, Fade)>,
+    /// Whether the combat stems should be heard
+    combat: bool,
+    /// The ambience loop playing under the music, like wind or rain, kept apart from the playlist
+    ambience: Option<(String, Fade)>,
+    /// The effects playing of each sound, oldest first
+    voices: HashMap<String, VecDeque<Voice>>,
+    /// How many voices each sound can have at once
+    polyphony: usize,
+    music_volume: f32,
+    effects_volume: f32,
+    /// Where in the world positional sounds are heard from
+    listener: Point2,
 }
 
 impl Default for MediaPlayer {
@@ -30,53 +348,328 @@ impl MediaPlayer {
     #[inline]
     pub fn new() -> Self {
         MediaPlayer {
-            effects: Vec::with_capacity(10),
+            voices: HashMap::with_capacity(24),
+            polyphony: DEFAULT_POLYPHONY,
             music_sources: HashMap::new(),
+            music_fades: HashMap::new(),
+            playlist: None,
+            stems: Vec::new(),
+            combat: false,
+            ambience: None,
             data: HashMap::with_capacity(24),
+            manifest: SoundManifest::default(),
+            music_volume: 1.,
+            effects_volume: EFFECTS_BASE_VOLUME,
+            listener: Point2::new(0., 0.),
         }
     }
+    /// Sets the volumes from the config, changing the music that is already playing too
+    pub fn set_volumes(&mut self, config: &Config) {
+        self.music_volume = config.master_volume * config.music_volume;
+        self.effects_volume = config.master_volume * config.sfx_volume * EFFECTS_BASE_VOLUME;
+        let stems = &self.stems;
+        let ambience = &self.ambience;
+        for (track, src) in &mut self.music_sources {
+            let level = self.music_fades.get(track)
+                .or_else(|| stems.iter().chain(ambience).find(|(s, _)| s == track).map(|(_, f)| f))
+                .map_or(1., |f| f.level);
+            src.set_volume(self.music_volume * self.manifest.gain(track) * level);
+        }
+        for (s, pool) in &mut self.voices {
+            for voice in pool {
+                voice.set_volume(self.effects_volume * self.manifest.gain(s));
+            }
+        }
+    }
+    /// Sets how many times the same effect can play over itself,
+    /// the oldest being cut off to make room past that
+    #[inline]
+    pub fn set_polyphony(&mut self, polyphony: usize) {
+        self.polyphony = polyphony.max(1);
+    }
+    /// Whether a sound at `pos` is close enough to the listener to be heard
+    #[inline]
+    pub fn can_hear(&self, pos: Point2) -> bool {
+        (pos - self.listener).norm() <= MAX_HEARING
+    }
+    /// Where positional sounds are heard from
+    #[inline]
+    pub fn listener(&self) -> Point2 {
+        self.listener
+    }
+    /// Moves where positional sounds are heard from, usually what the camera is looking at
+    #[inline]
+    pub fn set_listener(&mut self, pos: Point2) {
+        self.listener = pos;
+    }
+    /// Loads every sound in the manifest, so adding sounds doesn't take changing any code
+    ///
+    /// Sounds that can't be loaded are skipped, like they would be when played.
+    pub fn load_manifest(&mut self, ctx: &mut Context) -> GameResult<()> {
+        self.manifest = SoundManifest::load(ctx)?;
+        let entries: Vec<_> = self.manifest.iter().map(|(name, e)| (name.to_owned(), e.category, e.looping)).collect();
+        for (name, category, looping) in entries {
+            let loaded = match category {
+                SoundCategory::Music => self.register_music(ctx, name.as_str(), looping),
+                SoundCategory::Effect => self.add_effect(ctx, &name).map(|_| ()),
+            };
+            if let Err(e) = loaded {
+                error!("Couldn't load sound {}: {}", name, e);
+            }
+        }
+        Ok(())
+    }
+    #[inline]
+    pub fn manifest(&self) -> &SoundManifest {
+        &self.manifest
+    }
     pub fn add_effect(&mut self, ctx: &mut Context, s: &str) -> GameResult<&mut SoundData> {
-        let data = SoundData::new(ctx, format!("/sounds/{}.wav", s))?;
+        let data = SoundData::new(ctx, self.manifest.path(s, "wav"))?;
         self.data.insert(s.to_owned(), data);
         Ok(self.data.get_mut(s).unwrap())
     }
     pub fn register_music<S: Into<String>>(&mut self, ctx: &mut Context, s: S, repeat: bool) -> GameResult<()> {
         let s = s.into();
 
-        let data = SoundData::new(ctx, format!("/sounds/{}.ogg", s))?;
+        let data = SoundData::new(ctx, self.manifest.path(&s, "ogg"))?;
         self.data.insert(s.clone(), data);
 
         let cache = self.new_cache(ctx, &s, repeat)?;
         self.music_sources.insert(s, cache);
         Ok(())
     }
+    /// The sound data of an effect, loading it the first time
+    fn effect(&mut self, ctx: &mut Context, s: &str) -> GameResult<&SoundData> {
+        if self.data.contains_key(s) {
+            Ok(&self.data[s])
+        } else {
+            self.add_effect(ctx, s).map(|snd| &*snd)
+        }
+    }
+    /// Loads an effect ahead of time so playing it the first time doesn't hitch
+    pub fn preload_effect(&mut self, ctx: &mut Context, s: &str) -> GameResult<()> {
+        self.effect(ctx, s).map(|_| ())
+    }
+    /// Loads a music track ahead of time, unless it already has been
+    pub fn preload_music(&mut self, ctx: &mut Context, s: &str) -> GameResult<()> {
+        if self.music_sources.contains_key(s) {
+            return Ok(())
+        }
+        let looping = self.manifest.looping(s);
+        self.register_music(ctx, s, looping)
+    }
     pub fn play(&mut self, ctx: &mut Context, s: &str) -> GameResult<()> {
-        let snd;
+        let volume = self.effects_volume * self.manifest.gain(s);
+        let looping = self.manifest.looping(s);
 
         if let Some(music) = self.music_sources.get_mut(s) {
             return music.play();
-        } else if let Some(s) = self.data.get(s) {
-            snd = s;
-        } else {
-            snd = self.add_effect(ctx, s)?;
         }
-        let mut src = new_source(ctx, snd)?;
-        src.play()?;
+        let data = self.effect(ctx, s)?.clone();
+        let src = new_source(ctx, &data, volume, looping)?;
+        self.add_voice(s, Voice::Flat(src))
+    }
+    /// Plays an effect at a point in the world, quieter the further it is from the listener
+    /// and panned towards the side it's on
+    ///
+    /// Sounds `occluded` by something solid between them and the listener sound further away than they are
+    pub fn play_at(&mut self, ctx: &mut Context, s: &str, pos: Point2, occluded: bool) -> GameResult<()> {
+        if !self.can_hear(pos) {
+            return Ok(())
+        }
+        let mut offset = (pos - self.listener) / HEARING_SCALE;
+        if occluded {
+            offset *= OCCLUSION_DISTANCE;
+        }
+        let volume = self.effects_volume * self.manifest.gain(s);
+        let looping = self.manifest.looping(s);
+        let data = self.effect(ctx, s)?.clone();
 
+        let mut src = SpatialSource::from_data(ctx, data)?;
+        src.set_volume(volume);
+        src.set_repeat(looping);
+        src.set_ears([-EAR_OFFSET, 0., 0.], [EAR_OFFSET, 0., 0.]);
+        src.set_position([offset.x, offset.y, 0.]);
+        self.add_voice(s, Voice::Spatial(src))
+    }
+    /// Starts playing a voice of the effect `s`, stealing the oldest voice of that effect
+    /// if it's already playing as many times as it can
+    fn add_voice(&mut self, s: &str, mut voice: Voice) -> GameResult<()> {
         self.clear_effects();
 
-        if self.effects.len() < EFFECTS_LIMIT {
-            self.effects.push(src);
+        let polyphony = self.polyphony;
+        let playing: usize = self.voices.values().map(VecDeque::len).sum();
+        let pool = self.voices.entry(s.to_owned()).or_default();
+        let steal = pool.len() >= polyphony;
+        // Stealing a voice makes room for the new one, so only a new voice can go over the limit
+        if !steal && playing >= EFFECTS_LIMIT {
+            return Ok(())
+        }
+        if steal {
+            if let Some(mut oldest) = pool.pop_front() {
+                oldest.stop();
+            }
         }
+        voice.play()?;
+        pool.push_back(voice);
         Ok(())
     }
     fn clear_effects(&mut self) {
-        self.effects.retain(|src| src.playing());
+        for pool in self.voices.values_mut() {
+            pool.retain(Voice::playing);
+        }
+    }
+    /// Fades over to a playlist in `fade` seconds, unless it's already being played
+    pub fn play_music(&mut self, ctx: &mut Context, playlist: Playlist, fade: f32) -> GameResult<()> {
+        if self.playlist.as_ref().map_or(false, |(p, _)| *p == playlist) {
+            return Ok(())
+        }
+        if playlist.is_empty() {
+            return self.stop_music(ctx, fade)
+        }
+        let first = playlist.first();
+        self.playlist = Some((playlist, first));
+        self.start_track(ctx, fade)
+    }
+    /// The track of the playlist being played, if any
+    pub fn playing_track(&self) -> Option<&str> {
+        self.playlist.as_ref().map(|(list, i)| list.tracks[*i].as_str())
+    }
+    /// Fades out whatever music is playing in `fade` seconds
+    pub fn stop_music(&mut self, ctx: &mut Context, fade: f32) -> GameResult<()> {
+        self.playlist = None;
+        self.fade_out_stems(fade);
+        for fading in self.music_fades.values_mut() {
+            fading.retarget(0., fade);
+        }
+        self.update(ctx, 0.)
+    }
+    /// Fades over to another ambience loop in `fade` seconds, or out to none
+    ///
+    /// The ambience is mixed with the music but keeps going when the track changes.
+    pub fn play_ambience(&mut self, ctx: &mut Context, ambience: Option<&str>, fade: f32) -> GameResult<()> {
+        if self.ambience.as_ref().map(|(a, _)| a.as_str()) == ambience {
+            return Ok(())
+        }
+        if let Some((old, mut fading)) = self.ambience.take() {
+            fading.retarget(0., fade);
+            self.music_fades.insert(old, fading);
+        }
+        if let Some(ambience) = ambience {
+            let fading = self.fade_in(ctx, ambience, true, 1., fade)?;
+            self.ambience = Some((ambience.to_owned(), fading));
+        }
+        self.update(ctx, 0.)
+    }
+    /// Fades the combat stems in when a fight starts and out once it's over
+    pub fn set_combat(&mut self, combat: bool) {
+        if combat == self.combat {
+            return
+        }
+        self.combat = combat;
+        let (target, fade) = if combat { (1., COMBAT_FADE_IN) } else { (0., COMBAT_FADE_OUT) };
+        for (_, fading) in &mut self.stems {
+            fading.retarget(target, fade);
+        }
+    }
+    /// Lets the stems of the track that was playing fade out with it, stopping once they're silent
+    fn fade_out_stems(&mut self, fade: f32) {
+        for (stem, mut fading) in self.stems.drain(..) {
+            fading.retarget(0., fade);
+            self.music_fades.insert(stem, fading);
+        }
+    }
+    /// Starts a music track fading in to `target`, or brings it back if it's still fading out
+    fn fade_in(&mut self, ctx: &mut Context, track: &str, repeat: bool, target: f32, fade: f32) -> GameResult<Fade> {
+        if !self.music_sources.contains_key(track) {
+            self.register_music(ctx, track, repeat)?;
+        }
+        let volume = self.music_volume * self.manifest.gain(track);
+        let src = self.music_sources.get_mut(track).unwrap();
+        src.set_repeat(repeat);
+        match self.music_fades.remove(track) {
+            // Still fading out, so it's brought back without starting over
+            Some(mut fading) if src.playing() => {
+                fading.retarget(target, fade);
+                Ok(fading)
+            }
+            _ => {
+                let fading = Fade::new(0., target, fade);
+                src.set_volume(volume * fading.level);
+                src.play()?;
+                Ok(fading)
+            }
+        }
+    }
+    /// Starts the current track of the playlist, fading the others out
+    fn start_track(&mut self, ctx: &mut Context, fade: f32) -> GameResult<()> {
+        let (track, stems, repeat) = match self.playlist {
+            // A track played on its own just repeats, so it never has to be restarted
+            Some((ref list, i)) => (list.tracks[i].clone(), list.combat.clone(), list.looping && list.tracks.len() == 1),
+            None => return Ok(()),
+        };
+        self.fade_out_stems(fade);
+        for fading in self.music_fades.values_mut() {
+            fading.retarget(0., fade);
+        }
+        let fading = self.fade_in(ctx, &track, repeat, 1., fade)?;
+        self.music_fades.insert(track, fading);
+
+        let target = if self.combat { 1. } else { 0. };
+        for stem in stems {
+            let fading = self.fade_in(ctx, &stem, repeat, target, fade)?;
+            self.stems.push((stem, fading));
+        }
+        Ok(())
+    }
+    /// Fades the music for `dt` seconds and moves on to the next track of the playlist
+    /// when one finishes
+    pub fn update(&mut self, ctx: &mut Context, dt: f32) -> GameResult<()> {
+        let mut silent = Vec::new();
+        for (track, fading) in &mut self.music_fades {
+            fading.update(dt);
+            if let Some(src) = self.music_sources.get_mut(track) {
+                src.set_volume(self.music_volume * self.manifest.gain(track) * fading.level);
+            }
+            if fading.is_silent() {
+                silent.push(track.clone());
+            }
+        }
+        for track in silent {
+            self.music_fades.remove(&track);
+            self.stop(ctx, &track)?;
+        }
+        for (stem, fading) in self.stems.iter_mut().chain(&mut self.ambience) {
+            fading.update(dt);
+            if let Some(src) = self.music_sources.get_mut(stem) {
+                src.set_volume(self.music_volume * self.manifest.gain(stem) * fading.level);
+            }
+        }
+
+        let next = match self.playlist {
+            Some((ref list, i)) if !self.music_sources.get(&list.tracks[i]).map_or(false, Source::playing) => {
+                Some(list.next(i))
+            }
+            _ => None,
+        };
+        match next {
+            // The last track just ended, so there's nothing to fade out
+            Some(Some(i)) => {
+                self.playlist.as_mut().unwrap().1 = i;
+                self.start_track(ctx, 0.)
+            }
+            Some(None) => {
+                self.playlist = None;
+                Ok(())
+            }
+            None => Ok(()),
+        }
     }
     fn new_cache(&self, ctx: &mut Context, s: &str, repeat: bool) -> GameResult<Source> {
         Source::from_data(ctx, self.data[s].clone())
             .map(|mut src| {
                 src.set_repeat(repeat);
+                src.set_volume(self.music_volume * self.manifest.gain(s));
                 src
             })
     }
@@ -86,7 +679,11 @@ impl MediaPlayer {
             repeat = music_source.repeat();
             music_source.stop();
         } else {
-            panic!("{:?} can't be stopped", s);
+            // Effects just have their voices cut off, like looping ones that would go on forever
+            for mut voice in self.voices.remove(s).into_iter().flatten() {
+                voice.stop();
+            }
+            return Ok(())
         }
 
         if repeat {
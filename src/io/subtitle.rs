@@ -0,0 +1,44 @@
+use crate::util::Point2;
+
+use ggez::{GameResult, Context};
+use ggez::graphics::{self, Text, DrawParam};
+
+/// Seconds a subtitle stays on screen
+const LIFETIME: f32 = 3.;
+/// Most subtitles shown at once, the oldest being dropped to make room
+const MAX_LINES: usize = 3;
+const MARGIN: f32 = 48.;
+
+#[derive(Debug, Default)]
+/// Lines said out loud, shown centered at the bottom of the screen with the newest last
+pub struct Subtitles {
+    list: Vec<(Text, f32)>,
+}
+
+impl Subtitles {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(&mut self, text: Text) {
+        if self.list.len() >= MAX_LINES {
+            self.list.remove(0);
+        }
+        self.list.push((text, LIFETIME));
+    }
+    pub fn update(&mut self, dt: f32) {
+        for (_, time_left) in &mut self.list {
+            *time_left -= dt;
+        }
+        self.list.retain(|&(_, time_left)| time_left > 0.);
+    }
+    pub fn draw(&self, ctx: &mut Context, screen_width: f32, screen_height: f32) -> GameResult<()> {
+        let mut y = screen_height - MARGIN;
+        for (text, _) in self.list.iter().rev() {
+            let (w, h) = text.dimensions(ctx);
+            y -= h as f32;
+            graphics::draw(ctx, text, DrawParam::new().dest(Point2::new((screen_width - w as f32) / 2., y)))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use std::f32::consts::PI;
+
+use crate::util::{Point2, Vector2, GREEN, angle_to_vec, angle_from_vec, wrap_angle};
+use super::tex::Assets;
+
+use ggez::{Context, GameResult};
+use ggez::graphics::{self, Color, Mesh, DrawMode, DrawParam, WHITE};
+
+/// Pixels from the center to the entries
+const WHEEL_RADIUS: f32 = 72.;
+/// How far from the center something has to point to pick an entry
+const DEADZONE: f32 = 24.;
+const BACKGROUND: Color = Color{r:0.,g:0.,b:0.,a:0.4};
+
+/// A radial menu held open with a key, an entry being picked by pointing towards it
+///
+/// Entries are laid out clockwise from the top. Anything giving a direction can point,
+/// like the mouse relative to the center or a stick.
+pub struct Wheel<T> {
+    entries: Vec<(String, T)>,
+    center: Point2,
+    selected: Option<usize>,
+    open: bool,
+}
+
+impl<T: Copy> Wheel<T> {
+    pub fn new(entries: Vec<(String, T)>) -> Self {
+        Wheel {
+            entries,
+            center: Point2::new(0., 0.),
+            selected: None,
+            open: false,
+        }
+    }
+    /// Direction of the `i`th entry from the center
+    fn entry_dir(&self, i: usize) -> Vector2 {
+        angle_to_vec(-PI / 2. + i as f32 * 2. * PI / self.entries.len() as f32)
+    }
+    #[inline]
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+    /// Opens the wheel around a point on the screen with nothing picked
+    pub fn open(&mut self, center: Point2) {
+        self.open = !self.entries.is_empty();
+        self.center = center;
+        self.selected = None;
+    }
+    /// Moves the wheel, for when what it's around moves
+    #[inline]
+    pub fn set_center(&mut self, center: Point2) {
+        self.center = center;
+    }
+    /// Picks the entry in direction `dir`, or nothing if it's too short
+    pub fn point(&mut self, dir: Vector2) {
+        let n = self.entries.len();
+        self.selected = if n == 0 || dir.norm() < DEADZONE {
+            None
+        } else {
+            let step = 2. * PI / n as f32;
+            let from_top = wrap_angle(angle_from_vec(dir) + PI / 2.);
+            Some((from_top / step).round().rem_euclid(n as f32) as usize % n)
+        };
+    }
+    /// Picks the entry towards a point on the screen, like the cursor
+    #[inline]
+    pub fn point_at(&mut self, p: Point2) {
+        self.point(p - self.center);
+    }
+    /// The entry being pointed at
+    #[inline]
+    pub fn selected(&self) -> Option<T> {
+        self.selected.map(|i| self.entries[i].1)
+    }
+    /// Closes the wheel, returning the entry that was picked
+    pub fn close(&mut self) -> Option<T> {
+        let picked = if self.open { self.selected() } else { None };
+        self.open = false;
+        self.selected = None;
+        picked
+    }
+    pub fn draw(&self, ctx: &mut Context, assets: &Assets) -> GameResult<()> {
+        if !self.open {
+            return Ok(())
+        }
+        let background = Mesh::new_circle(ctx, DrawMode::fill(), self.center, WHEEL_RADIUS + 32., 1., BACKGROUND)?;
+        graphics::draw(ctx, &background, DrawParam::new())?;
+        for (i, (label, _)) in self.entries.iter().enumerate() {
+            let text = assets.raw_text_with(label, 20.);
+            let (w, h) = text.dimensions(ctx);
+            let dest = self.center + self.entry_dir(i) * WHEEL_RADIUS - Vector2::new(w as f32 / 2., h as f32 / 2.);
+            let color = if self.selected == Some(i) { GREEN } else { WHITE };
+            graphics::draw(ctx, &text, DrawParam::new().dest(dest).color(color))?;
+        }
+        Ok(())
+    }
+}
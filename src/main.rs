@@ -1,12 +1,16 @@
 // #![windows_subsystem = "windows"]
 //! Shooter game
 #![warn(clippy::all)]
+// Most of the game is left out without a window, so what the simulation doesn't use goes unused
+#![cfg_attr(feature = "headless", allow(dead_code))]
 
 #[macro_use]
 extern crate log;
 
+#[cfg(not(feature = "headless"))]
 use std::env::args;
 
+#[cfg(not(feature = "headless"))]
 use ggez::{
     ContextBuilder,
     conf,
@@ -18,8 +22,10 @@ pub mod ext;
 pub mod game;
 
 pub mod util {
-    use ggez::graphics::Color;
-    use ggez::{Context, input::keyboard::{self, KeyCode}};
+    use ggez::graphics::{Color, Rect};
+    use std::f32::consts::PI;
+    use crate::ext::FloatExt;
+    use ggez::{Context, timer};
     use nalgebra::base::coordinates::XY;
     pub type Vector2 = nalgebra::Vector2<f32>;
     pub type Point2 = nalgebra::Point2<f32>;
@@ -41,29 +47,139 @@ pub mod util {
         y.atan2(x)
     }
 
-    pub fn ver(ctx: &Context) -> f32 {
-        <f32>::from((keyboard::is_key_pressed(ctx, KeyCode::S) || keyboard::is_key_pressed(ctx, KeyCode::Down)) as i8 -
-            (keyboard::is_key_pressed(ctx, KeyCode::W) || keyboard::is_key_pressed(ctx, KeyCode::Up)) as i8)
+    /// Linear interpolation, giving `a` at `t = 0` and `b` at `t = 1`
+    #[inline]
+    pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
+        a + (b - a) * t
     }
-    pub fn hor(ctx: &Context) -> f32 {
-        <f32>::from((keyboard::is_key_pressed(ctx, KeyCode::D) || keyboard::is_key_pressed(ctx, KeyCode::Right)) as i8 -
-            (keyboard::is_key_pressed(ctx, KeyCode::A) || keyboard::is_key_pressed(ctx, KeyCode::Left)) as i8)
+    /// Wraps an angle into `[-π, π)`
+    #[inline]
+    pub fn wrap_angle(angle: f32) -> f32 {
+        (angle + PI).rem_euclid(2. * PI) - PI
+    }
+    /// The smallest rotation that turns angle `from` into angle `to`
+    #[inline]
+    pub fn angle_diff(from: f32, to: f32) -> f32 {
+        wrap_angle(to - from)
+    }
+    /// Whether `p` lies inside `r`
+    #[inline]
+    pub fn rect_contains(r: Rect, p: Point2) -> bool {
+        p.x >= r.x && p.x < r.x + r.w && p.y >= r.y && p.y < r.y + r.h
+    }
+    /// The overlapping part of two rectangles, if they overlap
+    pub fn rect_intersection(a: Rect, b: Rect) -> Option<Rect> {
+        let x = a.x.max(b.x);
+        let y = a.y.max(b.y);
+        let w = (a.x + a.w).min(b.x + b.w) - x;
+        let h = (a.y + a.h).min(b.y + b.h) - y;
+
+        if w > 0. && h > 0. {
+            Some(Rect::new(x, y, w, h))
+        } else {
+            None
+        }
+    }
+    /// The point in `r` closest to `p`
+    #[inline]
+    pub fn clamp_to_rect(p: Point2, r: Rect) -> Point2 {
+        Point2::new(p.x.limit(r.x, r.x + r.w), p.y.limit(r.y, r.y + r.h))
+    }
+    /// Whether a circle overlaps a rectangle
+    pub fn circle_rect(center: Point2, radius: f32, r: Rect) -> bool {
+        (clamp_to_rect(center, r) - center).norm_squared() < radius * radius
+    }
+
+    /// Seconds the last frame took, for animations that should not depend on the frame rate
+    #[inline]
+    pub fn frame_time(ctx: &Context) -> f32 {
+        timer::duration_to_f64(timer::delta(ctx)) as f32
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn close(a: f32, b: f32) -> bool {
+            (a - b).abs() < 1e-5
+        }
+
+        #[test]
+        fn wrap_angle_half_turns() {
+            // π is the end of the range left open, so both ends land on -π
+            assert!(close(wrap_angle(PI), -PI));
+            assert!(close(wrap_angle(-PI), -PI));
+        }
+        #[test]
+        fn wrap_angle_several_turns() {
+            assert!(close(wrap_angle(2. * PI + 0.5), 0.5));
+            assert!(close(wrap_angle(-4. * PI - 0.5), -0.5));
+            assert!(close(wrap_angle(3. * PI + 0.25), 0.25 - PI));
+        }
+        #[test]
+        fn angle_diff_across_seam() {
+            // Turning from just under π to just over -π is a small turn forward, not almost a whole one back
+            assert!(close(angle_diff(3., -3.), 2. * PI - 6.));
+            assert!(close(angle_diff(-3., 3.), 6. - 2. * PI));
+            assert!(close(angle_diff(0.5, 0.25), -0.25));
+        }
+        #[test]
+        fn rect_intersection_overlapping() {
+            let a = Rect::new(0., 0., 10., 10.);
+            let b = Rect::new(5., 2., 10., 4.);
+            assert_eq!(rect_intersection(a, b), Some(Rect::new(5., 2., 5., 4.)));
+            assert_eq!(rect_intersection(b, a), Some(Rect::new(5., 2., 5., 4.)));
+        }
+        #[test]
+        fn rect_intersection_touching() {
+            let a = Rect::new(0., 0., 10., 10.);
+            assert_eq!(rect_intersection(a, Rect::new(10., 0., 10., 10.)), None);
+            assert_eq!(rect_intersection(a, Rect::new(0., 10., 10., 10.)), None);
+        }
+        #[test]
+        fn rect_intersection_disjoint() {
+            let a = Rect::new(0., 0., 10., 10.);
+            assert_eq!(rect_intersection(a, Rect::new(20., 20., 5., 5.)), None);
+            assert_eq!(rect_intersection(a, Rect::new(-8., 3., 5., 5.)), None);
+        }
+        #[test]
+        fn circle_rect_corner() {
+            let r = Rect::new(0., 0., 10., 10.);
+            // Closer than the radius to both edges' lines, but not to the corner itself
+            assert!(!circle_rect(Point2::new(14., 14.), 5., r));
+            assert!(circle_rect(Point2::new(13., 13.), 5., r));
+            assert!(circle_rect(Point2::new(-3., -3.), 5., r));
+        }
     }
 }
 
+#[cfg(not(feature = "headless"))]
 use self::game::Master;
+#[cfg(not(feature = "headless"))]
+use self::io::config::Config;
 
+#[cfg(not(feature = "headless"))]
 fn main() {
     let mut args = args().skip(1);
-    let arg = args.next().unwrap_or_default();
-
-    // Set window mode
-    let window_mode = conf::WindowMode::default().dimensions(1152., 648.);
+    let mut arg = String::new();
+    let mut profile = io::save::DEFAULT_PROFILE.to_owned();
+    let mut kiosk = false;
+    while let Some(a) = args.next() {
+        match &*a {
+            "--profile" => profile = args.next().unwrap_or(profile),
+            "--kiosk" => kiosk = true,
+            _ => arg = a,
+        }
+    }
+    if !game::console::is_valid_name(&profile) {
+        eprintln!("Invalid profile name {:?}, using the default profile", profile);
+        profile = io::save::DEFAULT_PROFILE.to_owned();
+    }
 
     // Create a context (the part that runs the game loop)
     let (mut ctx, mut events) = ContextBuilder::new("cowfarg", "LFalch")
         .window_setup(conf::WindowSetup::default().title("Kofarve"))
-        .window_mode(window_mode)
+        .window_mode(Config::default().window_mode())
         .build().unwrap();
 
     #[cfg(debug_assertions)]
@@ -77,7 +193,13 @@ fn main() {
         }
     }
 
-    match Master::new(&mut ctx, &arg) {
+    // The config is in the user directory, so it can only be read once the context exists
+    let config = Config::load(&mut ctx, &profile);
+    if let Err(e) = config.apply(&mut ctx) {
+        eprintln!("Couldn't apply config {}", e);
+    }
+
+    match Master::new(&mut ctx, &arg, &profile, kiosk, config) {
         Err(e) => {
             eprintln!("Couldn't load game {}", e);
         }
@@ -86,6 +208,14 @@ fn main() {
                 Ok(_) => (),
                 Err(e) => eprintln!("Error occured: {}", e)
             }
+            if let Err(e) = game.save_config(&mut ctx) {
+                eprintln!("Couldn't save config {}", e);
+            }
         }
     }
 }
+
+#[cfg(feature = "headless")]
+fn main() {
+    eprintln!("Built headless, so there's nothing to play. The simulation is only stepped by the tests.");
+}